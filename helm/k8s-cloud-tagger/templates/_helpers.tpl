@@ -50,4 +50,82 @@ Container image reference.
 */}}
 {{- define "k8s-cloud-tagger.image" -}}
 {{- printf "%s:%s" .Values.image.repository (.Values.image.tag | default .Chart.AppVersion) }}
-{{- end }}
\ No newline at end of file
+{{- end }}
+
+{{/*
+RBAC rules for the namespaced resources the controller watches and tags.
+Granted cluster-wide, or per namespace when watch.namespaces is set.
+*/}}
+{{- define "k8s-cloud-tagger.namespacedRules" -}}
+- apiGroups: [""]
+  resources: ["persistentvolumeclaims"]
+  verbs: ["get", "list", "watch", "patch"]
+- apiGroups: [""]
+  resources: ["pods"]
+  verbs: ["get", "list", "watch"]
+- apiGroups: ["apps"]
+  resources: ["replicasets", "deployments", "statefulsets", "daemonsets"]
+  verbs: ["get"]
+- apiGroups: ["batch"]
+  resources: ["jobs", "cronjobs"]
+  verbs: ["get"]
+- apiGroups: [""]
+  resources: ["services"]
+  verbs: ["get", "list", "watch", "patch"]
+- apiGroups: ["snapshot.storage.k8s.io"]
+  resources: ["volumesnapshots"]
+  verbs: ["get", "list", "watch", "patch"]
+- apiGroups: [""]
+  resources: ["events"]
+  verbs: ["create", "patch"]
+- apiGroups: ["events.k8s.io"]
+  resources: ["events"]
+  verbs: ["create"]
+{{- end }}
+
+{{/*
+The controller's config file, shared by the ConfigMap and the validate-config hook.
+*/}}
+{{- define "k8s-cloud-tagger.config" -}}
+cloudProvider: {{ .Values.cloudProvider | quote }}
+requeue:
+  success: {{ .Values.requeue.success | quote }}
+  notReady: {{ .Values.requeue.notReady | quote }}
+  error: {{ .Values.requeue.error | quote }}
+  driftCheck: {{ .Values.requeue.driftCheck | quote }}
+leaderElection:
+  leaseName: {{ .Values.leaderElection.leaseName | default (include "k8s-cloud-tagger.fullname" .) | quote }}
+  leaseDuration: {{ .Values.leaderElection.leaseDuration | quote }}
+  renewDeadline: {{ .Values.leaderElection.renewDeadline | quote }}
+  retryPeriod: {{ .Values.leaderElection.retryPeriod | quote }}
+taggingMode: {{ .Values.taggingMode | quote }}
+dryRun: {{ .Values.dryRun }}
+{{- if eq .Values.cloudProvider "aws" }}
+aws:
+  credentials: {{ .Values.aws.credentials | quote }}
+  {{- with .Values.aws.accountRoles }}
+  accountRoles:
+    {{- toYaml . | nindent 4 }}
+  {{- end }}
+{{- end }}
+watch:
+  {{- toYaml .Values.watch | nindent 2 }}
+labelFilter:
+  {{- toYaml .Values.labelFilter | nindent 2 }}
+{{- with .Values.labelMapping }}
+labelMapping:
+  {{- toYaml . | nindent 2 }}
+{{- end }}
+{{- with .Values.namespaceLabels }}
+namespaceLabels:
+  {{- toYaml . | nindent 2 }}
+{{- end }}
+{{- with .Values.workloadLabels }}
+workloadLabels:
+  {{- toYaml . | nindent 2 }}
+{{- end }}
+{{- with .Values.defaultTags }}
+defaultTags:
+  {{- toYaml . | nindent 2 }}
+{{- end }}
+{{- end }}
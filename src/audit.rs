@@ -0,0 +1,436 @@
+//! The `audit` subcommand: report PVCs whose cloud resources are missing
+//! expected tags, without writing anything.
+//!
+//! Expected tags are worked out exactly as the reconciler would, then compared
+//! against the tags the cloud reports. Exits non-zero if any claim couldn't be
+//! audited.
+
+use crate::cloud::{CloudClient, Labels};
+use crate::error::Error;
+use crate::managed::managed_keys;
+use crate::reconciler::{Context, desired_tags, skip_reason};
+use crate::sync::{DEFAULT_CONCURRENCY, list_claims, parse_concurrency};
+use crate::traits::CloudTaggable;
+use anyhow::{anyhow, bail};
+use futures::{StreamExt, stream};
+use k8s_openapi::api::core::v1::PersistentVolumeClaim;
+use kube::{Resource, ResourceExt};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// How the report is printed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Json,
+    Csv,
+}
+
+/// Command line options for `audit`.
+#[derive(Debug, PartialEq)]
+pub struct Options {
+    pub format: Format,
+    /// How many claims are audited at once.
+    pub concurrency: usize,
+}
+
+impl Options {
+    pub fn parse(args: &[String]) -> anyhow::Result<Self> {
+        let mut opts = Self {
+            format: Format::Json,
+            concurrency: DEFAULT_CONCURRENCY,
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--format" => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| anyhow!("--format needs a value"))?;
+                    opts.format = match value.as_str() {
+                        "json" => Format::Json,
+                        "csv" => Format::Csv,
+                        _ => bail!("invalid --format '{value}', expected json or csv"),
+                    };
+                }
+                "--concurrency" => opts.concurrency = parse_concurrency(args.next())?,
+                other => bail!("unexpected argument '{other}'"),
+            }
+        }
+        Ok(opts)
+    }
+}
+
+/// Where a claim's cloud resource stands against its expected tags.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum Status {
+    /// Every expected tag is present with the expected value.
+    InSync,
+    /// Some tags are missing, extra or mismatched.
+    Drifted,
+    /// Tagging is disabled for the claim.
+    Skipped,
+    /// The claim isn't bound to a volume yet.
+    NotReady,
+    /// The claim is being deleted or can't be tagged.
+    Ignored,
+    /// The claim or its cloud resource couldn't be read.
+    Failed,
+}
+
+impl std::fmt::Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Status::InSync => write!(f, "in-sync"),
+            Status::Drifted => write!(f, "drifted"),
+            Status::Skipped => write!(f, "skipped"),
+            Status::NotReady => write!(f, "not-ready"),
+            Status::Ignored => write!(f, "ignored"),
+            Status::Failed => write!(f, "failed"),
+        }
+    }
+}
+
+/// An expected tag whose value on the cloud resource differs.
+#[derive(Debug, PartialEq, Serialize)]
+struct Mismatch {
+    expected: String,
+    actual: String,
+}
+
+/// Differences between the expected tags and those on the cloud resource.
+#[derive(Debug, Default, PartialEq, Serialize)]
+struct Drift {
+    /// Expected tags absent from the resource, with their expected values.
+    missing: Labels,
+    /// Tags the controller wrote earlier but no longer expects, with their
+    /// current values. Tags written by anyone else aren't reported.
+    extra: Labels,
+    /// Expected tags present with a different value.
+    mismatched: BTreeMap<String, Mismatch>,
+}
+
+impl Drift {
+    fn new(expected: &Labels, actual: &Labels, owned: &BTreeSet<String>) -> Self {
+        let mut drift = Self::default();
+        for (key, value) in expected {
+            match actual.get(key) {
+                None => {
+                    drift.missing.insert(key.clone(), value.clone());
+                }
+                Some(current) if current != value => {
+                    drift.mismatched.insert(
+                        key.clone(),
+                        Mismatch {
+                            expected: value.clone(),
+                            actual: current.clone(),
+                        },
+                    );
+                }
+                Some(_) => {}
+            }
+        }
+        for key in owned.iter().filter(|k| !expected.contains_key(*k)) {
+            if let Some(current) = actual.get(key) {
+                drift.extra.insert(key.clone(), current.clone());
+            }
+        }
+        drift
+    }
+
+    fn is_empty(&self) -> bool {
+        self.missing.is_empty() && self.extra.is_empty() && self.mismatched.is_empty()
+    }
+}
+
+/// The audit result for one claim.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Report {
+    namespace: String,
+    name: String,
+    status: Status,
+    provider: Option<String>,
+    resource_id: Option<String>,
+    #[serde(flatten)]
+    drift: Drift,
+    error: Option<String>,
+}
+
+/// Audit every PVC in the watch scope and print the report to stdout.
+pub async fn run<C: CloudClient>(ctx: &Context<C>, opts: &Options) -> anyhow::Result<()> {
+    let claims = list_claims(ctx).await?;
+    tracing::info!(count = claims.len(), "Auditing PersistentVolumeClaims");
+
+    let mut reports: Vec<Report> = stream::iter(&claims)
+        .map(|pvc| audit(pvc, ctx))
+        .buffer_unordered(opts.concurrency)
+        .collect()
+        .await;
+    reports.sort_by(|a, b| (&a.namespace, &a.name).cmp(&(&b.namespace, &b.name)));
+
+    match opts.format {
+        Format::Json => println!("{}", serde_json::to_string_pretty(&reports)?),
+        Format::Csv => print!("{}", csv(&reports)),
+    }
+
+    let failed = reports
+        .iter()
+        .filter(|report| report.status == Status::Failed)
+        .count();
+    if failed > 0 {
+        bail!(
+            "{failed} of {} PersistentVolumeClaim(s) could not be audited",
+            reports.len()
+        );
+    }
+    Ok(())
+}
+
+async fn audit<C: CloudClient>(pvc: &PersistentVolumeClaim, ctx: &Context<C>) -> Report {
+    let mut report = Report {
+        namespace: pvc.namespace().unwrap_or_default(),
+        name: pvc.name_any(),
+        status: Status::Failed,
+        provider: None,
+        resource_id: None,
+        drift: Drift::default(),
+        error: None,
+    };
+    match check(pvc, ctx, &mut report).await {
+        Ok(status) => report.status = status,
+        Err(e) => report.error = Some(e.to_string()),
+    }
+    report
+}
+
+/// Compare a claim's cloud resource against the tags the reconciler would
+/// give it, filling in `report` along the way.
+async fn check<C: CloudClient>(
+    pvc: &PersistentVolumeClaim,
+    ctx: &Context<C>,
+    report: &mut Report,
+) -> Result<Status, Error> {
+    let config = ctx.config.get();
+    if pvc.meta().deletion_timestamp.is_some() || !pvc.is_taggable() {
+        return Ok(Status::Ignored);
+    }
+    if skip_reason(pvc, ctx, &config).await?.is_some() {
+        return Ok(Status::Skipped);
+    }
+    let Some(cr) = pvc
+        .resolve_cloud_resource(&ctx.client, &ctx.stores, &config)
+        .await?
+    else {
+        return Ok(Status::NotReady);
+    };
+    report.provider = Some(cr.provider.to_string());
+    report.resource_id = Some(cr.resource_id.clone());
+
+    let expected = ctx.cloud.sanitise(&desired_tags(pvc, cr.labels, &config));
+    let actual = ctx
+        .cloud
+        .get_tags(cr.kind, &cr.resource_id, &cr.location)
+        .await?;
+    report.drift = Drift::new(&expected, &actual, &managed_keys(pvc));
+
+    Ok(if report.drift.is_empty() {
+        Status::InSync
+    } else {
+        Status::Drifted
+    })
+}
+
+/// Render reports as CSV, one row per tag difference. Claims without
+/// differences get a single row with an empty `issue`.
+fn csv(reports: &[Report]) -> String {
+    let mut out = String::from(
+        "namespace,name,status,provider,resource_id,issue,key,expected,actual,error\n",
+    );
+    for report in reports {
+        let mut issues: Vec<[&str; 4]> = Vec::new();
+        for (key, expected) in &report.drift.missing {
+            issues.push(["missing", key, expected, ""]);
+        }
+        for (key, actual) in &report.drift.extra {
+            issues.push(["extra", key, "", actual]);
+        }
+        for (key, mismatch) in &report.drift.mismatched {
+            issues.push(["mismatched", key, &mismatch.expected, &mismatch.actual]);
+        }
+        if issues.is_empty() {
+            issues.push(["", "", "", ""]);
+        }
+
+        let status = report.status.to_string();
+        for issue in issues {
+            let fields = [
+                report.namespace.as_str(),
+                &report.name,
+                &status,
+                report.provider.as_deref().unwrap_or_default(),
+                report.resource_id.as_deref().unwrap_or_default(),
+                issue[0],
+                issue[1],
+                issue[2],
+                issue[3],
+                report.error.as_deref().unwrap_or_default(),
+            ];
+            let fields: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+            out.push_str(&fields.join(","));
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// Quote a CSV field if it contains a delimiter, quote or line break.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn labels(pairs: &[(&str, &str)]) -> Labels {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    fn sample_reports() -> Vec<Report> {
+        vec![
+            Report {
+                namespace: "default".into(),
+                name: "data".into(),
+                status: Status::Drifted,
+                provider: Some("AWS".into()),
+                resource_id: Some("vol-abc123".into()),
+                drift: Drift::new(
+                    &labels(&[("env", "prod"), ("team", "platform")]),
+                    &labels(&[("team", "data"), ("tier", "db, primary")]),
+                    &BTreeSet::from(["tier".to_string()]),
+                ),
+                error: None,
+            },
+            Report {
+                namespace: "team-a".into(),
+                name: "db".into(),
+                status: Status::Failed,
+                provider: None,
+                resource_id: None,
+                drift: Drift::default(),
+                error: Some("Cloud API error: access denied".into()),
+            },
+        ]
+    }
+
+    #[test]
+    fn parses_options() {
+        assert_eq!(
+            Options::parse(&args(&[])).unwrap(),
+            Options {
+                format: Format::Json,
+                concurrency: DEFAULT_CONCURRENCY
+            }
+        );
+        assert_eq!(
+            Options::parse(&args(&["--format", "csv", "--concurrency", "4"])).unwrap(),
+            Options {
+                format: Format::Csv,
+                concurrency: 4
+            }
+        );
+        assert!(Options::parse(&args(&["--format"])).is_err());
+        assert!(Options::parse(&args(&["--format", "xml"])).is_err());
+        assert!(Options::parse(&args(&["--concurrency", "0"])).is_err());
+        assert!(Options::parse(&args(&["--dry-run"])).is_err());
+    }
+
+    #[test]
+    fn drift_lists_missing_extra_and_mismatched_tags() {
+        let expected = labels(&[("env", "prod"), ("team", "platform"), ("app", "web")]);
+        let actual = labels(&[
+            ("team", "data"),
+            ("app", "web"),
+            ("tier", "db"),
+            ("owner", "jane"),
+        ]);
+        let owned = BTreeSet::from(["app".to_string(), "tier".to_string()]);
+
+        let drift = Drift::new(&expected, &actual, &owned);
+
+        assert_eq!(drift.missing, labels(&[("env", "prod")]));
+        // "owner" wasn't written by the controller, so isn't extra.
+        assert_eq!(drift.extra, labels(&[("tier", "db")]));
+        assert_eq!(
+            drift.mismatched,
+            BTreeMap::from([(
+                "team".to_string(),
+                Mismatch {
+                    expected: "platform".into(),
+                    actual: "data".into()
+                }
+            )])
+        );
+        assert!(!drift.is_empty());
+        assert!(Drift::new(&expected, &expected, &owned).is_empty());
+    }
+
+    #[test]
+    fn csv_has_a_row_per_difference() {
+        assert_eq!(
+            csv(&sample_reports()),
+            "\
+namespace,name,status,provider,resource_id,issue,key,expected,actual,error
+default,data,drifted,AWS,vol-abc123,missing,env,prod,,
+default,data,drifted,AWS,vol-abc123,extra,tier,,\"db, primary\",
+default,data,drifted,AWS,vol-abc123,mismatched,team,platform,data,
+team-a,db,failed,,,,,,,Cloud API error: access denied
+"
+        );
+    }
+
+    #[test]
+    fn json_reports_each_claim() {
+        let json = serde_json::to_value(sample_reports()).unwrap();
+
+        assert_eq!(
+            json,
+            serde_json::json!([
+                {
+                    "namespace": "default",
+                    "name": "data",
+                    "status": "drifted",
+                    "provider": "AWS",
+                    "resourceId": "vol-abc123",
+                    "missing": { "env": "prod" },
+                    "extra": { "tier": "db, primary" },
+                    "mismatched": { "team": { "expected": "platform", "actual": "data" } },
+                    "error": null
+                },
+                {
+                    "namespace": "team-a",
+                    "name": "db",
+                    "status": "failed",
+                    "provider": null,
+                    "resourceId": null,
+                    "missing": {},
+                    "extra": {},
+                    "mismatched": {},
+                    "error": "Cloud API error: access denied"
+                }
+            ])
+        );
+    }
+}
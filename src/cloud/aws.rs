@@ -1,43 +1,151 @@
+mod credentials;
+
 use crate::cloud::CloudClient;
+use crate::cloud::cache::CredentialCache;
+use crate::config::AwsCredentialSource;
 use crate::error::Error;
 use crate::tls::http_client;
+use crate::traits::{Location, ResourceKind};
 use async_trait::async_trait;
 use aws_credential_types::Credentials;
 use aws_sigv4::http_request::{SignableBody, SignableRequest, SigningSettings, sign};
 use aws_sigv4::sign::v4;
 use aws_smithy_runtime_api::client::identity::Identity;
+use credentials::{
+    AwsCredentials, CredentialProvider, parse_credentials, role_session_name, sts_endpoint,
+};
 use reqwest::Client;
 use serde::Deserialize;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, Mutex};
 use std::time::SystemTime;
 
 /// An AWS EBS volume resource.
 ///
 /// The EBS CSI driver provides the volume ID (e.g., `vol-0123456789cafe0`) in the
-/// PersistentVolume's `csi.volumeHandle` field.
+/// PersistentVolume's `csi.volumeHandle` field. Handles may also be volume ARNs
+/// (e.g., `arn:aws:ec2:eu-west-2:123456789012:volume/vol-0123456789cafe0`),
+/// which carry the volume's region and account. For bare volume IDs, the PV's
+/// node affinity and account annotation are resolved to a [`Location`].
 pub struct AwsDisk {
     pub region: String,
+    /// The account owning the volume, if the ARN or location names it.
+    pub account: Option<String>,
     pub volume_id: String,
 }
 
 impl AwsDisk {
-    /// Create an AwsDisk from a volume ID.
+    /// Create an AwsDisk from a volume ID or ARN.
     ///
-    /// The EBS CSI driver returns volume IDs like `vol-0123456789cafe0`.
-    pub fn parse(volume_id: &str, region: &str) -> Option<Self> {
+    /// The region and account come from the ARN, then `location`. The region
+    /// falls back to `default_region` (`AWS_REGION`).
+    pub fn parse(
+        handle: &str,
+        location: &Location,
+        default_region: Option<&str>,
+    ) -> Result<Self, Error> {
+        let invalid = || Error::CloudApi(format!("Invalid AWS volume ID: {handle}"));
+        let (region, account, volume_id) = match handle.strip_prefix("arn:") {
+            Some(arn) => {
+                // <partition>:<service>:<region>:<account>:volume/<id>
+                let parts: Vec<&str> = arn.splitn(5, ':').collect();
+                let [_, "ec2" | "ebs", region, account, resource] = parts[..] else {
+                    return Err(invalid());
+                };
+                let volume_id = resource.strip_prefix("volume/").ok_or_else(invalid)?;
+                (
+                    Some(region).filter(|r| !r.is_empty()),
+                    Some(account).filter(|a| !a.is_empty()),
+                    volume_id,
+                )
+            }
+            None => (None, None, handle),
+        };
         if volume_id.is_empty() {
+            return Err(invalid());
+        }
+        let account = account.or(location.account.as_deref());
+        let region = region
+            .or(location.region.as_deref())
+            .or(default_region)
+            .ok_or_else(|| {
+                Error::CloudApi(format!(
+                    "No region for AWS volume {handle}: use an ARN volume handle or region node affinity, or set AWS_REGION"
+                ))
+            })?;
+
+        Ok(Self {
+            region: region.to_string(),
+            account: account.map(str::to_string),
+            volume_id: volume_id.to_string(),
+        })
+    }
+}
+
+/// Build the EC2 endpoint URL for a region.
+fn ec2_endpoint(region: &str) -> String {
+    format!("https://ec2.{region}.amazonaws.com/")
+}
+
+/// Check a CSI snapshot handle looks like an EBS snapshot ID (`snap-...`).
+fn is_ebs_snapshot_id(snapshot_id: &str) -> bool {
+    snapshot_id
+        .strip_prefix("snap-")
+        .is_some_and(|id| !id.is_empty())
+}
+
+/// An AWS load balancer: ELBv2 (Application or Network) or classic.
+///
+/// Kubernetes only records the load balancer's DNS name in the Service status,
+/// e.g. `k8s-default-web-0123456789-abcdef0123456789.elb.us-east-1.amazonaws.com`
+/// (NLB) or `k8s-default-web-0123456789-1234567890.us-east-1.elb.amazonaws.com`
+/// (ALB or classic ELB). The load balancer is looked up by name with ELBv2
+/// `DescribeLoadBalancers`, falling back to the classic API for the latter form.
+pub struct AwsLoadBalancer {
+    pub region: String,
+    pub name: String,
+    pub dns_name: String,
+    /// Whether the hostname has the `<name>-<id>.<region>.elb` form shared by
+    /// ALBs and classic ELBs. NLB hostnames are never classic.
+    pub maybe_classic: bool,
+}
+
+impl AwsLoadBalancer {
+    /// Parse a load balancer DNS name into its name and region.
+    ///
+    /// The name is the first DNS label minus its trailing `-<id>` suffix (and
+    /// the `internal-` prefix for internal load balancers).
+    pub fn parse(hostname: &str) -> Option<Self> {
+        let labels: Vec<&str> = hostname.split('.').collect();
+        let n = labels.len();
+        if n != 5 || labels[3] != "amazonaws" || labels[4] != "com" {
+            return None;
+        }
+        let (region, maybe_classic) = match (labels[1], labels[2]) {
+            ("elb", region) => (region, false),
+            (region, "elb") => (region, true),
+            _ => return None,
+        };
+        let first = labels[0].strip_prefix("internal-").unwrap_or(labels[0]);
+        let (name, _) = first.rsplit_once('-')?;
+        if name.is_empty() || region.is_empty() {
             return None;
         }
 
         Some(Self {
             region: region.to_string(),
-            volume_id: volume_id.to_string(),
+            name: name.to_string(),
+            dns_name: hostname.to_string(),
+            maybe_classic,
         })
     }
 
-    /// Build the EC2 endpoint URL for this volume's region.
+    /// Build the Elastic Load Balancing endpoint URL for this load balancer's region.
     pub fn endpoint(&self) -> String {
-        format!("https://ec2.{}.amazonaws.com/", self.region)
+        format!(
+            "https://elasticloadbalancing.{}.amazonaws.com/",
+            self.region
+        )
     }
 }
 
@@ -94,47 +202,183 @@ fn sanitise_tags(labels: &Labels) -> BTreeMap<String, String> {
     result
 }
 
-/// AWS temporary credentials from STS.
-#[derive(Debug)]
-pub struct AwsCredentials {
-    pub access_key_id: String,
-    pub secret_access_key: String,
-    pub session_token: Option<String>,
+/// XML response structure for ELBv2 DescribeLoadBalancers.
+#[derive(Debug, Deserialize)]
+struct DescribeLoadBalancersResponse {
+    #[serde(rename = "DescribeLoadBalancersResult")]
+    result: DescribeLoadBalancersResult,
+}
+
+#[derive(Debug, Deserialize)]
+struct DescribeLoadBalancersResult {
+    #[serde(rename = "LoadBalancers")]
+    load_balancers: LoadBalancers,
 }
 
-/// XML response structure for STS AssumeRoleWithWebIdentity.
 #[derive(Debug, Deserialize)]
-struct StsResponse {
-    #[serde(rename = "AssumeRoleWithWebIdentityResult")]
-    result: AssumeRoleResult,
+struct LoadBalancers {
+    #[serde(rename = "member", default)]
+    members: Vec<LoadBalancerElement>,
 }
 
 #[derive(Debug, Deserialize)]
-struct AssumeRoleResult {
-    #[serde(rename = "Credentials")]
-    credentials: CredentialsElement,
+struct LoadBalancerElement {
+    #[serde(rename = "LoadBalancerArn")]
+    arn: String,
+    #[serde(rename = "DNSName")]
+    dns_name: String,
+}
+
+/// Find the ARN of the load balancer with the given DNS name in a
+/// DescribeLoadBalancers response.
+fn parse_load_balancer_arn(xml: &str, dns_name: &str) -> Result<Option<String>, Error> {
+    let response: DescribeLoadBalancersResponse = quick_xml::de::from_str(xml).map_err(|e| {
+        Error::Aws(format!(
+            "Failed to parse DescribeLoadBalancers response: {e}"
+        ))
+    })?;
+
+    Ok(response
+        .result
+        .load_balancers
+        .members
+        .into_iter()
+        .find(|lb| lb.dns_name.eq_ignore_ascii_case(dns_name))
+        .map(|lb| lb.arn))
 }
 
+/// XML response structure for classic ELB DescribeLoadBalancers.
 #[derive(Debug, Deserialize)]
-struct CredentialsElement {
-    #[serde(rename = "AccessKeyId")]
-    access_key_id: String,
-    #[serde(rename = "SecretAccessKey")]
-    secret_access_key: String,
-    #[serde(rename = "SessionToken")]
-    session_token: String,
+struct DescribeClassicLoadBalancersResponse {
+    #[serde(rename = "DescribeLoadBalancersResult")]
+    result: DescribeClassicLoadBalancersResult,
 }
 
-/// Parse STS XML response to extract credentials.
-fn parse_credentials(xml: &str) -> Result<AwsCredentials, Error> {
-    let response: StsResponse = quick_xml::de::from_str(xml)
-        .map_err(|e| Error::Aws(format!("Failed to parse STS response: {e}")))?;
+#[derive(Debug, Deserialize)]
+struct DescribeClassicLoadBalancersResult {
+    #[serde(rename = "LoadBalancerDescriptions")]
+    descriptions: LoadBalancerDescriptions,
+}
 
-    Ok(AwsCredentials {
-        access_key_id: response.result.credentials.access_key_id,
-        secret_access_key: response.result.credentials.secret_access_key,
-        session_token: Some(response.result.credentials.session_token),
-    })
+#[derive(Debug, Deserialize)]
+struct LoadBalancerDescriptions {
+    #[serde(rename = "member", default)]
+    members: Vec<LoadBalancerDescription>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LoadBalancerDescription {
+    #[serde(rename = "LoadBalancerName")]
+    name: String,
+    #[serde(rename = "DNSName")]
+    dns_name: String,
+}
+
+/// Find the name of the classic load balancer with the given DNS name in a
+/// classic DescribeLoadBalancers response.
+fn parse_classic_load_balancer_name(xml: &str, dns_name: &str) -> Result<Option<String>, Error> {
+    let response: DescribeClassicLoadBalancersResponse =
+        quick_xml::de::from_str(xml).map_err(|e| {
+            Error::Aws(format!(
+                "Failed to parse DescribeLoadBalancers response: {e}"
+            ))
+        })?;
+
+    Ok(response
+        .result
+        .descriptions
+        .members
+        .into_iter()
+        .find(|lb| lb.dns_name.eq_ignore_ascii_case(dns_name))
+        .map(|lb| lb.name))
+}
+
+/// XML response structure for EC2 DescribeTags.
+#[derive(Debug, Deserialize)]
+struct DescribeTagsResponse {
+    #[serde(rename = "tagSet")]
+    tag_set: TagSet,
+}
+
+#[derive(Debug, Deserialize)]
+struct TagSet {
+    #[serde(rename = "item", default)]
+    items: Vec<TagSetItem>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TagSetItem {
+    key: String,
+    #[serde(default)]
+    value: String,
+}
+
+/// Parse an EC2 DescribeTags response into a tag map.
+fn parse_ec2_tags(xml: &str) -> Result<Labels, Error> {
+    let response: DescribeTagsResponse = quick_xml::de::from_str(xml)
+        .map_err(|e| Error::Aws(format!("Failed to parse DescribeTags response: {e}")))?;
+
+    Ok(response
+        .tag_set
+        .items
+        .into_iter()
+        .map(|tag| (tag.key, tag.value))
+        .collect())
+}
+
+/// XML response structure for ELBv2 and classic ELB DescribeTags, which share
+/// a shape apart from how each description names its load balancer.
+#[derive(Debug, Deserialize)]
+struct DescribeLoadBalancerTagsResponse {
+    #[serde(rename = "DescribeTagsResult")]
+    result: DescribeLoadBalancerTagsResult,
+}
+
+#[derive(Debug, Deserialize)]
+struct DescribeLoadBalancerTagsResult {
+    #[serde(rename = "TagDescriptions")]
+    tag_descriptions: TagDescriptions,
+}
+
+#[derive(Debug, Deserialize)]
+struct TagDescriptions {
+    #[serde(rename = "member", default)]
+    members: Vec<TagDescription>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TagDescription {
+    #[serde(rename = "Tags", default)]
+    tags: LoadBalancerTags,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct LoadBalancerTags {
+    #[serde(rename = "member", default)]
+    members: Vec<LoadBalancerTag>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LoadBalancerTag {
+    #[serde(rename = "Key")]
+    key: String,
+    #[serde(rename = "Value", default)]
+    value: String,
+}
+
+/// Parse an ELB DescribeTags response for a single load balancer into a tag map.
+fn parse_load_balancer_tags(xml: &str) -> Result<Labels, Error> {
+    let response: DescribeLoadBalancerTagsResponse = quick_xml::de::from_str(xml)
+        .map_err(|e| Error::Aws(format!("Failed to parse DescribeTags response: {e}")))?;
+
+    Ok(response
+        .result
+        .tag_descriptions
+        .members
+        .into_iter()
+        .flat_map(|description| description.tags.members)
+        .map(|tag| (tag.key, tag.value))
+        .collect())
 }
 
 /// Sign an AWS request using Signature Version 4.
@@ -142,6 +386,7 @@ fn sign_request(
     method: &str,
     url: &str,
     body: &str,
+    service: &str,
     region: &str,
     creds: &AwsCredentials,
 ) -> Result<Vec<(String, String)>, Error> {
@@ -159,7 +404,7 @@ fn sign_request(
     let params = v4::SigningParams::builder()
         .identity(&identity)
         .region(region)
-        .name("ec2")
+        .name(service)
         .time(SystemTime::now())
         .settings(settings)
         .build()
@@ -190,76 +435,182 @@ fn sign_request(
 
 pub struct AwsClient {
     http: Client,
-    role_arn: String,
-    token_file: String,
-    region: String,
+    /// `AWS_REGION`, for resources whose region can't be derived from their ID.
+    region: Option<String>,
+    provider: CredentialProvider,
+    credentials: CredentialCache<AwsCredentials>,
+    /// Roles assumed with the provider's credentials to reach resources in
+    /// other accounts, keyed by account ID.
+    account_roles: BTreeMap<String, String>,
+    /// Credentials for each account in `account_roles`, fetched on first use.
+    account_credentials: Mutex<BTreeMap<String, Arc<CredentialCache<AwsCredentials>>>>,
+    /// STS endpoint for `AssumeRole`, and the region its requests are signed for.
+    sts_endpoint: String,
+    sts_region: String,
     role_session_name: String,
 }
 
 impl AwsClient {
-    pub fn new() -> Result<Self, Error> {
-        let role_arn =
-            std::env::var("AWS_ROLE_ARN").map_err(|_| Error::Aws("AWS_ROLE_ARN not set".into()))?;
-        let token_file = std::env::var("AWS_WEB_IDENTITY_TOKEN_FILE")
-            .map_err(|_| Error::Aws("AWS_WEB_IDENTITY_TOKEN_FILE not set".into()))?;
-        let region =
-            std::env::var("AWS_REGION").map_err(|_| Error::Aws("AWS_REGION not set".into()))?;
-
-        // Use pod name (HOSTNAME) as session name for CloudTrail visibility
-        let role_session_name =
-            std::env::var("HOSTNAME").unwrap_or_else(|_| "k8s-cloud-tagger".to_string());
+    pub fn new(
+        source: AwsCredentialSource,
+        account_roles: BTreeMap<String, String>,
+    ) -> Result<Self, Error> {
+        let region = std::env::var("AWS_REGION")
+            .ok()
+            .filter(|region| !region.is_empty());
+        let env = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+        let provider = CredentialProvider::resolve(source, region.as_deref(), env)?;
+        tracing::info!(?source, provider = provider.name(), "Using AWS credentials");
+        if !account_roles.is_empty() {
+            tracing::info!(accounts = ?account_roles.keys(), "Assuming roles for AWS accounts");
+        }
 
         Ok(Self {
             http: http_client()?,
-            role_arn,
-            token_file,
+            sts_endpoint: sts_endpoint(&env, region.as_deref()),
+            sts_region: region.clone().unwrap_or_else(|| "us-east-1".to_string()),
+            role_session_name: role_session_name(&env),
             region,
-            role_session_name,
+            provider,
+            credentials: CredentialCache::default(),
+            account_roles,
+            account_credentials: Mutex::default(),
         })
     }
 
-    async fn credentials(&self) -> Result<AwsCredentials, Error> {
-        let token = std::fs::read_to_string(&self.token_file)
-            .map_err(|e| Error::Aws(format!("Failed to read {}: {e}", self.token_file)))?;
+    /// Credentials for signing requests, cached until shortly before they expire.
+    async fn credentials(&self) -> Result<Arc<AwsCredentials>, Error> {
+        self.credentials
+            .get(|| self.provider.fetch(&self.http))
+            .await
+    }
+
+    /// Credentials for a resource in `account`: those of its role in
+    /// `account_roles` if it has one, otherwise the provider's own.
+    async fn credentials_for(&self, account: Option<&str>) -> Result<Arc<AwsCredentials>, Error> {
+        let Some((account, role_arn)) =
+            account.and_then(|account| self.account_roles.get_key_value(account))
+        else {
+            return self.credentials().await;
+        };
+        let cache = self
+            .account_credentials
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(account.clone())
+            .or_default()
+            .clone();
+        cache.get(|| self.assume_role(role_arn)).await
+    }
+
+    /// Chain from the provider's credentials into `role_arn` with STS `AssumeRole`.
+    async fn assume_role(&self, role_arn: &str) -> Result<AwsCredentials, Error> {
+        tracing::debug!(%role_arn, "Assuming AWS role");
+        let params = [
+            ("Action".to_string(), "AssumeRole".to_string()),
+            ("Version".to_string(), "2011-06-15".to_string()),
+            ("RoleArn".to_string(), role_arn.to_string()),
+            (
+                "RoleSessionName".to_string(),
+                self.role_session_name.clone(),
+            ),
+        ];
+        let creds = self.credentials().await?;
+        let xml = self
+            .send_signed(&self.sts_endpoint, "sts", &self.sts_region, &creds, &params)
+            .await?;
+
+        parse_credentials(&xml)
+    }
+
+    /// Send a Query API request signed with the credentials for `account`
+    /// and return the response body.
+    async fn call(
+        &self,
+        url: &str,
+        service: &str,
+        region: &str,
+        account: Option<&str>,
+        params: &[(String, String)],
+    ) -> Result<String, Error> {
+        let creds = self.credentials_for(account).await?;
+        self.send_signed(url, service, region, &creds, params).await
+    }
 
-        let url = format!("https://sts.{}.amazonaws.com/", self.region);
+    /// Send a Query API request signed with `creds` and return the response body.
+    async fn send_signed(
+        &self,
+        url: &str,
+        service: &str,
+        region: &str,
+        creds: &AwsCredentials,
+        params: &[(String, String)],
+    ) -> Result<String, Error> {
+        let action = params
+            .iter()
+            .find(|(k, _)| k == "Action")
+            .map_or("<unknown>", |(_, v)| v.as_str());
+
+        let body = serde_urlencoded::to_string(params)
+            .map_err(|e| Error::Aws(format!("Failed to encode {action} request: {e}")))?;
+
+        let signed_headers = sign_request("POST", url, &body, service, region, creds)?;
 
-        let resp = self
+        let mut request = self
             .http
-            .post(&url)
+            .post(url)
             .header("Content-Type", "application/x-www-form-urlencoded")
-            .form(&[
-                ("Action", "AssumeRoleWithWebIdentity"),
-                ("Version", "2011-06-15"),
-                ("RoleArn", &self.role_arn),
-                ("RoleSessionName", &self.role_session_name),
-                ("WebIdentityToken", token.trim()),
-            ])
-            .send()
-            .await?;
+            .body(body);
 
+        for (key, value) in signed_headers {
+            request = request.header(key, value);
+        }
+
+        let resp = request.send().await?;
         let status = resp.status();
-        let body = resp.text().await?;
+        let text = resp.text().await?;
 
         if !status.is_success() {
-            return Err(Error::Aws(format!("STS error ({}): {}", status, body)));
+            return Err(Error::Aws(format!("{action} error ({}): {}", status, text)));
         }
 
-        parse_credentials(&body)
+        Ok(text)
+    }
+
+    /// Read the tags of any EC2 resource (volume, snapshot) with `DescribeTags`.
+    async fn describe_tags(
+        &self,
+        region: &str,
+        account: Option<&str>,
+        resource_id: &str,
+    ) -> Result<Labels, Error> {
+        let params = vec![
+            ("Action".to_string(), "DescribeTags".to_string()),
+            ("Version".to_string(), "2016-11-15".to_string()),
+            ("Filter.1.Name".to_string(), "resource-id".to_string()),
+            ("Filter.1.Value.1".to_string(), resource_id.to_string()),
+        ];
+
+        let xml = self
+            .call(&ec2_endpoint(region), "ec2", region, account, &params)
+            .await?;
+
+        parse_ec2_tags(&xml)
     }
 
+    /// Tag any EC2 resource (volume, snapshot) with `CreateTags`.
     async fn create_tags(
         &self,
-        disk: &AwsDisk,
+        region: &str,
+        account: Option<&str>,
+        resource_id: &str,
         tags: &BTreeMap<String, String>,
     ) -> Result<(), Error> {
-        let url = disk.endpoint();
-
         // Build query parameters using owned Strings
         let mut params: Vec<(String, String)> = vec![
             ("Action".to_string(), "CreateTags".to_string()),
             ("Version".to_string(), "2016-11-15".to_string()),
-            ("ResourceId.1".to_string(), disk.volume_id.clone()),
+            ("ResourceId.1".to_string(), resource_id.to_string()),
         ];
 
         for (i, (key, value)) in tags.iter().enumerate() {
@@ -268,61 +619,396 @@ impl AwsClient {
             params.push((format!("Tag.{n}.Value"), value.clone()));
         }
 
-        let body = serde_urlencoded::to_string(&params)
-            .map_err(|e| Error::Aws(format!("Failed to encode tags: {e}")))?;
+        self.call(&ec2_endpoint(region), "ec2", region, account, &params)
+            .await?;
 
-        let creds = self.credentials().await?;
-        let signed_headers = sign_request("POST", &url, &body, &disk.region, &creds)?;
+        tracing::debug!(
+            resource = %resource_id,
+            tags = ?tags,
+            "AWS: tags created"
+        );
 
-        let mut request = self
-            .http
-            .post(&url)
-            .header("Content-Type", "application/x-www-form-urlencoded")
-            .body(body);
+        Ok(())
+    }
 
-        for (key, value) in signed_headers {
-            request = request.header(key, value);
+    /// Remove tags from any EC2 resource with `DeleteTags`.
+    ///
+    /// Only keys are sent, so the tags are deleted whatever their value.
+    async fn delete_tags(
+        &self,
+        region: &str,
+        account: Option<&str>,
+        resource_id: &str,
+        keys: &BTreeSet<String>,
+    ) -> Result<(), Error> {
+        let mut params: Vec<(String, String)> = vec![
+            ("Action".to_string(), "DeleteTags".to_string()),
+            ("Version".to_string(), "2016-11-15".to_string()),
+            ("ResourceId.1".to_string(), resource_id.to_string()),
+        ];
+
+        for (i, key) in keys.iter().enumerate() {
+            params.push((format!("Tag.{}.Key", i + 1), key.clone()));
         }
 
-        let resp = request.send().await?;
-        let status = resp.status();
+        self.call(&ec2_endpoint(region), "ec2", region, account, &params)
+            .await?;
 
-        if !status.is_success() {
-            let text = resp.text().await?;
-            return Err(Error::Aws(format!(
-                "EC2 CreateTags error ({}): {}",
-                status, text
-            )));
+        tracing::debug!(
+            resource = %resource_id,
+            ?keys,
+            "AWS: tags deleted"
+        );
+
+        Ok(())
+    }
+
+    /// Find the load balancer with `lb`'s DNS name: by ARN in ELBv2, or by
+    /// name in the classic API when the hostname could be a classic ELB.
+    async fn load_balancer_target(
+        &self,
+        lb: &AwsLoadBalancer,
+    ) -> Result<LoadBalancerTarget, Error> {
+        if let Some(arn) = self.load_balancer_arn(lb).await? {
+            return Ok(LoadBalancerTarget::V2(arn));
+        }
+        if lb.maybe_classic
+            && let Some(name) = self.classic_load_balancer_name(lb).await?
+        {
+            return Ok(LoadBalancerTarget::Classic(name));
         }
+        Err(Error::Aws(format!(
+            "No load balancer named {} has DNS name {}",
+            lb.name, lb.dns_name
+        )))
+    }
+
+    async fn load_balancer_arn(&self, lb: &AwsLoadBalancer) -> Result<Option<String>, Error> {
+        let params = vec![
+            ("Action".to_string(), "DescribeLoadBalancers".to_string()),
+            ("Version".to_string(), ELBV2_VERSION.to_string()),
+            ("Names.member.1".to_string(), lb.name.clone()),
+        ];
+
+        let result = self
+            .call(
+                &lb.endpoint(),
+                "elasticloadbalancing",
+                &lb.region,
+                None,
+                &params,
+            )
+            .await;
+
+        match result {
+            Ok(xml) => parse_load_balancer_arn(&xml, &lb.dns_name),
+            Err(e) if is_load_balancer_not_found(&e) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn classic_load_balancer_name(
+        &self,
+        lb: &AwsLoadBalancer,
+    ) -> Result<Option<String>, Error> {
+        let params = vec![
+            ("Action".to_string(), "DescribeLoadBalancers".to_string()),
+            ("Version".to_string(), CLASSIC_ELB_VERSION.to_string()),
+            ("LoadBalancerNames.member.1".to_string(), lb.name.clone()),
+        ];
+
+        let result = self
+            .call(
+                &lb.endpoint(),
+                "elasticloadbalancing",
+                &lb.region,
+                None,
+                &params,
+            )
+            .await;
+
+        match result {
+            Ok(xml) => parse_classic_load_balancer_name(&xml, &lb.dns_name),
+            Err(e) if is_load_balancer_not_found(&e) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn describe_load_balancer_tags(&self, lb: &AwsLoadBalancer) -> Result<Labels, Error> {
+        let target = self.load_balancer_target(lb).await?;
+        let params = target.params("DescribeTags");
+
+        let xml = self
+            .call(
+                &lb.endpoint(),
+                "elasticloadbalancing",
+                &lb.region,
+                None,
+                &params,
+            )
+            .await?;
+
+        parse_load_balancer_tags(&xml)
+    }
+
+    async fn add_load_balancer_tags(
+        &self,
+        lb: &AwsLoadBalancer,
+        tags: &BTreeMap<String, String>,
+    ) -> Result<(), Error> {
+        let target = self.load_balancer_target(lb).await?;
+        let mut params = target.params("AddTags");
+
+        for (i, (key, value)) in tags.iter().enumerate() {
+            let n = i + 1;
+            params.push((format!("Tags.member.{n}.Key"), key.clone()));
+            params.push((format!("Tags.member.{n}.Value"), value.clone()));
+        }
+
+        self.call(
+            &lb.endpoint(),
+            "elasticloadbalancing",
+            &lb.region,
+            None,
+            &params,
+        )
+        .await?;
 
         tracing::debug!(
-            disk = %disk.volume_id,
+            load_balancer = %target,
             tags = ?tags,
-            "AWS: tags created"
+            "AWS: load balancer tags added"
+        );
+
+        Ok(())
+    }
+
+    async fn remove_load_balancer_tags(
+        &self,
+        lb: &AwsLoadBalancer,
+        keys: &BTreeSet<String>,
+    ) -> Result<(), Error> {
+        let target = self.load_balancer_target(lb).await?;
+        let mut params = target.params("RemoveTags");
+
+        for (i, key) in keys.iter().enumerate() {
+            let name = match target {
+                LoadBalancerTarget::V2(_) => format!("TagKeys.member.{}", i + 1),
+                LoadBalancerTarget::Classic(_) => format!("Tags.member.{}.Key", i + 1),
+            };
+            params.push((name, key.clone()));
+        }
+
+        self.call(
+            &lb.endpoint(),
+            "elasticloadbalancing",
+            &lb.region,
+            None,
+            &params,
+        )
+        .await?;
+
+        tracing::debug!(
+            load_balancer = %target,
+            ?keys,
+            "AWS: load balancer tags removed"
         );
 
         Ok(())
     }
 }
 
+/// Query API version of ELBv2 (Application and Network Load Balancers).
+const ELBV2_VERSION: &str = "2015-12-01";
+/// Query API version of classic Elastic Load Balancing.
+const CLASSIC_ELB_VERSION: &str = "2012-06-01";
+
+/// Whether an Elastic Load Balancing error says the named load balancer does
+/// not exist (`LoadBalancerNotFound` in ELBv2, `LoadBalancerNotFound` or
+/// `AccessPointNotFound` in the classic API).
+fn is_load_balancer_not_found(e: &Error) -> bool {
+    matches!(e, Error::Aws(message)
+        if message.contains("LoadBalancerNotFound") || message.contains("AccessPointNotFound"))
+}
+
+/// A load balancer found by [`AwsClient::load_balancer_target`].
+enum LoadBalancerTarget {
+    /// An ELBv2 load balancer, tagged by ARN.
+    V2(String),
+    /// A classic load balancer, tagged by name.
+    Classic(String),
+}
+
+impl LoadBalancerTarget {
+    /// The parameters of a tagging `action` naming this load balancer.
+    fn params(&self, action: &str) -> Vec<(String, String)> {
+        let (version, key, value) = match self {
+            Self::V2(arn) => (ELBV2_VERSION, "ResourceArns.member.1", arn),
+            Self::Classic(name) => (CLASSIC_ELB_VERSION, "LoadBalancerNames.member.1", name),
+        };
+        vec![
+            ("Action".to_string(), action.to_string()),
+            ("Version".to_string(), version.to_string()),
+            (key.to_string(), value.clone()),
+        ]
+    }
+}
+
+impl std::fmt::Display for LoadBalancerTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::V2(arn) => f.write_str(arn),
+            Self::Classic(name) => write!(f, "classic/{name}"),
+        }
+    }
+}
+
+/// Where a resource's tags live: EC2 for volumes and snapshots, Elastic Load
+/// Balancing for load balancers.
+enum AwsResource {
+    Ec2 {
+        region: String,
+        account: Option<String>,
+        resource_id: String,
+    },
+    LoadBalancer(AwsLoadBalancer),
+}
+
+impl AwsResource {
+    fn parse(
+        kind: ResourceKind,
+        resource_id: &str,
+        location: &Location,
+        default_region: Option<&str>,
+    ) -> Result<Self, Error> {
+        match kind {
+            ResourceKind::Disk => {
+                let disk = AwsDisk::parse(resource_id, location, default_region)?;
+                Ok(Self::Ec2 {
+                    region: disk.region,
+                    account: disk.account,
+                    resource_id: disk.volume_id,
+                })
+            }
+            ResourceKind::Snapshot => {
+                if !is_ebs_snapshot_id(resource_id) {
+                    return Err(Error::CloudApi(format!(
+                        "Invalid AWS snapshot ID: {resource_id}"
+                    )));
+                }
+                // A snapshot lives where its source volume does.
+                let region = location
+                    .region
+                    .as_deref()
+                    .or(default_region)
+                    .ok_or_else(|| {
+                        Error::CloudApi(format!(
+                            "No region for AWS snapshot {resource_id}: give its source volume region node affinity, or set AWS_REGION"
+                        ))
+                    })?;
+                Ok(Self::Ec2 {
+                    region: region.to_string(),
+                    account: location.account.clone(),
+                    resource_id: resource_id.to_string(),
+                })
+            }
+            ResourceKind::LoadBalancer => AwsLoadBalancer::parse(resource_id)
+                .map(Self::LoadBalancer)
+                .ok_or_else(|| {
+                    Error::CloudApi(format!("Invalid AWS load balancer hostname: {resource_id}"))
+                }),
+        }
+    }
+}
+
 #[async_trait]
 impl CloudClient for AwsClient {
     fn provider_name(&self) -> &'static str {
         "aws"
     }
 
-    async fn set_tags(&self, resource_id: &str, labels: &Labels) -> Result<(), Error> {
-        let disk = AwsDisk::parse(resource_id, &self.region)
-            .ok_or_else(|| Error::CloudApi(format!("Invalid AWS volume ID: {resource_id}")))?;
+    fn sanitise(&self, labels: &Labels) -> Labels {
+        sanitise_tags(labels)
+    }
 
+    async fn get_tags(
+        &self,
+        kind: ResourceKind,
+        resource_id: &str,
+        location: &Location,
+    ) -> Result<Labels, Error> {
+        match AwsResource::parse(kind, resource_id, location, self.region.as_deref())? {
+            AwsResource::Ec2 {
+                region,
+                account,
+                resource_id,
+            } => {
+                self.describe_tags(&region, account.as_deref(), &resource_id)
+                    .await
+            }
+            AwsResource::LoadBalancer(lb) => self.describe_load_balancer_tags(&lb).await,
+        }
+    }
+
+    async fn set_tags(
+        &self,
+        kind: ResourceKind,
+        resource_id: &str,
+        location: &Location,
+        labels: &Labels,
+    ) -> Result<(), Error> {
         let sanitised = sanitise_tags(labels);
 
-        self.create_tags(&disk, &sanitised).await?;
+        match AwsResource::parse(kind, resource_id, location, self.region.as_deref())? {
+            AwsResource::Ec2 {
+                region,
+                account,
+                resource_id,
+            } => {
+                self.create_tags(&region, account.as_deref(), &resource_id, &sanitised)
+                    .await?;
+            }
+            AwsResource::LoadBalancer(lb) => {
+                self.add_load_balancer_tags(&lb, &sanitised).await?;
+            }
+        }
 
         tracing::debug!(
-            disk = %resource_id,
+            %kind,
+            resource = %resource_id,
             tags = ?sanitised,
-            "AWS: tags created"
+            "AWS: tags set"
+        );
+
+        Ok(())
+    }
+
+    async fn remove_tags(
+        &self,
+        kind: ResourceKind,
+        resource_id: &str,
+        location: &Location,
+        keys: &BTreeSet<String>,
+    ) -> Result<(), Error> {
+        match AwsResource::parse(kind, resource_id, location, self.region.as_deref())? {
+            AwsResource::Ec2 {
+                region,
+                account,
+                resource_id,
+            } => {
+                self.delete_tags(&region, account.as_deref(), &resource_id, keys)
+                    .await?;
+            }
+            AwsResource::LoadBalancer(lb) => {
+                self.remove_load_balancer_tags(&lb, keys).await?;
+            }
+        }
+
+        tracing::debug!(
+            %kind,
+            resource = %resource_id,
+            ?keys,
+            "AWS: tags removed"
         );
 
         Ok(())
@@ -332,26 +1018,352 @@ impl CloudClient for AwsClient {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::cloud::stub::serve;
+    use futures::future::join_all;
+    use jiff::{SignedDuration, Timestamp};
+    use std::io::Write;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tempfile::NamedTempFile;
 
     #[test]
     fn parse_volume_id() {
-        let disk = AwsDisk::parse("vol-0123456789cafe0", "us-east-1").unwrap();
+        let disk = AwsDisk::parse(
+            "vol-0123456789cafe0",
+            &Location::default(),
+            Some("us-east-1"),
+        )
+        .unwrap();
         assert_eq!(disk.region, "us-east-1");
         assert_eq!(disk.volume_id, "vol-0123456789cafe0");
-        assert_eq!(disk.endpoint(), "https://ec2.us-east-1.amazonaws.com/");
+        assert_eq!(
+            ec2_endpoint(&disk.region),
+            "https://ec2.us-east-1.amazonaws.com/"
+        );
     }
 
     #[test]
     fn parse_different_region() {
-        let disk = AwsDisk::parse("vol-abc123", "eu-west-2").unwrap();
+        let disk = AwsDisk::parse("vol-abc123", &Location::default(), Some("eu-west-2")).unwrap();
         assert_eq!(disk.region, "eu-west-2");
         assert_eq!(disk.volume_id, "vol-abc123");
-        assert_eq!(disk.endpoint(), "https://ec2.eu-west-2.amazonaws.com/");
+        assert_eq!(
+            ec2_endpoint(&disk.region),
+            "https://ec2.eu-west-2.amazonaws.com/"
+        );
     }
 
     #[test]
     fn parse_empty_volume_id() {
-        assert!(AwsDisk::parse("", "us-east-1").is_none());
+        assert!(AwsDisk::parse("", &Location::default(), Some("us-east-1")).is_err());
+    }
+
+    #[test]
+    fn parse_volume_id_uses_its_location() {
+        let location = Location {
+            region: Some("eu-west-2".into()),
+            account: Some("111122223333".into()),
+        };
+
+        let disk = AwsDisk::parse("vol-abc123", &location, Some("us-east-1")).unwrap();
+        assert_eq!(disk.region, "eu-west-2");
+        assert_eq!(disk.account.as_deref(), Some("111122223333"));
+        assert_eq!(disk.volume_id, "vol-abc123");
+
+        let disk = AwsDisk::parse(
+            "arn:aws:ec2:ap-south-1:123456789012:volume/vol-abc123",
+            &location,
+            None,
+        )
+        .unwrap();
+        assert_eq!(disk.region, "ap-south-1");
+        assert_eq!(disk.account.as_deref(), Some("123456789012"));
+    }
+
+    #[test]
+    fn parse_snapshot_uses_its_source_volume_location() {
+        let location = Location {
+            region: Some("eu-west-2".into()),
+            account: Some("111122223333".into()),
+        };
+
+        let Ok(AwsResource::Ec2 {
+            region, account, ..
+        }) = AwsResource::parse(
+            ResourceKind::Snapshot,
+            "snap-0123456789cafe0",
+            &location,
+            None,
+        )
+        else {
+            panic!("expected an EC2 resource");
+        };
+        assert_eq!(region, "eu-west-2");
+        assert_eq!(account.as_deref(), Some("111122223333"));
+
+        let Ok(AwsResource::Ec2 {
+            region, account, ..
+        }) = AwsResource::parse(
+            ResourceKind::Snapshot,
+            "snap-0123456789cafe0",
+            &Location::default(),
+            Some("us-east-1"),
+        )
+        else {
+            panic!("expected an EC2 resource");
+        };
+        assert_eq!(region, "us-east-1");
+        assert_eq!(account, None);
+
+        assert!(
+            AwsResource::parse(
+                ResourceKind::Snapshot,
+                "snap-0123456789cafe0",
+                &Location::default(),
+                None,
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn parse_volume_arn_uses_its_region() {
+        let disk = AwsDisk::parse(
+            "arn:aws:ebs:eu-west-2:123456789012:volume/vol-0123456789cafe0",
+            &Location::default(),
+            Some("us-east-1"),
+        )
+        .unwrap();
+        assert_eq!(disk.region, "eu-west-2");
+        assert_eq!(disk.account.as_deref(), Some("123456789012"));
+        assert_eq!(disk.volume_id, "vol-0123456789cafe0");
+
+        let disk = AwsDisk::parse(
+            "arn:aws:ec2:ap-south-1::volume/vol-abc123",
+            &Location::default(),
+            None,
+        )
+        .unwrap();
+        assert_eq!(disk.region, "ap-south-1");
+        assert_eq!(disk.account, None);
+
+        let disk = AwsDisk::parse(
+            "arn:aws:ec2:::volume/vol-abc123",
+            &Location::default(),
+            Some("us-east-1"),
+        )
+        .unwrap();
+        assert_eq!(disk.region, "us-east-1", "empty ARN region falls back");
+    }
+
+    #[test]
+    fn parse_invalid_volume_arn() {
+        for handle in [
+            "arn:aws:ec2:us-east-1:123456789012:snapshot/snap-abc123",
+            "arn:aws:s3:::bucket",
+            "arn:aws:ec2:us-east-1:123456789012:volume/",
+        ] {
+            assert!(
+                AwsDisk::parse(handle, &Location::default(), Some("us-east-1")).is_err(),
+                "{handle}"
+            );
+        }
+    }
+
+    #[test]
+    fn parse_volume_without_region() {
+        let Err(err) = AwsDisk::parse("vol-abc123", &Location::default(), None) else {
+            panic!("expected an error without a region");
+        };
+        assert!(err.to_string().contains("AWS_REGION"));
+    }
+
+    #[test]
+    fn snapshot_id_validation() {
+        assert!(is_ebs_snapshot_id("snap-0123456789cafe0"));
+        assert!(!is_ebs_snapshot_id("snap-"));
+        assert!(!is_ebs_snapshot_id("vol-0123456789cafe0"));
+    }
+
+    #[test]
+    fn parse_nlb_hostname() {
+        let lb = AwsLoadBalancer::parse(
+            "k8s-default-web-0123456789-abcdef0123456789.elb.us-east-1.amazonaws.com",
+        )
+        .unwrap();
+        assert_eq!(lb.region, "us-east-1");
+        assert_eq!(lb.name, "k8s-default-web-0123456789");
+        assert_eq!(
+            lb.endpoint(),
+            "https://elasticloadbalancing.us-east-1.amazonaws.com/"
+        );
+    }
+
+    #[test]
+    fn parse_internal_alb_hostname() {
+        let lb = AwsLoadBalancer::parse(
+            "internal-k8s-default-web-0123456789-1234567890.eu-west-2.elb.amazonaws.com",
+        )
+        .unwrap();
+        assert_eq!(lb.region, "eu-west-2");
+        assert_eq!(lb.name, "k8s-default-web-0123456789");
+    }
+
+    #[test]
+    fn parse_classic_elb_hostname() {
+        let lb =
+            AwsLoadBalancer::parse("a1b2c3d4e5f6a7b8c9d0-1234567890.us-west-2.elb.amazonaws.com")
+                .unwrap();
+        assert_eq!(lb.region, "us-west-2");
+        assert_eq!(lb.name, "a1b2c3d4e5f6a7b8c9d0");
+        assert!(lb.maybe_classic);
+
+        let nlb = AwsLoadBalancer::parse(
+            "k8s-default-web-0123456789-abcdef0123456789.elb.us-east-1.amazonaws.com",
+        )
+        .unwrap();
+        assert!(!nlb.maybe_classic);
+    }
+
+    #[test]
+    fn parse_classic_load_balancer_name_matches_dns_name() {
+        let xml = r#"<DescribeLoadBalancersResponse xmlns="http://elasticloadbalancing.amazonaws.com/doc/2012-06-01/">
+            <DescribeLoadBalancersResult>
+                <LoadBalancerDescriptions>
+                    <member>
+                        <LoadBalancerName>a1b2c3d4e5f6a7b8c9d0</LoadBalancerName>
+                        <DNSName>a1b2c3d4e5f6a7b8c9d0-1234567890.us-west-2.elb.amazonaws.com</DNSName>
+                        <Scheme>internet-facing</Scheme>
+                    </member>
+                </LoadBalancerDescriptions>
+            </DescribeLoadBalancersResult>
+        </DescribeLoadBalancersResponse>"#;
+
+        let name = parse_classic_load_balancer_name(
+            xml,
+            "a1b2c3d4e5f6a7b8c9d0-1234567890.us-west-2.elb.amazonaws.com",
+        )
+        .unwrap();
+        assert_eq!(name.as_deref(), Some("a1b2c3d4e5f6a7b8c9d0"));
+
+        let missing = parse_classic_load_balancer_name(xml, "other.us-west-2.elb.amazonaws.com");
+        assert!(missing.unwrap().is_none());
+    }
+
+    #[test]
+    fn load_balancer_target_params() {
+        let classic = LoadBalancerTarget::Classic("a1b2c3d4e5f6a7b8c9d0".to_string());
+        assert_eq!(
+            classic.params("AddTags"),
+            vec![
+                ("Action".to_string(), "AddTags".to_string()),
+                ("Version".to_string(), "2012-06-01".to_string()),
+                (
+                    "LoadBalancerNames.member.1".to_string(),
+                    "a1b2c3d4e5f6a7b8c9d0".to_string()
+                ),
+            ]
+        );
+        assert!(is_load_balancer_not_found(&Error::Aws(
+            "DescribeLoadBalancers error (400 Bad Request): <Code>LoadBalancerNotFound</Code>"
+                .to_string()
+        )));
+        assert!(!is_load_balancer_not_found(&Error::Aws(
+            "DescribeLoadBalancers error (403 Forbidden): <Code>AccessDenied</Code>".to_string()
+        )));
+    }
+
+    #[test]
+    fn parse_invalid_load_balancer_hostname() {
+        assert!(AwsLoadBalancer::parse("203.0.113.10").is_none());
+        assert!(AwsLoadBalancer::parse("example.com").is_none());
+        assert!(AwsLoadBalancer::parse("web.us-east-1.example.amazonaws.com").is_none());
+        assert!(AwsLoadBalancer::parse("nohyphen.us-east-1.elb.amazonaws.com").is_none());
+    }
+
+    #[test]
+    fn parse_load_balancer_arn_matches_dns_name() {
+        let xml = r#"<DescribeLoadBalancersResponse xmlns="http://elasticloadbalancing.amazonaws.com/doc/2015-12-01/">
+            <DescribeLoadBalancersResult>
+                <LoadBalancers>
+                    <member>
+                        <LoadBalancerArn>arn:aws:elasticloadbalancing:us-east-1:123456789012:loadbalancer/net/k8s-default-web-0123456789/abcdef0123456789</LoadBalancerArn>
+                        <DNSName>k8s-default-web-0123456789-abcdef0123456789.elb.us-east-1.amazonaws.com</DNSName>
+                        <Type>network</Type>
+                    </member>
+                </LoadBalancers>
+            </DescribeLoadBalancersResult>
+        </DescribeLoadBalancersResponse>"#;
+
+        let arn = parse_load_balancer_arn(
+            xml,
+            "k8s-default-web-0123456789-abcdef0123456789.elb.us-east-1.amazonaws.com",
+        )
+        .unwrap();
+        assert_eq!(
+            arn.as_deref(),
+            Some(
+                "arn:aws:elasticloadbalancing:us-east-1:123456789012:loadbalancer/net/k8s-default-web-0123456789/abcdef0123456789"
+            )
+        );
+
+        let missing = parse_load_balancer_arn(xml, "other.elb.us-east-1.amazonaws.com").unwrap();
+        assert!(missing.is_none());
+    }
+
+    #[test]
+    fn parse_ec2_tags_from_describe_tags() {
+        let xml = r#"<DescribeTagsResponse xmlns="http://ec2.amazonaws.com/doc/2016-11-15/">
+            <requestId>7a62c49f-347e-4fc4-9331-6e8eEXAMPLE</requestId>
+            <tagSet>
+                <item>
+                    <resourceId>vol-0123456789cafe0</resourceId>
+                    <resourceType>volume</resourceType>
+                    <key>env</key>
+                    <value>production</value>
+                </item>
+                <item>
+                    <resourceId>vol-0123456789cafe0</resourceId>
+                    <resourceType>volume</resourceType>
+                    <key>empty</key>
+                    <value/>
+                </item>
+            </tagSet>
+        </DescribeTagsResponse>"#;
+
+        assert_eq!(
+            parse_ec2_tags(xml).unwrap(),
+            BTreeMap::from([
+                ("empty".to_string(), String::new()),
+                ("env".to_string(), "production".to_string()),
+            ])
+        );
+
+        let untagged = r#"<DescribeTagsResponse><tagSet/></DescribeTagsResponse>"#;
+        assert!(parse_ec2_tags(untagged).unwrap().is_empty());
+    }
+
+    #[test]
+    fn parse_load_balancer_tags_from_describe_tags() {
+        let xml = r#"<DescribeTagsResponse xmlns="http://elasticloadbalancing.amazonaws.com/doc/2015-12-01/">
+            <DescribeTagsResult>
+                <TagDescriptions>
+                    <member>
+                        <ResourceArn>arn:aws:elasticloadbalancing:us-east-1:123456789012:loadbalancer/net/k8s-default-web-0123456789/abcdef0123456789</ResourceArn>
+                        <Tags>
+                            <member>
+                                <Key>env</Key>
+                                <Value>production</Value>
+                            </member>
+                        </Tags>
+                    </member>
+                </TagDescriptions>
+            </DescribeTagsResult>
+        </DescribeTagsResponse>"#;
+
+        assert_eq!(
+            parse_load_balancer_tags(xml).unwrap(),
+            BTreeMap::from([("env".to_string(), "production".to_string())])
+        );
     }
 
     #[test]
@@ -416,61 +1428,20 @@ mod tests {
         assert_eq!(result.len(), 2);
     }
 
-    #[test]
-    fn parse_credentials_valid_xml() {
-        let xml = r#"<AssumeRoleWithWebIdentityResponse>
-            <AssumeRoleWithWebIdentityResult>
-                <Credentials>
-                    <AccessKeyId>ASIA1234567890</AccessKeyId>
-                    <SecretAccessKey>wJalrXUtnFEMI/K7MDENG/bPxRfiCY1234567890</SecretAccessKey>
-                    <SessionToken>FwoGZXIvYXdzEBYaDK1234567890</SessionToken>
-                </Credentials>
-            </AssumeRoleWithWebIdentityResult>
-        </AssumeRoleWithWebIdentityResponse>"#;
-
-        let creds = parse_credentials(xml).unwrap();
-        assert_eq!(creds.access_key_id, "ASIA1234567890");
-        assert_eq!(
-            creds.secret_access_key,
-            "wJalrXUtnFEMI/K7MDENG/bPxRfiCY1234567890"
-        );
-        assert_eq!(
-            creds.session_token,
-            Some("FwoGZXIvYXdzEBYaDK1234567890".to_string())
-        );
-    }
-
-    #[test]
-    fn parse_credentials_invalid_xml() {
-        let xml = "not valid xml";
-        assert!(parse_credentials(xml).is_err());
-    }
-
-    #[test]
-    fn parse_credentials_missing_fields() {
-        let xml = r#"<AssumeRoleWithWebIdentityResponse>
-            <AssumeRoleWithWebIdentityResult>
-                <Credentials>
-                    <AccessKeyId>ASIA123</AccessKeyId>
-                </Credentials>
-            </AssumeRoleWithWebIdentityResult>
-        </AssumeRoleWithWebIdentityResponse>"#;
-
-        assert!(parse_credentials(xml).is_err());
-    }
-
     #[test]
     fn sign_request_generates_headers() {
         let creds = AwsCredentials {
             access_key_id: "ASIA123".to_string(),
             secret_access_key: "secret123".to_string(),
             session_token: Some("token123".to_string()),
+            expiration: None,
         };
 
         let headers = sign_request(
             "POST",
             "https://ec2.us-east-1.amazonaws.com/",
             "Action=CreateTags&Version=2016-11-15",
+            "ec2",
             "us-east-1",
             &creds,
         )
@@ -482,4 +1453,190 @@ mod tests {
         assert!(header_names.contains(&"x-amz-date"));
         assert!(header_names.contains(&"x-amz-security-token"));
     }
+
+    /// A local STS stub issuing credentials valid for `lifetime`, and the
+    /// number of requests it has served.
+    async fn stub_sts(lifetime: SignedDuration) -> (String, Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let app = axum::Router::new().route(
+            "/",
+            axum::routing::post(move |body: String| {
+                let counter = counter.clone();
+                async move {
+                    assert!(body.contains("Action=AssumeRoleWithWebIdentity"));
+                    assert!(body.contains("WebIdentityToken=sa-token"));
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    // Slow enough for concurrent callers to queue up behind this refresh.
+                    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                    format!(
+                        "<AssumeRoleWithWebIdentityResponse>
+                            <AssumeRoleWithWebIdentityResult>
+                                <Credentials>
+                                    <AccessKeyId>ASIA1234567890</AccessKeyId>
+                                    <SecretAccessKey>secret</SecretAccessKey>
+                                    <SessionToken>session</SessionToken>
+                                    <Expiration>{}</Expiration>
+                                </Credentials>
+                            </AssumeRoleWithWebIdentityResult>
+                        </AssumeRoleWithWebIdentityResponse>",
+                        Timestamp::now() + lifetime
+                    )
+                }
+            }),
+        );
+        (format!("{}/", serve(app).await), calls)
+    }
+
+    fn sts_client(sts_endpoint: String, token_file: &NamedTempFile) -> AwsClient {
+        AwsClient {
+            http: http_client().unwrap(),
+            region: Some("us-east-1".into()),
+            provider: CredentialProvider::WebIdentity {
+                role_arn: "arn:aws:iam::123456789012:role/k8s-cloud-tagger".into(),
+                token_file: token_file.path().display().to_string(),
+                role_session_name: "test".into(),
+                sts_endpoint: sts_endpoint.clone(),
+            },
+            credentials: CredentialCache::default(),
+            account_roles: BTreeMap::new(),
+            account_credentials: Mutex::default(),
+            sts_endpoint,
+            sts_region: "us-east-1".into(),
+            role_session_name: "test".into(),
+        }
+    }
+
+    fn token_file() -> NamedTempFile {
+        let mut file = NamedTempFile::new().unwrap();
+        write!(file, "sa-token").unwrap();
+        file
+    }
+
+    #[tokio::test]
+    async fn concurrent_requests_share_one_sts_call() {
+        let (endpoint, calls) = stub_sts(SignedDuration::from_hours(1)).await;
+        let token = token_file();
+        let client = sts_client(endpoint, &token);
+
+        let results = join_all((0..10).map(|_| client.credentials())).await;
+        assert!(results.iter().all(Result::is_ok));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        client.credentials().await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 1, "cached until near expiry");
+    }
+
+    #[tokio::test]
+    async fn refreshes_credentials_near_expiry() {
+        let (endpoint, calls) = stub_sts(SignedDuration::from_mins(1)).await;
+        let token = token_file();
+        let client = sts_client(endpoint, &token);
+
+        client.credentials().await.unwrap();
+        client.credentials().await.unwrap();
+
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    /// A local STS stub answering `AssumeRoleWithWebIdentity` with the base
+    /// access key `ASIABASE`, and `AssumeRole` with `ASIA<account>`. Records
+    /// each request's action, role and `Authorization` header.
+    async fn stub_chained_sts() -> (String, Arc<Mutex<Vec<(String, String, String)>>>) {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+        let app = axum::Router::new().route(
+            "/",
+            axum::routing::post(move |headers: axum::http::HeaderMap, body: String| {
+                let recorded = recorded.clone();
+                async move {
+                    let params: BTreeMap<String, String> =
+                        serde_urlencoded::from_str(&body).unwrap();
+                    let action = params["Action"].clone();
+                    let role_arn = params["RoleArn"].clone();
+                    let authorization = headers
+                        .get("authorization")
+                        .map(|v| v.to_str().unwrap().to_string())
+                        .unwrap_or_default();
+                    recorded.lock().unwrap().push((
+                        action.clone(),
+                        role_arn.clone(),
+                        authorization,
+                    ));
+                    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+
+                    let access_key_id = match action.as_str() {
+                        "AssumeRole" => format!("ASIA{}", role_arn.split(':').nth(4).unwrap()),
+                        _ => "ASIABASE".to_string(),
+                    };
+                    format!(
+                        "<{action}Response>
+                            <{action}Result>
+                                <Credentials>
+                                    <AccessKeyId>{access_key_id}</AccessKeyId>
+                                    <SecretAccessKey>secret</SecretAccessKey>
+                                    <SessionToken>session</SessionToken>
+                                    <Expiration>{}</Expiration>
+                                </Credentials>
+                            </{action}Result>
+                        </{action}Response>",
+                        Timestamp::now() + SignedDuration::from_hours(1)
+                    )
+                }
+            }),
+        );
+        (format!("{}/", serve(app).await), requests)
+    }
+
+    #[tokio::test]
+    async fn assumes_account_roles_with_cached_credentials_per_account() {
+        let (endpoint, requests) = stub_chained_sts().await;
+        let token = token_file();
+        let mut client = sts_client(endpoint, &token);
+        client.account_roles = BTreeMap::from([
+            (
+                "111122223333".to_string(),
+                "arn:aws:iam::111122223333:role/tagger".to_string(),
+            ),
+            (
+                "444455556666".to_string(),
+                "arn:aws:iam::444455556666:role/tagger".to_string(),
+            ),
+        ]);
+
+        let shared = join_all((0..5).map(|_| client.credentials_for(Some("111122223333")))).await;
+        for creds in shared {
+            assert_eq!(creds.unwrap().access_key_id, "ASIA111122223333");
+        }
+        let other = client.credentials_for(Some("444455556666")).await.unwrap();
+        assert_eq!(other.access_key_id, "ASIA444455556666");
+        let unlisted = client.credentials_for(Some("999999999999")).await.unwrap();
+        assert_eq!(unlisted.access_key_id, "ASIABASE");
+        let own = client.credentials_for(None).await.unwrap();
+        assert_eq!(own.access_key_id, "ASIABASE");
+        client.credentials_for(Some("111122223333")).await.unwrap();
+
+        let requests = requests.lock().unwrap();
+        let actions: Vec<(&str, &str)> = requests
+            .iter()
+            .map(|(action, role, _)| (action.as_str(), role.as_str()))
+            .collect();
+        assert_eq!(
+            actions,
+            [
+                (
+                    "AssumeRoleWithWebIdentity",
+                    "arn:aws:iam::123456789012:role/k8s-cloud-tagger"
+                ),
+                ("AssumeRole", "arn:aws:iam::111122223333:role/tagger"),
+                ("AssumeRole", "arn:aws:iam::444455556666:role/tagger"),
+            ]
+        );
+        let (_, _, authorization) = &requests[1];
+        assert!(
+            authorization.contains("Credential=ASIABASE/")
+                && authorization.contains("/us-east-1/sts/aws4_request"),
+            "AssumeRole is signed with the base credentials: {authorization}"
+        );
+    }
 }
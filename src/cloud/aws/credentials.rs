@@ -0,0 +1,757 @@
+//! Where [`AwsClient`](super::AwsClient) gets credentials from, following the
+//! AWS SDKs' default credential chain.
+
+use crate::cloud::cache::Expiring;
+use crate::config::AwsCredentialSource;
+use crate::error::Error;
+use jiff::Timestamp;
+use reqwest::{Client, Response};
+use serde::Deserialize;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::time::Duration;
+
+/// The ECS agent's credentials endpoint, which relative URIs are resolved against.
+const ECS_CONTAINER_HOST: &str = "http://169.254.170.2";
+
+/// Hosts a container credentials URI may use over plain HTTP: the ECS agent
+/// and the EKS Pod Identity agent. Loopback addresses are allowed too.
+const CONTAINER_HOSTS: [IpAddr; 3] = [
+    IpAddr::V4(Ipv4Addr::new(169, 254, 170, 2)),
+    IpAddr::V4(Ipv4Addr::new(169, 254, 170, 23)),
+    IpAddr::V6(Ipv6Addr::new(0xfd00, 0xec2, 0, 0, 0, 0, 0, 0x23)),
+];
+
+const IMDS_ENDPOINT: &str = "http://169.254.169.254";
+
+/// How long an IMDSv2 session token is requested for.
+const IMDS_TOKEN_TTL_SECONDS: &str = "21600";
+
+/// IMDS answers quickly or not at all, e.g. when the hop limit blocks pods.
+const IMDS_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// AWS credentials, temporary unless read from the environment.
+#[derive(Clone, Debug)]
+pub struct AwsCredentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub session_token: Option<String>,
+    pub expiration: Option<Timestamp>,
+}
+
+impl Expiring for AwsCredentials {
+    fn expires_at(&self) -> Option<Timestamp> {
+        self.expiration
+    }
+}
+
+/// The authorization token sent to a container credentials endpoint.
+#[derive(Debug, PartialEq)]
+pub enum ContainerToken {
+    /// A file holding the token, re-read on every request as it is rotated.
+    File(String),
+    Value(String),
+}
+
+impl ContainerToken {
+    fn read(&self) -> Result<String, Error> {
+        match self {
+            Self::File(path) => std::fs::read_to_string(path)
+                .map(|token| token.trim().to_string())
+                .map_err(|e| Error::Aws(format!("Failed to read {path}: {e}"))),
+            Self::Value(token) => Ok(token.clone()),
+        }
+    }
+}
+
+/// A source of AWS credentials.
+#[derive(Debug)]
+pub enum CredentialProvider {
+    /// Static keys from `AWS_ACCESS_KEY_ID` and `AWS_SECRET_ACCESS_KEY`.
+    Environment(AwsCredentials),
+    /// IRSA: the service account token exchanged with STS
+    /// `AssumeRoleWithWebIdentity`.
+    WebIdentity {
+        role_arn: String,
+        token_file: String,
+        role_session_name: String,
+        /// STS endpoint, overridable with `AWS_ENDPOINT_URL_STS` (e.g. for a VPC endpoint).
+        sts_endpoint: String,
+    },
+    /// An HTTP endpoint serving credentials as JSON: the EKS Pod Identity
+    /// agent or the ECS agent.
+    Container {
+        uri: String,
+        token: Option<ContainerToken>,
+    },
+    /// The EC2 instance profile, read from IMDSv2.
+    InstanceProfile { endpoint: String },
+}
+
+impl CredentialProvider {
+    /// Choose the provider for `source` from the environment variables
+    /// returned by `env`. `Auto` tries static keys, IRSA, container
+    /// credentials and then the instance profile, like the AWS SDKs.
+    pub fn resolve(
+        source: AwsCredentialSource,
+        region: Option<&str>,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, Error> {
+        match source {
+            AwsCredentialSource::Auto => {
+                if let Some(provider) =
+                    Self::environment(&env).or_else(|| Self::web_identity(&env, region))
+                {
+                    return Ok(provider);
+                }
+                if let Some(provider) = Self::container_from_env(&env)? {
+                    return Ok(provider);
+                }
+                Self::instance_profile(&env).ok_or_else(|| {
+                    Error::Aws(
+                        "No AWS credentials found and AWS_EC2_METADATA_DISABLED is set".into(),
+                    )
+                })
+            }
+            AwsCredentialSource::Environment => Self::environment(&env).ok_or_else(|| {
+                Error::Aws("AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY not set".into())
+            }),
+            AwsCredentialSource::WebIdentity => {
+                Self::web_identity(&env, region).ok_or_else(|| {
+                    Error::Aws("AWS_ROLE_ARN and AWS_WEB_IDENTITY_TOKEN_FILE not set".into())
+                })
+            }
+            AwsCredentialSource::PodIdentity => {
+                let uri = require(&env, "AWS_CONTAINER_CREDENTIALS_FULL_URI")?;
+                let token_file = require(&env, "AWS_CONTAINER_AUTHORIZATION_TOKEN_FILE")?;
+                Self::container(uri, Some(ContainerToken::File(token_file)))
+            }
+            AwsCredentialSource::Ecs => Self::container_from_env(&env)?.ok_or_else(|| {
+                Error::Aws(
+                    "AWS_CONTAINER_CREDENTIALS_RELATIVE_URI or AWS_CONTAINER_CREDENTIALS_FULL_URI not set"
+                        .into(),
+                )
+            }),
+            AwsCredentialSource::InstanceProfile => Ok(Self::InstanceProfile {
+                endpoint: imds_endpoint(&env),
+            }),
+        }
+    }
+
+    /// A short name for logs.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Environment(_) => "environment",
+            Self::WebIdentity { .. } => "webIdentity",
+            Self::Container { .. } => "container",
+            Self::InstanceProfile { .. } => "instanceProfile",
+        }
+    }
+
+    fn environment(env: &impl Fn(&str) -> Option<String>) -> Option<Self> {
+        Some(Self::Environment(AwsCredentials {
+            access_key_id: env("AWS_ACCESS_KEY_ID")?,
+            secret_access_key: env("AWS_SECRET_ACCESS_KEY")?,
+            session_token: env("AWS_SESSION_TOKEN"),
+            expiration: None,
+        }))
+    }
+
+    fn web_identity(env: &impl Fn(&str) -> Option<String>, region: Option<&str>) -> Option<Self> {
+        Some(Self::WebIdentity {
+            role_arn: env("AWS_ROLE_ARN")?,
+            token_file: env("AWS_WEB_IDENTITY_TOKEN_FILE")?,
+            role_session_name: role_session_name(env),
+            sts_endpoint: sts_endpoint(env, region),
+        })
+    }
+
+    /// Container credentials, if the ECS or EKS Pod Identity agent's variables are set.
+    fn container_from_env(env: &impl Fn(&str) -> Option<String>) -> Result<Option<Self>, Error> {
+        let uri = match (
+            env("AWS_CONTAINER_CREDENTIALS_RELATIVE_URI"),
+            env("AWS_CONTAINER_CREDENTIALS_FULL_URI"),
+        ) {
+            (Some(relative), _) => format!("{ECS_CONTAINER_HOST}{relative}"),
+            (None, Some(full)) => full,
+            (None, None) => return Ok(None),
+        };
+        let token = env("AWS_CONTAINER_AUTHORIZATION_TOKEN_FILE")
+            .map(ContainerToken::File)
+            .or_else(|| env("AWS_CONTAINER_AUTHORIZATION_TOKEN").map(ContainerToken::Value));
+        Self::container(uri, token).map(Some)
+    }
+
+    fn container(uri: String, token: Option<ContainerToken>) -> Result<Self, Error> {
+        check_container_uri(&uri)?;
+        Ok(Self::Container { uri, token })
+    }
+
+    fn instance_profile(env: &impl Fn(&str) -> Option<String>) -> Option<Self> {
+        if env("AWS_EC2_METADATA_DISABLED").is_some_and(|v| v.eq_ignore_ascii_case("true")) {
+            return None;
+        }
+        Some(Self::InstanceProfile {
+            endpoint: imds_endpoint(env),
+        })
+    }
+
+    /// Fetch credentials from this provider.
+    pub async fn fetch(&self, http: &Client) -> Result<AwsCredentials, Error> {
+        match self {
+            Self::Environment(credentials) => Ok(credentials.clone()),
+            Self::WebIdentity {
+                role_arn,
+                token_file,
+                role_session_name,
+                sts_endpoint,
+            } => {
+                assume_role_with_web_identity(
+                    http,
+                    role_arn,
+                    token_file,
+                    role_session_name,
+                    sts_endpoint,
+                )
+                .await
+            }
+            Self::Container { uri, token } => {
+                container_credentials(http, uri, token.as_ref()).await
+            }
+            Self::InstanceProfile { endpoint } => {
+                instance_profile_credentials(http, endpoint).await
+            }
+        }
+    }
+}
+
+/// The STS endpoint: `AWS_ENDPOINT_URL_STS` (e.g. for a VPC endpoint), or
+/// the regional endpoint, or the global one without a region.
+pub fn sts_endpoint(env: &impl Fn(&str) -> Option<String>, region: Option<&str>) -> String {
+    env("AWS_ENDPOINT_URL_STS").unwrap_or_else(|| match region {
+        Some(region) => format!("https://sts.{region}.amazonaws.com/"),
+        None => "https://sts.amazonaws.com/".to_string(),
+    })
+}
+
+/// Use pod name (HOSTNAME) as session name for CloudTrail visibility
+pub fn role_session_name(env: &impl Fn(&str) -> Option<String>) -> String {
+    env("HOSTNAME").unwrap_or_else(|| "k8s-cloud-tagger".to_string())
+}
+
+fn require(env: &impl Fn(&str) -> Option<String>, name: &str) -> Result<String, Error> {
+    env(name).ok_or_else(|| Error::Aws(format!("{name} not set")))
+}
+
+fn imds_endpoint(env: &impl Fn(&str) -> Option<String>) -> String {
+    env("AWS_EC2_METADATA_SERVICE_ENDPOINT")
+        .unwrap_or_else(|| IMDS_ENDPOINT.to_string())
+        .trim_end_matches('/')
+        .to_string()
+}
+
+/// Credentials are secrets, so a full container credentials URI must use
+/// HTTPS unless it points at a loopback address or one of the AWS agents.
+fn check_container_uri(uri: &str) -> Result<(), Error> {
+    let url = reqwest::Url::parse(uri)
+        .map_err(|e| Error::Aws(format!("Invalid container credentials URI '{uri}': {e}")))?;
+    let host = url
+        .host_str()
+        .unwrap_or_default()
+        .trim_start_matches('[')
+        .trim_end_matches(']');
+    let allowed = match url.scheme() {
+        "https" => true,
+        "http" => {
+            host == "localhost"
+                || host
+                    .parse::<IpAddr>()
+                    .is_ok_and(|ip| ip.is_loopback() || CONTAINER_HOSTS.contains(&ip))
+        }
+        _ => false,
+    };
+    if !allowed {
+        return Err(Error::Aws(format!(
+            "Container credentials URI '{uri}' must use HTTPS or a loopback or AWS agent address"
+        )));
+    }
+    Ok(())
+}
+
+/// The response body, or an error naming `what` if the request failed.
+async fn response_text(resp: Response, what: &str) -> Result<String, Error> {
+    let status = resp.status();
+    let body = resp.text().await?;
+    if !status.is_success() {
+        return Err(Error::Aws(format!("{what} error ({status}): {body}")));
+    }
+    Ok(body)
+}
+
+/// Exchange the service account token for role credentials with STS.
+async fn assume_role_with_web_identity(
+    http: &Client,
+    role_arn: &str,
+    token_file: &str,
+    role_session_name: &str,
+    sts_endpoint: &str,
+) -> Result<AwsCredentials, Error> {
+    // Read on every refresh, as the kubelet rotates the token.
+    let token = std::fs::read_to_string(token_file)
+        .map_err(|e| Error::Aws(format!("Failed to read {token_file}: {e}")))?;
+
+    tracing::debug!(%role_arn, "Fetching AWS credentials from STS");
+    let resp = http
+        .post(sts_endpoint)
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .form(&[
+            ("Action", "AssumeRoleWithWebIdentity"),
+            ("Version", "2011-06-15"),
+            ("RoleArn", role_arn),
+            ("RoleSessionName", role_session_name),
+            ("WebIdentityToken", token.trim()),
+        ])
+        .send()
+        .await?;
+
+    parse_credentials(&response_text(resp, "STS").await?)
+}
+
+/// Fetch credentials from the EKS Pod Identity or ECS agent.
+async fn container_credentials(
+    http: &Client,
+    uri: &str,
+    token: Option<&ContainerToken>,
+) -> Result<AwsCredentials, Error> {
+    tracing::debug!(%uri, "Fetching AWS credentials from the container endpoint");
+    let mut request = http.get(uri);
+    if let Some(token) = token {
+        request = request.header("Authorization", token.read()?);
+    }
+    let resp = request.send().await?;
+
+    parse_json_credentials(&response_text(resp, "Container credentials").await?)
+}
+
+/// Fetch the instance profile's credentials from IMDSv2: get a session token,
+/// look up the role attached to the instance, then its credentials.
+async fn instance_profile_credentials(
+    http: &Client,
+    endpoint: &str,
+) -> Result<AwsCredentials, Error> {
+    tracing::debug!(%endpoint, "Fetching AWS credentials from the instance metadata service");
+    let resp = http
+        .put(format!("{endpoint}/latest/api/token"))
+        .header(
+            "X-aws-ec2-metadata-token-ttl-seconds",
+            IMDS_TOKEN_TTL_SECONDS,
+        )
+        .timeout(IMDS_TIMEOUT)
+        .send()
+        .await?;
+    let token = response_text(resp, "IMDS token").await?;
+
+    let credentials_path = format!("{endpoint}/latest/meta-data/iam/security-credentials/");
+    let resp = http
+        .get(&credentials_path)
+        .header("X-aws-ec2-metadata-token", &token)
+        .timeout(IMDS_TIMEOUT)
+        .send()
+        .await?;
+    let roles = response_text(resp, "IMDS").await?;
+    let role = roles
+        .lines()
+        .map(str::trim)
+        .find(|role| !role.is_empty())
+        .ok_or_else(|| Error::Aws("No IAM instance profile attached to this instance".into()))?;
+
+    let resp = http
+        .get(format!("{credentials_path}{role}"))
+        .header("X-aws-ec2-metadata-token", &token)
+        .timeout(IMDS_TIMEOUT)
+        .send()
+        .await?;
+
+    parse_json_credentials(&response_text(resp, "IMDS").await?)
+}
+
+/// XML response structure for STS AssumeRoleWithWebIdentity and AssumeRole.
+#[derive(Debug, Deserialize)]
+struct StsResponse {
+    #[serde(rename = "AssumeRoleWithWebIdentityResult", alias = "AssumeRoleResult")]
+    result: AssumeRoleResult,
+}
+
+#[derive(Debug, Deserialize)]
+struct AssumeRoleResult {
+    #[serde(rename = "Credentials")]
+    credentials: CredentialsElement,
+}
+
+#[derive(Debug, Deserialize)]
+struct CredentialsElement {
+    #[serde(rename = "AccessKeyId")]
+    access_key_id: String,
+    #[serde(rename = "SecretAccessKey")]
+    secret_access_key: String,
+    #[serde(rename = "SessionToken")]
+    session_token: String,
+    #[serde(rename = "Expiration")]
+    expiration: Option<Timestamp>,
+}
+
+/// Parse STS XML response to extract credentials.
+pub fn parse_credentials(xml: &str) -> Result<AwsCredentials, Error> {
+    let response: StsResponse = quick_xml::de::from_str(xml)
+        .map_err(|e| Error::Aws(format!("Failed to parse STS response: {e}")))?;
+
+    Ok(AwsCredentials {
+        access_key_id: response.result.credentials.access_key_id,
+        secret_access_key: response.result.credentials.secret_access_key,
+        session_token: Some(response.result.credentials.session_token),
+        expiration: response.result.credentials.expiration,
+    })
+}
+
+/// JSON credentials served by the container agents and IMDS.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct JsonCredentials {
+    access_key_id: String,
+    secret_access_key: String,
+    token: Option<String>,
+    expiration: Option<Timestamp>,
+}
+
+fn parse_json_credentials(json: &str) -> Result<AwsCredentials, Error> {
+    let credentials: JsonCredentials = serde_json::from_str(json)
+        .map_err(|e| Error::Aws(format!("Failed to parse credentials response: {e}")))?;
+
+    Ok(AwsCredentials {
+        access_key_id: credentials.access_key_id,
+        secret_access_key: credentials.secret_access_key,
+        session_token: credentials.token,
+        expiration: credentials.expiration,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cloud::stub::serve;
+    use crate::tls::http_client;
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::routing::{get, put};
+    use std::collections::HashMap;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |name| vars.get(name).cloned()
+    }
+
+    fn resolve(
+        source: AwsCredentialSource,
+        vars: &[(&str, &str)],
+    ) -> Result<CredentialProvider, Error> {
+        CredentialProvider::resolve(source, Some("us-east-1"), env(vars))
+    }
+
+    const JSON_CREDENTIALS: &str = r#"{
+        "AccessKeyId": "ASIA1234567890",
+        "SecretAccessKey": "secret",
+        "Token": "session",
+        "Expiration": "2026-10-17T13:34:41Z"
+    }"#;
+
+    #[test]
+    fn auto_prefers_environment_keys() {
+        let provider = resolve(
+            AwsCredentialSource::Auto,
+            &[
+                ("AWS_ACCESS_KEY_ID", "AKIA123"),
+                ("AWS_SECRET_ACCESS_KEY", "secret"),
+                ("AWS_ROLE_ARN", "arn:aws:iam::123456789012:role/tagger"),
+                ("AWS_WEB_IDENTITY_TOKEN_FILE", "/var/run/token"),
+            ],
+        )
+        .unwrap();
+
+        let CredentialProvider::Environment(credentials) = provider else {
+            panic!("expected environment credentials, got {provider:?}");
+        };
+        assert_eq!(credentials.access_key_id, "AKIA123");
+        assert_eq!(credentials.session_token, None);
+    }
+
+    #[test]
+    fn auto_falls_through_the_chain() {
+        let web_identity = [
+            ("AWS_ROLE_ARN", "arn:aws:iam::123456789012:role/tagger"),
+            ("AWS_WEB_IDENTITY_TOKEN_FILE", "/var/run/token"),
+            (
+                "AWS_CONTAINER_CREDENTIALS_RELATIVE_URI",
+                "/v2/credentials/id",
+            ),
+        ];
+        assert_eq!(
+            resolve(AwsCredentialSource::Auto, &web_identity)
+                .unwrap()
+                .name(),
+            "webIdentity"
+        );
+
+        let provider = resolve(
+            AwsCredentialSource::Auto,
+            &[
+                ("AWS_ACCESS_KEY_ID", "AKIA123"),
+                (
+                    "AWS_CONTAINER_CREDENTIALS_RELATIVE_URI",
+                    "/v2/credentials/id",
+                ),
+            ],
+        )
+        .unwrap();
+        assert!(matches!(
+            provider,
+            CredentialProvider::Container { ref uri, token: None }
+                if uri == "http://169.254.170.2/v2/credentials/id"
+        ));
+
+        let provider = resolve(AwsCredentialSource::Auto, &[]).unwrap();
+        assert!(matches!(
+            provider,
+            CredentialProvider::InstanceProfile { ref endpoint } if endpoint == IMDS_ENDPOINT
+        ));
+
+        assert!(
+            resolve(
+                AwsCredentialSource::Auto,
+                &[("AWS_EC2_METADATA_DISABLED", "true")]
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn pinned_source_must_be_configured() {
+        let web_identity = [
+            ("AWS_ROLE_ARN", "arn:aws:iam::123456789012:role/tagger"),
+            ("AWS_WEB_IDENTITY_TOKEN_FILE", "/var/run/token"),
+        ];
+
+        assert!(resolve(AwsCredentialSource::Environment, &web_identity).is_err());
+        assert!(resolve(AwsCredentialSource::PodIdentity, &web_identity).is_err());
+        assert!(resolve(AwsCredentialSource::Ecs, &web_identity).is_err());
+        assert!(resolve(AwsCredentialSource::WebIdentity, &[]).is_err());
+        assert_eq!(
+            resolve(AwsCredentialSource::InstanceProfile, &web_identity)
+                .unwrap()
+                .name(),
+            "instanceProfile"
+        );
+    }
+
+    #[test]
+    fn pod_identity_uses_the_token_file() {
+        let provider = resolve(
+            AwsCredentialSource::PodIdentity,
+            &[
+                (
+                    "AWS_CONTAINER_CREDENTIALS_FULL_URI",
+                    "http://169.254.170.23/v1/credentials",
+                ),
+                (
+                    "AWS_CONTAINER_AUTHORIZATION_TOKEN_FILE",
+                    "/var/run/secrets/pods.eks.amazonaws.com/serviceaccount/eks-pod-identity-token",
+                ),
+            ],
+        )
+        .unwrap();
+
+        assert!(matches!(
+            provider,
+            CredentialProvider::Container {
+                token: Some(ContainerToken::File(_)),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn container_uri_must_be_https_or_local() {
+        assert!(check_container_uri("https://credentials.example.com/creds").is_ok());
+        assert!(check_container_uri("http://127.0.0.1:8080/creds").is_ok());
+        assert!(check_container_uri("http://localhost/creds").is_ok());
+        assert!(check_container_uri("http://169.254.170.23/v1/credentials").is_ok());
+        assert!(check_container_uri("http://[fd00:ec2::23]/v1/credentials").is_ok());
+        assert!(check_container_uri("http://credentials.example.com/creds").is_err());
+        assert!(check_container_uri("not a uri").is_err());
+    }
+
+    #[test]
+    fn parse_credentials_valid_xml() {
+        let xml = r#"<AssumeRoleWithWebIdentityResponse>
+            <AssumeRoleWithWebIdentityResult>
+                <Credentials>
+                    <AccessKeyId>ASIA1234567890</AccessKeyId>
+                    <SecretAccessKey>wJalrXUtnFEMI/K7MDENG/bPxRfiCY1234567890</SecretAccessKey>
+                    <SessionToken>FwoGZXIvYXdzEBYaDK1234567890</SessionToken>
+                    <Expiration>2026-10-17T13:34:41Z</Expiration>
+                </Credentials>
+            </AssumeRoleWithWebIdentityResult>
+        </AssumeRoleWithWebIdentityResponse>"#;
+
+        let creds = parse_credentials(xml).unwrap();
+        assert_eq!(creds.access_key_id, "ASIA1234567890");
+        assert_eq!(
+            creds.secret_access_key,
+            "wJalrXUtnFEMI/K7MDENG/bPxRfiCY1234567890"
+        );
+        assert_eq!(
+            creds.session_token,
+            Some("FwoGZXIvYXdzEBYaDK1234567890".to_string())
+        );
+        assert_eq!(
+            creds.expiration,
+            Some("2026-10-17T13:34:41Z".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn parse_credentials_invalid_xml() {
+        let xml = "not valid xml";
+        assert!(parse_credentials(xml).is_err());
+    }
+
+    #[test]
+    fn parse_credentials_missing_fields() {
+        let xml = r#"<AssumeRoleWithWebIdentityResponse>
+            <AssumeRoleWithWebIdentityResult>
+                <Credentials>
+                    <AccessKeyId>ASIA123</AccessKeyId>
+                </Credentials>
+            </AssumeRoleWithWebIdentityResult>
+        </AssumeRoleWithWebIdentityResponse>"#;
+
+        assert!(parse_credentials(xml).is_err());
+    }
+
+    #[test]
+    fn parse_json_credentials_valid() {
+        let creds = parse_json_credentials(JSON_CREDENTIALS).unwrap();
+        assert_eq!(creds.access_key_id, "ASIA1234567890");
+        assert_eq!(creds.session_token, Some("session".to_string()));
+        assert_eq!(
+            creds.expiration,
+            Some("2026-10-17T13:34:41Z".parse().unwrap())
+        );
+
+        assert!(parse_json_credentials(r#"{"AccessKeyId": "ASIA123"}"#).is_err());
+    }
+
+    #[tokio::test]
+    async fn container_credentials_send_the_current_token() {
+        let app = axum::Router::new().route(
+            "/v1/credentials",
+            get(|headers: HeaderMap| async move {
+                match headers.get("authorization") {
+                    Some(token) if token == "rotated-token" => {
+                        (StatusCode::OK, JSON_CREDENTIALS.to_string())
+                    }
+                    _ => (StatusCode::UNAUTHORIZED, "bad token".to_string()),
+                }
+            }),
+        );
+        let uri = format!("{}/v1/credentials", serve(app).await);
+        let mut token_file = NamedTempFile::new().unwrap();
+        write!(token_file, "pod-token").unwrap();
+        let provider = CredentialProvider::Container {
+            uri,
+            token: Some(ContainerToken::File(
+                token_file.path().display().to_string(),
+            )),
+        };
+        let http = http_client().unwrap();
+
+        assert!(provider.fetch(&http).await.is_err());
+
+        std::fs::write(token_file.path(), "rotated-token\n").unwrap();
+        let creds = provider.fetch(&http).await.unwrap();
+        assert_eq!(creds.access_key_id, "ASIA1234567890");
+    }
+
+    #[tokio::test]
+    async fn instance_profile_credentials_use_an_imds_session() {
+        fn has_token(headers: &HeaderMap) -> bool {
+            headers
+                .get("x-aws-ec2-metadata-token")
+                .is_some_and(|token| token == "imds-token")
+        }
+
+        let app = axum::Router::new()
+            .route(
+                "/latest/api/token",
+                put(|headers: HeaderMap| async move {
+                    assert_eq!(
+                        headers["x-aws-ec2-metadata-token-ttl-seconds"],
+                        IMDS_TOKEN_TTL_SECONDS
+                    );
+                    "imds-token"
+                }),
+            )
+            .route(
+                "/latest/meta-data/iam/security-credentials/",
+                get(|headers: HeaderMap| async move {
+                    if has_token(&headers) {
+                        (StatusCode::OK, "tagger-node-role\n")
+                    } else {
+                        (StatusCode::UNAUTHORIZED, "")
+                    }
+                }),
+            )
+            .route(
+                "/latest/meta-data/iam/security-credentials/tagger-node-role",
+                get(|headers: HeaderMap| async move {
+                    if has_token(&headers) {
+                        (StatusCode::OK, JSON_CREDENTIALS)
+                    } else {
+                        (StatusCode::UNAUTHORIZED, "")
+                    }
+                }),
+            );
+        let endpoint = serve(app).await;
+        let provider = resolve(
+            AwsCredentialSource::InstanceProfile,
+            &[("AWS_EC2_METADATA_SERVICE_ENDPOINT", &format!("{endpoint}/"))],
+        )
+        .unwrap();
+
+        let creds = provider.fetch(&http_client().unwrap()).await.unwrap();
+
+        assert_eq!(creds.access_key_id, "ASIA1234567890");
+        assert_eq!(creds.session_token, Some("session".to_string()));
+    }
+
+    #[tokio::test]
+    async fn environment_credentials_are_returned_as_is() {
+        let provider = resolve(
+            AwsCredentialSource::Environment,
+            &[
+                ("AWS_ACCESS_KEY_ID", "AKIA123"),
+                ("AWS_SECRET_ACCESS_KEY", "secret"),
+                ("AWS_SESSION_TOKEN", "session"),
+            ],
+        )
+        .unwrap();
+
+        let creds = provider.fetch(&http_client().unwrap()).await.unwrap();
+
+        assert_eq!(creds.access_key_id, "AKIA123");
+        assert_eq!(creds.session_token, Some("session".to_string()));
+        assert_eq!(creds.expiration, None);
+    }
+}
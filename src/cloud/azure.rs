@@ -1,12 +1,19 @@
+use crate::cloud::cache::{CredentialCache, Expiring};
 use crate::cloud::{CloudClient, Labels};
 use crate::error::Error;
 use crate::tls::http_client;
+use crate::traits::{Location, ResourceKind};
 use async_trait::async_trait;
+use jiff::{SignedDuration, Timestamp};
 use reqwest::Client;
-use serde::Serialize;
-use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::net::IpAddr;
+use tokio::sync::Mutex;
 
+const ARM_ENDPOINT: &str = "https://management.azure.com";
 const TAGS_API_VERSION: &str = "2021-04-01";
+const NETWORK_API_VERSION: &str = "2024-05-01";
 const DEFAULT_AUTHORITY_HOST: &str = "https://login.microsoftonline.com/";
 const ARM_SCOPE: &str = "https://management.azure.com/.default";
 const CLIENT_ASSERTION_TYPE: &str = "urn:ietf:params:oauth:client-assertion-type:jwt-bearer";
@@ -25,34 +32,36 @@ impl AzureDisk {
     /// Expected shape (case-insensitive provider segment):
     ///   `/subscriptions/<sub>/resourceGroups/<rg>/providers/Microsoft.Compute/disks/<name>`
     pub fn parse(resource_id: &str) -> Option<Self> {
-        // Split on '/' — leading '/' yields an empty first element.
-        let parts: Vec<&str> = resource_id.split('/').collect();
-        // ["", "subscriptions", sub, "resourceGroups", rg,
-        //  "providers", "Microsoft.Compute", "disks", name]
-        if parts.len() != 9 {
-            return None;
-        }
-        if !parts[0].is_empty()
-            || !parts[1].eq_ignore_ascii_case("subscriptions")
-            || !parts[3].eq_ignore_ascii_case("resourceGroups")
-            || !parts[5].eq_ignore_ascii_case("providers")
-            || !parts[6].eq_ignore_ascii_case("Microsoft.Compute")
-            || !parts[7].eq_ignore_ascii_case("disks")
-        {
-            return None;
-        }
-        Some(Self {
+        is_compute_resource(resource_id, "disks").then(|| Self {
             resource_id: resource_id.to_string(),
         })
     }
+}
 
-    /// Build the ARM Tags API URL for this disk.
-    pub fn tags_url(&self) -> String {
-        format!(
-            "https://management.azure.com{}/providers/Microsoft.Resources/tags/default?api-version={}",
-            self.resource_id, TAGS_API_VERSION
-        )
-    }
+/// Check an ARM resource ID names a `Microsoft.Compute` resource of the given type.
+///
+/// Snapshots use the same shape as disks, with `snapshots` in place of `disks`:
+///   `/subscriptions/<sub>/resourceGroups/<rg>/providers/Microsoft.Compute/snapshots/<name>`
+fn is_compute_resource(resource_id: &str, resource_type: &str) -> bool {
+    // Split on '/' — leading '/' yields an empty first element.
+    let parts: Vec<&str> = resource_id.split('/').collect();
+    // ["", "subscriptions", sub, "resourceGroups", rg,
+    //  "providers", "Microsoft.Compute", <resource_type>, name]
+    parts.len() == 9
+        && parts[0].is_empty()
+        && parts[1].eq_ignore_ascii_case("subscriptions")
+        && parts[3].eq_ignore_ascii_case("resourceGroups")
+        && parts[5].eq_ignore_ascii_case("providers")
+        && parts[6].eq_ignore_ascii_case("Microsoft.Compute")
+        && parts[7].eq_ignore_ascii_case(resource_type)
+}
+
+/// Build the ARM Tags API URL for any ARM resource ID.
+fn tags_url(resource_id: &str) -> String {
+    format!(
+        "{ARM_ENDPOINT}{}/providers/Microsoft.Resources/tags/default?api-version={}",
+        resource_id, TAGS_API_VERSION
+    )
 }
 
 /// Sanitise a string for use as an Azure resource tag key or value.
@@ -87,6 +96,64 @@ fn sanitise_tags(labels: &Labels) -> BTreeMap<String, String> {
 #[derive(serde::Deserialize)]
 struct TokenResponse {
     access_token: String,
+    /// Seconds until the token expires.
+    #[serde(default)]
+    expires_in: Option<i64>,
+}
+
+/// An ARM bearer token and when it expires.
+struct AccessToken {
+    token: String,
+    expires_at: Option<Timestamp>,
+}
+
+impl Expiring for AccessToken {
+    fn expires_at(&self) -> Option<Timestamp> {
+        self.expires_at
+    }
+}
+
+/// One page of `Microsoft.Network/publicIPAddresses` list results.
+#[derive(Deserialize)]
+struct PublicIpList {
+    #[serde(default)]
+    value: Vec<PublicIp>,
+    #[serde(rename = "nextLink")]
+    next_link: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct PublicIp {
+    id: String,
+    #[serde(default)]
+    properties: PublicIpProperties,
+}
+
+#[derive(Default, Deserialize)]
+struct PublicIpProperties {
+    #[serde(rename = "ipAddress")]
+    ip_address: Option<String>,
+}
+
+/// The addresses and resource IDs of the allocated public IPs in one page of results.
+fn public_ip_addresses(list: PublicIpList) -> impl Iterator<Item = (IpAddr, String)> {
+    list.value.into_iter().filter_map(|pip| {
+        let ip = pip.properties.ip_address?.parse().ok()?;
+        Some((ip, pip.id))
+    })
+}
+
+/// The `default` tags resource of an ARM resource, as returned by the Tags API.
+#[derive(Deserialize)]
+struct TagsResource {
+    #[serde(default)]
+    properties: TagsResourceProperties,
+}
+
+#[derive(Default, Deserialize)]
+struct TagsResourceProperties {
+    #[serde(default)]
+    tags: BTreeMap<String, String>,
 }
 
 #[derive(Serialize)]
@@ -106,6 +173,14 @@ pub struct AzureClient {
     tenant_id: String,
     authority_host: String,
     federated_token_file: String,
+    /// Only needed to look up load balancer public IPs, which are found by
+    /// listing the subscription.
+    subscription_id: Option<String>,
+    arm_endpoint: String,
+    token: CredentialCache<AccessToken>,
+    /// Public IP resource IDs by address, from the last subscription listing.
+    /// Held across a listing so concurrent lookups wait for it.
+    public_ips: Mutex<HashMap<IpAddr, String>>,
 }
 
 impl AzureClient {
@@ -118,15 +193,26 @@ impl AzureClient {
             .unwrap_or_else(|_| DEFAULT_AUTHORITY_HOST.to_string());
         let federated_token_file = std::env::var("AZURE_FEDERATED_TOKEN_FILE")
             .map_err(|_| Error::Azure("AZURE_FEDERATED_TOKEN_FILE not set".into()))?;
+        let subscription_id = std::env::var("AZURE_SUBSCRIPTION_ID").ok();
         Ok(Self {
             http: http_client()?,
             client_id,
             tenant_id,
             authority_host,
             federated_token_file,
+            subscription_id,
+            arm_endpoint: ARM_ENDPOINT.to_string(),
+            token: CredentialCache::default(),
+            public_ips: Mutex::default(),
         })
     }
 
+    /// A bearer token for ARM, cached until shortly before it expires.
+    async fn workload_identity_token(&self) -> Result<String, Error> {
+        let token = self.token.get(|| self.exchange_federated_token()).await?;
+        Ok(token.token.clone())
+    }
+
     /// Obtain a bearer token using AKS Workload Identity.
     ///
     /// The AKS Workload Identity webhook injects four environment variables:
@@ -136,8 +222,9 @@ impl AzureClient {
     /// - `AZURE_AUTHORITY_HOST`       — AAD endpoint (defaults to https://login.microsoftonline.com/)
     ///
     /// The K8s token is exchanged for an ARM bearer token via the OAuth 2.0
-    /// client credentials flow with a federated assertion.
-    async fn workload_identity_token(&self) -> Result<String, Error> {
+    /// client credentials flow with a federated assertion. The K8s token is
+    /// read on every exchange, as the kubelet rotates it.
+    async fn exchange_federated_token(&self) -> Result<AccessToken, Error> {
         let assertion = std::fs::read_to_string(&self.federated_token_file).map_err(|e| {
             Error::Azure(format!("Failed to read {}: {e}", self.federated_token_file))
         })?;
@@ -147,6 +234,7 @@ impl AzureClient {
             self.authority_host, self.tenant_id
         );
 
+        tracing::debug!(client_id = %self.client_id, "Fetching Azure access token");
         let resp: TokenResponse = self
             .http
             .post(&url)
@@ -163,51 +251,234 @@ impl AzureClient {
             .json()
             .await?;
 
-        Ok(resp.access_token)
+        Ok(AccessToken {
+            token: resp.access_token,
+            expires_at: resp
+                .expires_in
+                .map(|secs| Timestamp::now() + SignedDuration::from_secs(secs)),
+        })
     }
-}
 
-#[async_trait]
-impl CloudClient for AzureClient {
-    fn provider_name(&self) -> &'static str {
-        "azure"
+    /// Find the ARM resource ID of the public IP resource with the given address.
+    ///
+    /// Addresses are cached from one listing of the subscription's public IPs,
+    /// which is only repeated when an address is missing from the cache.
+    async fn public_ip_resource_id(&self, token: &str, ip: IpAddr) -> Result<String, Error> {
+        let mut public_ips = self.public_ips.lock().await;
+        if let Some(id) = public_ips.get(&ip) {
+            return Ok(id.clone());
+        }
+
+        let subscription_id = self.subscription_id.as_deref().ok_or_else(|| {
+            Error::Azure("AZURE_SUBSCRIPTION_ID must be set to tag load balancers".into())
+        })?;
+
+        tracing::debug!(%ip, "Listing Azure public IPs");
+        let mut listed = HashMap::new();
+        let mut url = format!(
+            "{}/subscriptions/{subscription_id}/providers/Microsoft.Network/publicIPAddresses?api-version={NETWORK_API_VERSION}",
+            self.arm_endpoint
+        );
+        loop {
+            let page: PublicIpList = self
+                .http
+                .get(&url)
+                .bearer_auth(token)
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+
+            let next_link = page.next_link.clone();
+            listed.extend(public_ip_addresses(page));
+            match next_link {
+                Some(next) => url = next,
+                None => break,
+            }
+        }
+        *public_ips = listed;
+
+        public_ips
+            .get(&ip)
+            .cloned()
+            .ok_or_else(|| Error::CloudApi(format!("No public IP resource found for {ip}")))
     }
 
-    async fn set_tags(&self, resource_id: &str, labels: &Labels) -> Result<(), Error> {
-        let disk = AzureDisk::parse(resource_id)
-            .ok_or_else(|| Error::CloudApi(format!("Invalid Azure resource ID: {resource_id}")))?;
+    /// Drop a load balancer's cached public IP once its resource is gone, so
+    /// the next lookup lists the subscription again.
+    async fn forget_deleted_public_ip<T>(
+        &self,
+        kind: ResourceKind,
+        resource_id: &str,
+        result: Result<T, Error>,
+    ) -> Result<T, Error> {
+        if kind == ResourceKind::LoadBalancer
+            && let Err(Error::Reqwest(e)) = &result
+            && e.status() == Some(reqwest::StatusCode::NOT_FOUND)
+            && let Ok(ip) = resource_id.parse::<IpAddr>()
+        {
+            self.public_ips.lock().await.remove(&ip);
+        }
+        result
+    }
 
-        let token = self.workload_identity_token().await?;
+    /// Resolve the ARM resource ID whose tags should be updated.
+    async fn arm_resource_id(
+        &self,
+        token: &str,
+        kind: ResourceKind,
+        resource_id: &str,
+    ) -> Result<String, Error> {
+        match kind {
+            ResourceKind::Disk => Ok(AzureDisk::parse(resource_id)
+                .ok_or_else(|| {
+                    Error::CloudApi(format!("Invalid Azure resource ID: {resource_id}"))
+                })?
+                .resource_id),
+            ResourceKind::Snapshot => {
+                if !is_compute_resource(resource_id, "snapshots") {
+                    return Err(Error::CloudApi(format!(
+                        "Invalid Azure snapshot ID: {resource_id}"
+                    )));
+                }
+                Ok(resource_id.to_string())
+            }
+            ResourceKind::LoadBalancer => {
+                let ip: IpAddr = resource_id.parse().map_err(|_| {
+                    Error::CloudApi(format!("Invalid load balancer IP: {resource_id}"))
+                })?;
+                self.public_ip_resource_id(token, ip).await
+            }
+        }
+    }
 
-        let sanitised = sanitise_tags(labels);
+    /// Read the tags of any ARM resource using the Tags API.
+    async fn read_tags(&self, token: &str, resource_id: &str) -> Result<Labels, Error> {
+        let resp: TagsResource = self
+            .http
+            .get(tags_url(resource_id))
+            .bearer_auth(token)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(resp.properties.tags)
+    }
+
+    /// Update tags on any ARM resource using the Tags API.
+    ///
+    /// `Merge` adds or replaces the given tags; `Delete` removes them.
+    async fn patch_tags(
+        &self,
+        token: &str,
+        resource_id: &str,
+        operation: &'static str,
+        tags: BTreeMap<String, String>,
+    ) -> Result<(), Error> {
         let body = TagsPatch {
-            operation: "Merge",
-            properties: TagsProperties {
-                tags: sanitised.clone(),
-            },
+            operation,
+            properties: TagsProperties { tags },
         };
 
         self.http
-            .patch(disk.tags_url())
-            .bearer_auth(&token)
+            .patch(tags_url(resource_id))
+            .bearer_auth(token)
             .json(&body)
             .send()
             .await?
             .error_for_status()?;
 
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl CloudClient for AzureClient {
+    fn provider_name(&self) -> &'static str {
+        "azure"
+    }
+
+    fn sanitise(&self, labels: &Labels) -> Labels {
+        sanitise_tags(labels)
+    }
+
+    async fn get_tags(
+        &self,
+        kind: ResourceKind,
+        resource_id: &str,
+        _location: &Location,
+    ) -> Result<Labels, Error> {
+        let token = self.workload_identity_token().await?;
+        let arm_id = self.arm_resource_id(&token, kind, resource_id).await?;
+
+        let result = self.read_tags(&token, &arm_id).await;
+        self.forget_deleted_public_ip(kind, resource_id, result)
+            .await
+    }
+
+    async fn set_tags(
+        &self,
+        kind: ResourceKind,
+        resource_id: &str,
+        _location: &Location,
+        labels: &Labels,
+    ) -> Result<(), Error> {
+        let sanitised = sanitise_tags(labels);
+        let token = self.workload_identity_token().await?;
+        let arm_id = self.arm_resource_id(&token, kind, resource_id).await?;
+
+        let result = self
+            .patch_tags(&token, &arm_id, "Merge", sanitised.clone())
+            .await;
+        self.forget_deleted_public_ip(kind, resource_id, result)
+            .await?;
+
         tracing::debug!(
-            disk = %resource_id,
+            resource = %arm_id,
             tags = ?sanitised,
             "Azure: tags merged"
         );
 
         Ok(())
     }
+
+    async fn remove_tags(
+        &self,
+        kind: ResourceKind,
+        resource_id: &str,
+        _location: &Location,
+        keys: &BTreeSet<String>,
+    ) -> Result<(), Error> {
+        let token = self.workload_identity_token().await?;
+        let arm_id = self.arm_resource_id(&token, kind, resource_id).await?;
+
+        // An empty value deletes the tag whatever its current value.
+        let tags = keys.iter().map(|k| (k.clone(), String::new())).collect();
+        let result = self.patch_tags(&token, &arm_id, "Delete", tags).await;
+        self.forget_deleted_public_ip(kind, resource_id, result)
+            .await?;
+
+        tracing::debug!(
+            resource = %arm_id,
+            ?keys,
+            "Azure: tags deleted"
+        );
+
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::cloud::stub::{serve, serve_with};
+    use futures::future::join_all;
+    use std::sync::Arc;
+    use std::sync::Mutex;
+    use tempfile::NamedTempFile;
 
     #[test]
     fn parse_valid_disk() {
@@ -216,7 +487,7 @@ mod tests {
         let disk = AzureDisk::parse(id).unwrap();
         assert_eq!(disk.resource_id, id);
         assert_eq!(
-            disk.tags_url(),
+            tags_url(&disk.resource_id),
             format!(
                 "https://management.azure.com{}/providers/Microsoft.Resources/tags/default?api-version={}",
                 id, TAGS_API_VERSION
@@ -244,6 +515,66 @@ mod tests {
         );
     }
 
+    #[test]
+    fn snapshot_resource_ids() {
+        assert!(is_compute_resource(
+            "/subscriptions/s/resourceGroups/rg/providers/Microsoft.Compute/snapshots/snapshot-abc",
+            "snapshots"
+        ));
+        assert!(!is_compute_resource(
+            "/subscriptions/s/resourceGroups/rg/providers/Microsoft.Compute/disks/d",
+            "snapshots"
+        ));
+    }
+
+    #[test]
+    fn public_ip_addresses_skips_unallocated() {
+        let json = r#"{
+            "value": [
+                {
+                    "id": "/subscriptions/s/resourceGroups/mc_rg/providers/Microsoft.Network/publicIPAddresses/kubernetes-a0123",
+                    "properties": { "ipAddress": "203.0.113.10" }
+                },
+                {
+                    "id": "/subscriptions/s/resourceGroups/mc_rg/providers/Microsoft.Network/publicIPAddresses/pending",
+                    "properties": {}
+                }
+            ],
+            "nextLink": "https://management.azure.com/next"
+        }"#;
+        let page: PublicIpList = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            page.next_link.as_deref(),
+            Some("https://management.azure.com/next")
+        );
+
+        assert_eq!(
+            public_ip_addresses(page).collect::<Vec<_>>(),
+            [(
+                "203.0.113.10".parse().unwrap(),
+                "/subscriptions/s/resourceGroups/mc_rg/providers/Microsoft.Network/publicIPAddresses/kubernetes-a0123".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn parse_tags_resource() {
+        let json = r#"{
+            "id": "/subscriptions/s/resourceGroups/rg/providers/Microsoft.Compute/disks/d/providers/Microsoft.Resources/tags/default",
+            "name": "default",
+            "type": "Microsoft.Resources/tags",
+            "properties": { "tags": { "env": "prod" } }
+        }"#;
+        let resource: TagsResource = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            resource.properties.tags,
+            BTreeMap::from([("env".to_string(), "prod".to_string())])
+        );
+
+        let untagged: TagsResource = serde_json::from_str(r#"{ "properties": {} }"#).unwrap();
+        assert!(untagged.properties.tags.is_empty());
+    }
+
     #[test]
     fn sanitise_tag_key_replaces_disallowed() {
         assert_eq!(
@@ -278,4 +609,143 @@ mod tests {
         assert_eq!(result["app.kubernetes.io-name"], "frontend");
         assert_eq!(result["env"], "prod");
     }
+
+    /// A local Entra ID stub issuing tokens valid for `expires_in` seconds,
+    /// and the client assertions it has been sent.
+    async fn stub_token_endpoint(expires_in: i64) -> (String, Arc<Mutex<Vec<String>>>) {
+        let assertions = Arc::new(Mutex::new(Vec::new()));
+        let seen = assertions.clone();
+        let app = axum::Router::new().route(
+            "/tenant/oauth2/v2.0/token",
+            axum::routing::post(move |body: String| {
+                let seen = seen.clone();
+                async move {
+                    let form: BTreeMap<String, String> = serde_urlencoded::from_str(&body).unwrap();
+                    seen.lock().unwrap().push(form["client_assertion"].clone());
+                    // Slow enough for concurrent callers to queue up behind this refresh.
+                    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                    axum::Json(serde_json::json!({
+                        "token_type": "Bearer",
+                        "expires_in": expires_in,
+                        "access_token": "arm-token",
+                    }))
+                }
+            }),
+        );
+        (format!("{}/", serve(app).await), assertions)
+    }
+
+    fn token_client(authority_host: String, federated_token_file: &NamedTempFile) -> AzureClient {
+        AzureClient {
+            http: http_client().unwrap(),
+            client_id: "client".into(),
+            tenant_id: "tenant".into(),
+            authority_host,
+            federated_token_file: federated_token_file.path().display().to_string(),
+            subscription_id: None,
+            arm_endpoint: ARM_ENDPOINT.to_string(),
+            token: CredentialCache::default(),
+            public_ips: tokio::sync::Mutex::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn concurrent_requests_share_one_token_exchange() {
+        let (authority, assertions) = stub_token_endpoint(3600).await;
+        let file = NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), "sa-token").unwrap();
+        let client = token_client(authority, &file);
+
+        let tokens = join_all((0..10).map(|_| client.workload_identity_token())).await;
+        client.workload_identity_token().await.unwrap();
+
+        assert!(
+            tokens
+                .iter()
+                .all(|t| t.as_deref().ok() == Some("arm-token"))
+        );
+        assert_eq!(*assertions.lock().unwrap(), ["sa-token"]);
+    }
+
+    #[tokio::test]
+    async fn refresh_reads_rotated_federated_token() {
+        // Expires within the refresh margin, so every call exchanges again.
+        let (authority, assertions) = stub_token_endpoint(60).await;
+        let file = NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), "sa-token-1").unwrap();
+        let client = token_client(authority, &file);
+
+        client.workload_identity_token().await.unwrap();
+        std::fs::write(file.path(), "sa-token-2").unwrap();
+        client.workload_identity_token().await.unwrap();
+
+        assert_eq!(*assertions.lock().unwrap(), ["sa-token-1", "sa-token-2"]);
+    }
+
+    /// A local ARM stub listing two pages of public IPs, and how many pages
+    /// it has served.
+    async fn stub_public_ip_list() -> (String, Arc<std::sync::atomic::AtomicUsize>) {
+        let pages = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let served = pages.clone();
+        let endpoint = serve_with(|endpoint| {
+            let next_link = format!("{endpoint}/page2");
+            axum::Router::new()
+                .route(
+                    "/subscriptions/sub/providers/Microsoft.Network/publicIPAddresses",
+                    axum::routing::get({
+                        let served = served.clone();
+                        move || async move {
+                            served.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                            axum::Json(serde_json::json!({
+                                "value": [{
+                                    "id": "/subscriptions/sub/resourceGroups/mc_rg/providers/Microsoft.Network/publicIPAddresses/a",
+                                    "properties": { "ipAddress": "203.0.113.10" }
+                                }],
+                                "nextLink": next_link,
+                            }))
+                        }
+                    }),
+                )
+                .route(
+                    "/page2",
+                    axum::routing::get(move || async move {
+                        served.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                        axum::Json(serde_json::json!({
+                            "value": [{
+                                "id": "/subscriptions/sub/resourceGroups/other/providers/Microsoft.Network/publicIPAddresses/b",
+                                "properties": { "ipAddress": "198.51.100.20" }
+                            }]
+                        }))
+                    }),
+                )
+        })
+        .await;
+        (endpoint, pages)
+    }
+
+    #[tokio::test]
+    async fn public_ip_lookups_share_one_listing() {
+        let (endpoint, pages) = stub_public_ip_list().await;
+        let file = NamedTempFile::new().unwrap();
+        let mut client = token_client(String::new(), &file);
+        client.subscription_id = Some("sub".into());
+        client.arm_endpoint = endpoint;
+
+        let ids = join_all(
+            ["203.0.113.10", "198.51.100.20", "203.0.113.10"]
+                .map(|ip| client.public_ip_resource_id("arm-token", ip.parse().unwrap())),
+        )
+        .await;
+
+        assert!(ids[0].as_deref().unwrap().ends_with("/publicIPAddresses/a"));
+        assert!(ids[1].as_deref().unwrap().ends_with("/publicIPAddresses/b"));
+        assert_eq!(pages.load(std::sync::atomic::Ordering::SeqCst), 2);
+
+        // An unknown address lists again, and still fails.
+        let missing = client
+            .public_ip_resource_id("arm-token", "192.0.2.1".parse().unwrap())
+            .await;
+        assert!(missing.is_err());
+        assert_eq!(pages.load(std::sync::atomic::Ordering::SeqCst), 4);
+    }
 }
@@ -0,0 +1,122 @@
+use crate::error::Error;
+use jiff::{SignedDuration, Timestamp};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// How long before they expire cached credentials are replaced, so a request
+/// never goes out with credentials about to expire.
+const REFRESH_BEFORE_EXPIRY: SignedDuration = SignedDuration::from_mins(5);
+
+/// Credentials which stop working at some point.
+pub trait Expiring {
+    /// When the credentials expire, or `None` if they don't.
+    fn expires_at(&self) -> Option<Timestamp>;
+}
+
+/// Caches credentials until shortly before they expire.
+///
+/// Callers needing a refresh at the same time share one: the first fetches
+/// new credentials while the rest wait for its result.
+pub struct CredentialCache<T> {
+    current: Mutex<Option<Arc<T>>>,
+}
+
+impl<T> Default for CredentialCache<T> {
+    fn default() -> Self {
+        Self {
+            current: Mutex::new(None),
+        }
+    }
+}
+
+impl<T: Expiring> CredentialCache<T> {
+    /// The cached credentials, or new ones from `fetch` if they are missing or
+    /// about to expire. A failed fetch isn't cached, so the next call retries.
+    pub async fn get<F, Fut>(&self, fetch: F) -> Result<Arc<T>, Error>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, Error>>,
+    {
+        // Holding the lock across the fetch is what makes concurrent callers share it.
+        let mut current = self.current.lock().await;
+        if let Some(credentials) = current.as_ref()
+            && is_fresh(credentials.as_ref())
+        {
+            return Ok(credentials.clone());
+        }
+
+        let credentials = Arc::new(fetch().await?);
+        *current = Some(credentials.clone());
+        Ok(credentials)
+    }
+}
+
+fn is_fresh<T: Expiring>(credentials: &T) -> bool {
+    credentials
+        .expires_at()
+        .is_none_or(|at| at.duration_since(Timestamp::now()) > REFRESH_BEFORE_EXPIRY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct Token(Option<Timestamp>);
+
+    impl Expiring for Token {
+        fn expires_at(&self) -> Option<Timestamp> {
+            self.0
+        }
+    }
+
+    fn expiring_in(mins: i64) -> Token {
+        Token(Some(Timestamp::now() + SignedDuration::from_mins(mins)))
+    }
+
+    #[tokio::test]
+    async fn reuses_credentials_until_near_expiry() {
+        let cache = CredentialCache::default();
+        let fetches = AtomicUsize::new(0);
+        let fetch = |mins| {
+            fetches.fetch_add(1, Ordering::Relaxed);
+            async move { Ok(expiring_in(mins)) }
+        };
+
+        cache.get(|| fetch(60)).await.unwrap();
+        cache.get(|| fetch(60)).await.unwrap();
+        assert_eq!(fetches.load(Ordering::Relaxed), 1);
+
+        let cache = CredentialCache::default();
+        cache.get(|| fetch(2)).await.unwrap();
+        cache.get(|| fetch(2)).await.unwrap();
+        assert_eq!(
+            fetches.load(Ordering::Relaxed),
+            3,
+            "refreshed within 5m of expiry"
+        );
+    }
+
+    #[tokio::test]
+    async fn credentials_without_expiry_are_kept() {
+        let cache = CredentialCache::default();
+
+        cache.get(|| async { Ok(Token(None)) }).await.unwrap();
+        let result = cache
+            .get(|| async { Err(Error::Aws("should not fetch".into())) })
+            .await;
+
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn failed_fetch_is_retried() {
+        let cache: CredentialCache<Token> = CredentialCache::default();
+
+        let failed = cache.get(|| async { Err(Error::Aws("down".into())) }).await;
+        let retried = cache.get(|| async { Ok(expiring_in(60)) }).await;
+
+        assert!(failed.is_err());
+        assert!(retried.is_ok());
+    }
+}
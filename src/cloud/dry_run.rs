@@ -0,0 +1,176 @@
+use super::{CloudClient, Labels};
+use crate::error::Error;
+use crate::traits::{Location, ResourceKind};
+use async_trait::async_trait;
+use std::collections::BTreeSet;
+
+/// Wrapper which logs the changes a CloudClient would make instead of making them.
+///
+/// Reads go through to the real cloud so the reconciler can diff its changes
+/// against the current tags. If the tags can't be read (e.g. missing
+/// permissions), they read as empty and every tag is reported as a change.
+pub struct DryRunClient<C: CloudClient> {
+    inner: C,
+}
+
+impl<C: CloudClient> DryRunClient<C> {
+    pub fn new(inner: C) -> Self {
+        Self { inner }
+    }
+}
+
+#[async_trait]
+impl<C: CloudClient> CloudClient for DryRunClient<C> {
+    fn provider_name(&self) -> &'static str {
+        self.inner.provider_name()
+    }
+
+    fn sanitise(&self, labels: &Labels) -> Labels {
+        self.inner.sanitise(labels)
+    }
+
+    async fn get_tags(
+        &self,
+        kind: ResourceKind,
+        resource_id: &str,
+        location: &Location,
+    ) -> Result<Labels, Error> {
+        match self.inner.get_tags(kind, resource_id, location).await {
+            Ok(tags) => Ok(tags),
+            Err(e) => {
+                tracing::warn!(%kind, %resource_id, %e, "Dry run: could not read current tags");
+                Ok(Labels::new())
+            }
+        }
+    }
+
+    async fn set_tags(
+        &self,
+        kind: ResourceKind,
+        resource_id: &str,
+        location: &Location,
+        labels: &Labels,
+    ) -> Result<(), Error> {
+        // The reconciler has already logged the changes against the current tags.
+        tracing::debug!(%kind, %resource_id, ?location, ?labels, "Dry run: not setting tags");
+        Ok(())
+    }
+
+    async fn remove_tags(
+        &self,
+        kind: ResourceKind,
+        resource_id: &str,
+        location: &Location,
+        keys: &BTreeSet<String>,
+    ) -> Result<(), Error> {
+        tracing::info!(%kind, %resource_id, ?location, ?keys, "Dry run: would remove tags");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Records reads and writes, and fails reads when it has no tags.
+    #[derive(Default)]
+    struct RecordingClient {
+        tags: Option<Labels>,
+        reads: Mutex<usize>,
+        writes: Mutex<usize>,
+    }
+
+    #[async_trait]
+    impl CloudClient for RecordingClient {
+        fn provider_name(&self) -> &'static str {
+            "recording"
+        }
+
+        fn sanitise(&self, labels: &Labels) -> Labels {
+            labels.clone()
+        }
+
+        async fn get_tags(
+            &self,
+            _kind: ResourceKind,
+            _resource_id: &str,
+            _location: &Location,
+        ) -> Result<Labels, Error> {
+            *self.reads.lock().unwrap() += 1;
+            self.tags
+                .clone()
+                .ok_or_else(|| Error::CloudApi("access denied".into()))
+        }
+
+        async fn set_tags(
+            &self,
+            _kind: ResourceKind,
+            _resource_id: &str,
+            _location: &Location,
+            _labels: &Labels,
+        ) -> Result<(), Error> {
+            *self.writes.lock().unwrap() += 1;
+            Ok(())
+        }
+
+        async fn remove_tags(
+            &self,
+            _kind: ResourceKind,
+            _resource_id: &str,
+            _location: &Location,
+            _keys: &BTreeSet<String>,
+        ) -> Result<(), Error> {
+            *self.writes.lock().unwrap() += 1;
+            Ok(())
+        }
+    }
+
+    fn labels(pairs: &[(&str, &str)]) -> Labels {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn never_writes() {
+        let client = DryRunClient::new(RecordingClient {
+            tags: Some(labels(&[("env", "dev")])),
+            ..Default::default()
+        });
+
+        client
+            .set_tags(
+                ResourceKind::Disk,
+                "vol-abc123",
+                &Location::default(),
+                &labels(&[("env", "prod")]),
+            )
+            .await
+            .unwrap();
+        client
+            .remove_tags(
+                ResourceKind::Disk,
+                "vol-abc123",
+                &Location::default(),
+                &BTreeSet::from(["env".to_string()]),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(*client.inner.writes.lock().unwrap(), 0);
+        assert_eq!(*client.inner.reads.lock().unwrap(), 0, "nothing re-read");
+    }
+
+    #[tokio::test]
+    async fn unreadable_tags_read_as_empty() {
+        let client = DryRunClient::new(RecordingClient::default());
+
+        let tags = client
+            .get_tags(ResourceKind::Disk, "vol-abc123", &Location::default())
+            .await;
+
+        assert!(tags.unwrap().is_empty());
+    }
+}
@@ -1,13 +1,17 @@
 use crate::cloud::{CloudClient, Labels};
 use crate::error::Error;
 use crate::tls::http_client;
+use crate::traits::{Location, ResourceKind};
 use async_trait::async_trait;
 use gcp_auth::TokenProvider;
 use reqwest::Client;
 use serde::Deserialize;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
+use std::net::IpAddr;
 use std::sync::Arc;
 
+const COMPUTE_API: &str = "https://compute.googleapis.com/compute/v1";
+
 pub struct GcpDisk {
     pub project: String,
     pub location: String,
@@ -45,12 +49,47 @@ impl GcpDisk {
     pub fn api_path(&self) -> String {
         let loc_type = if self.regional { "regions" } else { "zones" };
         format!(
-            "https://compute.googleapis.com/compute/v1/projects/{}/{}/{}/disks/{}",
+            "{COMPUTE_API}/projects/{}/{}/{}/disks/{}",
             self.project, loc_type, self.location, self.name
         )
     }
 }
 
+pub struct GcpSnapshot {
+    pub project: String,
+    pub name: String,
+}
+
+impl GcpSnapshot {
+    /// Parse a CSI snapshot handle into a GcpSnapshot.
+    ///
+    /// Snapshots are global: "projects/my-proj/global/snapshots/snapshot-abc"
+    pub fn parse(snapshot_handle: &str) -> Option<Self> {
+        let parts: Vec<&str> = snapshot_handle.split('/').collect();
+        // Expected: ["projects", proj, "global", "snapshots", name]
+        if parts.len() != 5
+            || parts[0] != "projects"
+            || parts[2] != "global"
+            || parts[3] != "snapshots"
+        {
+            return None;
+        }
+
+        Some(Self {
+            project: parts[1].to_string(),
+            name: parts[4].to_string(),
+        })
+    }
+
+    /// Build the Compute API URL path for this snapshot.
+    pub fn api_path(&self) -> String {
+        format!(
+            "{COMPUTE_API}/projects/{}/global/snapshots/{}",
+            self.project, self.name
+        )
+    }
+}
+
 /// Sanitise a string for use as a GCP label key or value.
 ///
 /// GCP labels allow `[a-z0-9_-]`, max 63 chars.
@@ -94,14 +133,62 @@ fn sanitise_labels(labels: &Labels) -> BTreeMap<String, String> {
     result
 }
 
+/// Current labels on a disk or snapshot.
+#[derive(Deserialize)]
+struct LabelsResponse {
+    #[serde(default)]
+    labels: BTreeMap<String, String>,
+    #[serde(rename = "labelFingerprint")]
+    label_fingerprint: String,
+}
+
+/// Aggregated list of forwarding rules across all regions (and `global`).
 #[derive(Deserialize)]
-struct DiskResponse {
+struct ForwardingRuleAggregatedList {
+    #[serde(default)]
+    items: BTreeMap<String, ForwardingRulesScopedList>,
+}
+
+#[derive(Deserialize)]
+struct ForwardingRulesScopedList {
+    #[serde(default, rename = "forwardingRules")]
+    forwarding_rules: Vec<ForwardingRule>,
+}
+
+#[derive(Deserialize)]
+struct ForwardingRule {
+    #[serde(rename = "selfLink")]
+    self_link: String,
     #[serde(default)]
     labels: BTreeMap<String, String>,
     #[serde(rename = "labelFingerprint")]
     label_fingerprint: String,
 }
 
+/// Build the Compute API URL of a disk or snapshot.
+///
+/// Load balancers have no single URL; their labels live on forwarding rules.
+fn resource_url(kind: ResourceKind, resource_id: &str) -> Result<String, Error> {
+    match kind {
+        ResourceKind::Disk => Ok(GcpDisk::parse(resource_id)
+            .ok_or(Error::CloudApi("Invalid resource ID".into()))?
+            .api_path()),
+        ResourceKind::Snapshot => Ok(GcpSnapshot::parse(resource_id)
+            .ok_or_else(|| Error::CloudApi(format!("Invalid GCP snapshot handle: {resource_id}")))?
+            .api_path()),
+        ResourceKind::LoadBalancer => Err(Error::CloudApi(format!(
+            "Load balancer {resource_id} has no resource URL"
+        ))),
+    }
+}
+
+/// Parse a load balancer's ingress IP.
+fn load_balancer_ip(resource_id: &str) -> Result<IpAddr, Error> {
+    resource_id
+        .parse()
+        .map_err(|_| Error::CloudApi(format!("Invalid load balancer IP: {resource_id}")))
+}
+
 pub struct GcpClient {
     http: Client,
     auth: Arc<dyn TokenProvider>,
@@ -122,11 +209,12 @@ impl GcpClient {
         Ok(token.as_str().to_string())
     }
 
-    async fn get_disk_response(&self, disk: &GcpDisk) -> Result<DiskResponse, Error> {
+    /// Read the labels and label fingerprint of a resource, given its API URL.
+    async fn get_labels(&self, resource_url: &str) -> Result<LabelsResponse, Error> {
         let token = self.token().await?;
-        let resp: DiskResponse = self
+        let resp: LabelsResponse = self
             .http
-            .get(disk.api_path())
+            .get(resource_url)
             .bearer_auth(&token)
             .query(&[("fields", "labels,labelFingerprint")])
             .send()
@@ -138,9 +226,120 @@ impl GcpClient {
         Ok(resp)
     }
 
+    /// Find every forwarding rule (regional or global) with the given IP address.
+    ///
+    /// A load balancer Service usually maps to one forwarding rule, but may
+    /// have several sharing an IP (e.g. one per protocol).
+    async fn find_forwarding_rules(&self, ip: IpAddr) -> Result<Vec<ForwardingRule>, Error> {
+        let project = self.auth.project_id().await?;
+        let token = self.token().await?;
+        let resp: ForwardingRuleAggregatedList = self
+            .http
+            .get(format!(
+                "{COMPUTE_API}/projects/{project}/aggregated/forwardingRules"
+            ))
+            .bearer_auth(&token)
+            .query(&[("filter", format!("IPAddress=\"{ip}\""))])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(resp
+            .items
+            .into_values()
+            .flat_map(|scoped| scoped.forwarding_rules)
+            .collect())
+    }
+
+    /// Read the current labels of a resource.
+    ///
+    /// A load balancer's labels are those shared by every forwarding rule
+    /// sharing its IP, so a rule missing a label counts as drift.
+    async fn read_labels(&self, kind: ResourceKind, resource_id: &str) -> Result<Labels, Error> {
+        if kind == ResourceKind::LoadBalancer {
+            let rules = self
+                .find_forwarding_rules(load_balancer_ip(resource_id)?)
+                .await?;
+            let mut rules = rules.into_iter();
+            let Some(first) = rules.next() else {
+                return Err(Error::CloudApi(format!(
+                    "No forwarding rule found for {resource_id}"
+                )));
+            };
+            let mut labels = first.labels;
+            for rule in rules {
+                labels.retain(|k, v| rule.labels.get(k) == Some(v));
+            }
+            return Ok(labels);
+        }
+
+        let resource_url = resource_url(kind, resource_id)?;
+        Ok(self.get_labels(&resource_url).await?.labels)
+    }
+
+    /// Read-modify-write the labels of a resource with `setLabels`.
+    ///
+    /// Load balancers are updated through every forwarding rule sharing their IP.
+    async fn update_labels<F>(
+        &self,
+        kind: ResourceKind,
+        resource_id: &str,
+        update: F,
+    ) -> Result<(), Error>
+    where
+        F: Fn(&mut BTreeMap<String, String>) + Send + Sync,
+    {
+        if kind == ResourceKind::LoadBalancer {
+            let rules = self
+                .find_forwarding_rules(load_balancer_ip(resource_id)?)
+                .await?;
+            if rules.is_empty() {
+                return Err(Error::CloudApi(format!(
+                    "No forwarding rule found for {resource_id}"
+                )));
+            }
+
+            for rule in rules {
+                let mut labels = rule.labels;
+                update(&mut labels);
+
+                self.post_labels(&rule.self_link, &labels, &rule.label_fingerprint)
+                    .await?;
+
+                tracing::debug!(
+                    forwarding_rule = %rule.self_link,
+                    labels = ?labels,
+                    "GCP: labels set"
+                );
+            }
+            return Ok(());
+        }
+
+        let resource_url = resource_url(kind, resource_id)?;
+
+        let current = self.get_labels(&resource_url).await?;
+
+        let mut labels = current.labels;
+        update(&mut labels);
+
+        self.post_labels(&resource_url, &labels, &current.label_fingerprint)
+            .await?;
+
+        tracing::debug!(
+            resource = %resource_url,
+            labels = ?labels,
+            "GCP: labels set"
+        );
+
+        Ok(())
+    }
+
+    /// Call `setLabels` on a resource, given its API URL.
     async fn post_labels(
         &self,
-        disk: &GcpDisk,
+        resource_url: &str,
         labels: &BTreeMap<String, String>,
         fingerprint: &str,
     ) -> Result<(), Error> {
@@ -151,7 +350,7 @@ impl GcpClient {
         });
 
         self.http
-            .post(format!("{}/setLabels", disk.api_path()))
+            .post(format!("{resource_url}/setLabels"))
             .bearer_auth(&token)
             .json(&body)
             .send()
@@ -168,26 +367,46 @@ impl CloudClient for GcpClient {
         "gcp"
     }
 
-    async fn set_tags(&self, resource_id: &str, labels: &Labels) -> Result<(), Error> {
-        let disk =
-            GcpDisk::parse(resource_id).ok_or(Error::CloudApi("Invalid resource ID".into()))?;
+    fn sanitise(&self, labels: &Labels) -> Labels {
+        sanitise_labels(labels)
+    }
 
-        let disk_response = self.get_disk_response(&disk).await?;
+    async fn get_tags(
+        &self,
+        kind: ResourceKind,
+        resource_id: &str,
+        _location: &Location,
+    ) -> Result<Labels, Error> {
+        self.read_labels(kind, resource_id).await
+    }
 
+    async fn set_tags(
+        &self,
+        kind: ResourceKind,
+        resource_id: &str,
+        _location: &Location,
+        labels: &Labels,
+    ) -> Result<(), Error> {
         let sanitised = sanitise_labels(labels);
-        let mut merged = disk_response.labels;
-        merged.extend(sanitised);
 
-        self.post_labels(&disk, &merged, &disk_response.label_fingerprint)
-            .await?;
-
-        tracing::debug!(
-            disk = %resource_id,
-            labels = ?merged,
-            "GCP: labels set"
-        );
+        self.update_labels(kind, resource_id, |current| {
+            current.extend(sanitised.clone())
+        })
+        .await
+    }
 
-        Ok(())
+    async fn remove_tags(
+        &self,
+        kind: ResourceKind,
+        resource_id: &str,
+        _location: &Location,
+        keys: &BTreeSet<String>,
+    ) -> Result<(), Error> {
+        // setLabels replaces the whole set, so removal is a write without the keys.
+        self.update_labels(kind, resource_id, |current| {
+            current.retain(|k, _| !keys.contains(k))
+        })
+        .await
     }
 }
 
@@ -221,6 +440,19 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_snapshot() {
+        let s = GcpSnapshot::parse("projects/my-proj/global/snapshots/snapshot-abc").unwrap();
+        assert_eq!(s.project, "my-proj");
+        assert_eq!(s.name, "snapshot-abc");
+        assert_eq!(
+            s.api_path(),
+            "https://compute.googleapis.com/compute/v1/projects/my-proj/global/snapshots/snapshot-abc"
+        );
+        assert!(GcpSnapshot::parse("projects/my-proj/zones/z/disks/d").is_none());
+        assert!(GcpSnapshot::parse("snapshot-abc").is_none());
+    }
+
     #[test]
     fn parse_invalid() {
         assert!(GcpDisk::parse("not-a-handle").is_none());
@@ -228,6 +460,41 @@ mod tests {
         assert!(GcpDisk::parse("").is_none());
     }
 
+    #[test]
+    fn parse_forwarding_rule_aggregated_list() {
+        let json = r#"{
+            "items": {
+                "regions/us-central1": {
+                    "forwardingRules": [{
+                        "name": "a0123456789abcdef",
+                        "IPAddress": "203.0.113.10",
+                        "selfLink": "https://www.googleapis.com/compute/v1/projects/p/regions/us-central1/forwardingRules/a0123456789abcdef",
+                        "labelFingerprint": "42WmSpB8rSM="
+                    }]
+                },
+                "regions/europe-west2": {
+                    "warning": { "code": "NO_RESULTS_ON_PAGE" }
+                }
+            }
+        }"#;
+
+        let list: ForwardingRuleAggregatedList = serde_json::from_str(json).unwrap();
+        let rules: Vec<ForwardingRule> = list
+            .items
+            .into_values()
+            .flat_map(|s| s.forwarding_rules)
+            .collect();
+
+        assert_eq!(rules.len(), 1);
+        assert!(
+            rules[0]
+                .self_link
+                .ends_with("/forwardingRules/a0123456789abcdef")
+        );
+        assert!(rules[0].labels.is_empty());
+        assert_eq!(rules[0].label_fingerprint, "42WmSpB8rSM=");
+    }
+
     #[test]
     fn sanitise_labels_documented_examples() {
         struct Case {
@@ -1,6 +1,8 @@
 use super::{CloudClient, Labels};
 use crate::error::Error;
+use crate::traits::{Location, ResourceKind};
 use async_trait::async_trait;
+use std::collections::BTreeSet;
 use std::time::Duration;
 
 pub struct MockClient {
@@ -25,8 +27,42 @@ impl CloudClient for MockClient {
         "mock"
     }
 
-    async fn set_tags(&self, resource_id: &str, tags: &Labels) -> Result<(), Error> {
-        tracing::debug!(%resource_id, ?tags, "Mock: setting tags");
+    fn sanitise(&self, labels: &Labels) -> Labels {
+        labels.clone()
+    }
+
+    async fn get_tags(
+        &self,
+        kind: ResourceKind,
+        resource_id: &str,
+        _location: &Location,
+    ) -> Result<Labels, Error> {
+        tracing::debug!(%kind, %resource_id, "Mock: getting tags");
+        // Simulate API latency
+        tokio::time::sleep(self.delay).await;
+        Ok(Labels::new())
+    }
+
+    async fn set_tags(
+        &self,
+        kind: ResourceKind,
+        resource_id: &str,
+        _location: &Location,
+        tags: &Labels,
+    ) -> Result<(), Error> {
+        tracing::debug!(%kind, %resource_id, ?tags, "Mock: setting tags");
+        // Simulate API latency
+        tokio::time::sleep(self.delay).await;
+        Ok(())
+    }
+    async fn remove_tags(
+        &self,
+        kind: ResourceKind,
+        resource_id: &str,
+        _location: &Location,
+        keys: &BTreeSet<String>,
+    ) -> Result<(), Error> {
+        tracing::debug!(%kind, %resource_id, ?keys, "Mock: removing tags");
         // Simulate API latency
         tokio::time::sleep(self.delay).await;
         Ok(())
@@ -1,18 +1,24 @@
 mod aws;
 mod azure;
+mod cache;
+mod dry_run;
 mod gcp;
 mod mock;
+#[cfg(test)]
+mod stub;
 
+pub use dry_run::DryRunClient;
 pub use mock::MockClient;
 
 use crate::cloud::aws::AwsClient;
 use crate::cloud::azure::AzureClient;
 use crate::cloud::gcp::GcpClient;
+use crate::config::Config;
 use crate::error::Error;
 use crate::metrics::API_CALL_DURATION;
-use crate::traits::CloudProvider;
+use crate::traits::{CloudProvider, Location, ResourceKind};
 use async_trait::async_trait;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
 pub type Labels = BTreeMap<String, String>;
 
@@ -20,7 +26,35 @@ pub type Labels = BTreeMap<String, String>;
 pub trait CloudClient: Send + Sync {
     fn provider_name(&self) -> &'static str;
 
-    async fn set_tags(&self, resource_id: &str, labels: &Labels) -> Result<(), Error>;
+    /// Convert Kubernetes labels into the tags [`CloudClient::set_tags`] would write.
+    fn sanitise(&self, labels: &Labels) -> Labels;
+
+    /// Read the resource's current tags.
+    async fn get_tags(
+        &self,
+        kind: ResourceKind,
+        resource_id: &str,
+        location: &Location,
+    ) -> Result<Labels, Error>;
+
+    /// Merge labels into the resource's tags, leaving other tags untouched.
+    async fn set_tags(
+        &self,
+        kind: ResourceKind,
+        resource_id: &str,
+        location: &Location,
+        labels: &Labels,
+    ) -> Result<(), Error>;
+
+    /// Delete tags from the resource. `keys` are cloud tag keys, i.e. already
+    /// sanitised.
+    async fn remove_tags(
+        &self,
+        kind: ResourceKind,
+        resource_id: &str,
+        location: &Location,
+        keys: &BTreeSet<String>,
+    ) -> Result<(), Error>;
 }
 
 /// Blanket implementation of [`CloudClient`] for boxed trait objects.
@@ -36,10 +70,46 @@ impl CloudClient for Box<dyn CloudClient> {
         (**self).provider_name()
     }
 
+    /// Sanitises labels by delegating to the inner implementation.
+    fn sanitise(&self, labels: &Labels) -> Labels {
+        (**self).sanitise(labels)
+    }
+
+    /// Reads the specified resource's tags by delegating to the inner
+    /// implementation.
+    async fn get_tags(
+        &self,
+        kind: ResourceKind,
+        resource_id: &str,
+        location: &Location,
+    ) -> Result<Labels, Error> {
+        (**self).get_tags(kind, resource_id, location).await
+    }
+
     /// Applies the given labels to the specified resource by delegating to the
     /// inner implementation.
-    async fn set_tags(&self, resource_id: &str, labels: &Labels) -> Result<(), Error> {
-        (**self).set_tags(resource_id, labels).await
+    async fn set_tags(
+        &self,
+        kind: ResourceKind,
+        resource_id: &str,
+        location: &Location,
+        labels: &Labels,
+    ) -> Result<(), Error> {
+        (**self).set_tags(kind, resource_id, location, labels).await
+    }
+
+    /// Deletes the given tags from the specified resource by delegating to the
+    /// inner implementation.
+    async fn remove_tags(
+        &self,
+        kind: ResourceKind,
+        resource_id: &str,
+        location: &Location,
+        keys: &BTreeSet<String>,
+    ) -> Result<(), Error> {
+        (**self)
+            .remove_tags(kind, resource_id, location, keys)
+            .await
     }
 }
 
@@ -53,9 +123,38 @@ impl<C: CloudClient> MeteredClient<C> {
         Self { inner }
     }
 
-    pub async fn set_tags(&self, resource_id: &str, labels: &Labels) -> Result<(), Error> {
+    pub fn sanitise(&self, labels: &Labels) -> Labels {
+        self.inner.sanitise(labels)
+    }
+
+    pub async fn get_tags(
+        &self,
+        kind: ResourceKind,
+        resource_id: &str,
+        location: &Location,
+    ) -> Result<Labels, Error> {
+        let start = std::time::Instant::now();
+        let result = self.inner.get_tags(kind, resource_id, location).await;
+
+        API_CALL_DURATION
+            .with_label_values(&[self.inner.provider_name(), "get_tags"])
+            .observe(start.elapsed().as_secs_f64());
+
+        result
+    }
+
+    pub async fn set_tags(
+        &self,
+        kind: ResourceKind,
+        resource_id: &str,
+        location: &Location,
+        labels: &Labels,
+    ) -> Result<(), Error> {
         let start = std::time::Instant::now();
-        let result = self.inner.set_tags(resource_id, labels).await;
+        let result = self
+            .inner
+            .set_tags(kind, resource_id, location, labels)
+            .await;
 
         API_CALL_DURATION
             .with_label_values(&[self.inner.provider_name(), "set_tags"])
@@ -63,12 +162,35 @@ impl<C: CloudClient> MeteredClient<C> {
 
         result
     }
+
+    pub async fn remove_tags(
+        &self,
+        kind: ResourceKind,
+        resource_id: &str,
+        location: &Location,
+        keys: &BTreeSet<String>,
+    ) -> Result<(), Error> {
+        let start = std::time::Instant::now();
+        let result = self
+            .inner
+            .remove_tags(kind, resource_id, location, keys)
+            .await;
+
+        API_CALL_DURATION
+            .with_label_values(&[self.inner.provider_name(), "remove_tags"])
+            .observe(start.elapsed().as_secs_f64());
+
+        result
+    }
 }
 
-pub async fn create_client(provider: &CloudProvider) -> Result<Box<dyn CloudClient>, Error> {
-    match provider {
+pub async fn create_client(config: &Config) -> Result<Box<dyn CloudClient>, Error> {
+    match config.cloud_provider {
         CloudProvider::Mock => Ok(Box::new(MockClient::default())),
-        CloudProvider::Aws => Ok(Box::new(AwsClient::new()?)),
+        CloudProvider::Aws => Ok(Box::new(AwsClient::new(
+            config.aws_credentials,
+            config.aws_account_roles.clone(),
+        )?)),
         CloudProvider::Azure => Ok(Box::new(AzureClient::new()?)),
         CloudProvider::Gcp => Ok(Box::new(GcpClient::new().await?)),
         CloudProvider::Other => Err(Error::Config(
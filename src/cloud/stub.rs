@@ -0,0 +1,16 @@
+//! Local HTTP servers standing in for cloud APIs in tests.
+
+/// Serve `app` on a free local port, returning its base URL, e.g.
+/// `http://127.0.0.1:41234`.
+pub async fn serve(app: axum::Router) -> String {
+    serve_with(|_| app).await
+}
+
+/// Like [`serve`], for apps whose responses link back to the server itself.
+pub async fn serve_with(app: impl FnOnce(&str) -> axum::Router) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let endpoint = format!("http://{}", listener.local_addr().unwrap());
+    let app = app(&endpoint);
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    endpoint
+}
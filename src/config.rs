@@ -1,34 +1,180 @@
 use crate::error::Error;
+use crate::labels::{KeyPattern, LabelFilter, LabelMapping, LabelMappingRule};
 use crate::traits::CloudProvider;
+use kube::api::ListParams;
+use kube::runtime::watcher;
+use std::collections::BTreeMap;
 use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
 use std::time::Duration;
 
 const DEFAULT_PROBE_ADDR: SocketAddr =
     SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 8080));
 const DEFAULT_CONFIG_PATH: &str = "/etc/k8s-cloud-tagger/config.yaml";
+const DEFAULT_LEASE_NAME: &str = "k8s-cloud-tagger";
 
 #[derive(serde::Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
 struct FileConfig {
     cloud_provider: String,
     requeue: FileRequeueConfig,
+    #[serde(default)]
+    leader_election: Option<FileLeaderElectionConfig>,
+    #[serde(default)]
+    label_filter: FileLabelFilterConfig,
+    /// Keyed by Kubernetes label key.
+    #[serde(default)]
+    label_mapping: BTreeMap<String, FileLabelMappingRule>,
+    /// Values may reference environment variables as `${NAME}`.
+    #[serde(default)]
+    default_tags: BTreeMap<String, String>,
+    #[serde(default)]
+    namespace_labels: Vec<String>,
+    #[serde(default)]
+    workload_labels: Vec<String>,
+    #[serde(default)]
+    tagging_mode: TaggingMode,
+    #[serde(default)]
+    watch: FileWatchConfig,
+    #[serde(default)]
+    dry_run: bool,
+    #[serde(default)]
+    aws: FileAwsConfig,
+}
+
+#[derive(serde::Deserialize, Default)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct FileAwsConfig {
+    #[serde(default)]
+    credentials: AwsCredentialSource,
+    #[serde(default)]
+    account_roles: BTreeMap<String, String>,
+}
+
+#[derive(serde::Deserialize, Default)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct FileWatchConfig {
+    #[serde(default)]
+    namespaces: Vec<String>,
+    #[serde(default)]
+    label_selector: Option<String>,
+    #[serde(default)]
+    field_selector: Option<String>,
 }
 
 #[derive(serde::Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
 struct FileRequeueConfig {
     success: String,
     not_ready: String,
     error: String,
+    #[serde(default)]
+    drift_check: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct FileLeaderElectionConfig {
+    lease_name: String,
+    lease_duration: String,
+    #[serde(default)]
+    renew_deadline: Option<String>,
+    retry_period: String,
+}
+
+#[derive(serde::Deserialize, Default)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct FileLabelFilterConfig {
+    #[serde(default)]
+    include: Vec<String>,
+    #[serde(default)]
+    exclude: Vec<String>,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct FileLabelMappingRule {
+    #[serde(default)]
+    key: Option<String>,
+    /// Keyed by cloud provider name (`aws`, `azure`, `gcp`).
+    #[serde(default)]
+    key_overrides: BTreeMap<String, String>,
+    #[serde(default)]
+    values: BTreeMap<String, String>,
+}
+
+/// Whether resources are tagged unless they opt out, or only when they opt in,
+/// with the `cloud-tagger.upgrades.dev/enabled` annotation.
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum TaggingMode {
+    #[default]
+    OptOut,
+    OptIn,
+}
+
+/// Where the AWS client gets credentials from. `Auto` picks the first source
+/// configured in the environment, like the AWS SDKs' default chain.
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum AwsCredentialSource {
+    #[default]
+    Auto,
+    /// `AWS_ACCESS_KEY_ID` and `AWS_SECRET_ACCESS_KEY`.
+    Environment,
+    /// IAM Roles for Service Accounts (IRSA).
+    WebIdentity,
+    /// The EKS Pod Identity agent.
+    PodIdentity,
+    /// ECS container credentials.
+    Ecs,
+    /// An EC2 instance profile, from IMDSv2.
+    InstanceProfile,
 }
 
 pub struct Config {
     pub requeue_success: Duration,
     pub requeue_not_ready: Duration,
     pub requeue_error: Duration,
+    /// How long a recorded sync is trusted before tags are re-read from the cloud.
+    pub drift_check_interval: Duration,
     pub probe_addr: SocketAddr,
     pub cloud_provider: CloudProvider,
+    /// Name of the `coordination.k8s.io` Lease used for leader election.
+    pub lease_name: String,
+    /// How long a Lease is valid without being renewed.
+    pub lease_duration: Duration,
+    /// How long the leader keeps leading without a successful renewal.
+    /// Shorter than `lease_duration`, so it steps down before a standby can
+    /// take over.
+    pub lease_renew_deadline: Duration,
+    /// How often candidates try to acquire, and the leader renews, the Lease.
+    pub lease_retry_period: Duration,
+    /// Which Kubernetes labels are propagated to the cloud.
+    pub label_filter: LabelFilter,
+    /// How label keys and values are rewritten before tagging.
+    pub label_mapping: LabelMapping,
+    /// Tags written to every resource. A resource's own labels take precedence.
+    pub default_tags: BTreeMap<String, String>,
+    /// Namespace labels inherited by PVCs in that namespace. Empty disables inheritance.
+    pub namespace_labels: Vec<KeyPattern>,
+    /// Labels inherited by PVCs from the workload using them. Empty disables inheritance.
+    pub workload_labels: Vec<KeyPattern>,
+    pub tagging_mode: TaggingMode,
+    /// Namespaces to watch. Empty watches the whole cluster.
+    pub watch_namespaces: Vec<String>,
+    /// Label selector applied to every watched resource.
+    pub watch_label_selector: Option<String>,
+    /// Field selector applied to every watched resource.
+    pub watch_field_selector: Option<String>,
+    /// Log and emit Events for the changes that would be made, without
+    /// writing to the cloud or recording syncs on resources.
+    pub dry_run: bool,
+    pub aws_credentials: AwsCredentialSource,
+    /// IAM roles assumed to tag resources in other AWS accounts, keyed by
+    /// account ID.
+    pub aws_account_roles: BTreeMap<String, String>,
 }
 
 impl Default for Config {
@@ -37,29 +183,587 @@ impl Default for Config {
             requeue_success: Duration::from_secs(300),
             requeue_not_ready: Duration::from_secs(30),
             requeue_error: Duration::from_secs(60),
+            drift_check_interval: Duration::from_secs(3600),
             probe_addr: DEFAULT_PROBE_ADDR,
             cloud_provider: CloudProvider::Mock,
+            lease_name: DEFAULT_LEASE_NAME.to_string(),
+            lease_duration: Duration::from_secs(15),
+            lease_renew_deadline: Duration::from_secs(10),
+            lease_retry_period: Duration::from_secs(2),
+            label_filter: LabelFilter::default(),
+            label_mapping: LabelMapping::default(),
+            default_tags: BTreeMap::new(),
+            namespace_labels: Vec::new(),
+            workload_labels: Vec::new(),
+            tagging_mode: TaggingMode::default(),
+            watch_namespaces: Vec::new(),
+            watch_label_selector: None,
+            watch_field_selector: None,
+            dry_run: false,
+            aws_credentials: AwsCredentialSource::default(),
+            aws_account_roles: BTreeMap::new(),
         }
     }
 }
 
 impl Config {
     pub fn load() -> Result<Self, Error> {
-        let path = std::env::var("CONFIG_PATH").unwrap_or_else(|_| DEFAULT_CONFIG_PATH.to_string());
-        Self::from_file(path)
+        Self::from_file(Self::path())
+    }
+
+    /// Where the config file is read from: `CONFIG_PATH`, or the default mount path.
+    pub fn path() -> PathBuf {
+        std::env::var_os("CONFIG_PATH")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_CONFIG_PATH))
     }
     pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Error> {
         let raw = std::fs::read_to_string(path).map_err(|e| Error::Config(e.to_string()))?;
-        let fc: FileConfig =
-            serde_yaml::from_str(&raw).map_err(|e| Error::Config(e.to_string()))?;
-        Ok(Self {
-            requeue_success: parse_duration_str(&fc.requeue.success).map_err(Error::Config)?,
-            requeue_not_ready: parse_duration_str(&fc.requeue.not_ready).map_err(Error::Config)?,
-            requeue_error: parse_duration_str(&fc.requeue.error).map_err(Error::Config)?,
+        Self::parse(&raw).map_err(|errors| {
+            let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+            Error::Config(errors.join("; "))
+        })
+    }
+
+    /// Parse the contents of a config file, reporting every invalid field
+    /// rather than stopping at the first.
+    pub fn parse(raw: &str) -> Result<Self, Vec<ConfigError>> {
+        let fc = deserialize(raw)?;
+        let defaults = Self::default();
+        let mut checks = Checks {
+            raw,
+            errors: Vec::new(),
+        };
+
+        let (lease_name, lease_duration, lease_renew_deadline, lease_retry_period) =
+            match fc.leader_election {
+                Some(le) => {
+                    let lease_duration = checks.duration(
+                        &["leaderElection", "leaseDuration"],
+                        &le.lease_duration,
+                        defaults.lease_duration,
+                    );
+                    let renew_deadline = match &le.renew_deadline {
+                        Some(s) => checks.duration(
+                            &["leaderElection", "renewDeadline"],
+                            s,
+                            defaults.lease_renew_deadline,
+                        ),
+                        // Two thirds, like client-go's 10s of 15s.
+                        None => lease_duration * 2 / 3,
+                    };
+                    (
+                        le.lease_name,
+                        lease_duration,
+                        renew_deadline,
+                        checks.duration(
+                            &["leaderElection", "retryPeriod"],
+                            &le.retry_period,
+                            defaults.lease_retry_period,
+                        ),
+                    )
+                }
+                None => (
+                    defaults.lease_name,
+                    defaults.lease_duration,
+                    defaults.lease_renew_deadline,
+                    defaults.lease_retry_period,
+                ),
+            };
+        if lease_retry_period >= lease_duration {
+            checks.check(
+                &["leaderElection", "retryPeriod"],
+                Err("must be shorter than leaderElection.leaseDuration".to_string()),
+                (),
+            );
+        } else if lease_renew_deadline >= lease_duration {
+            checks.check(
+                &["leaderElection", "renewDeadline"],
+                Err("must be shorter than leaderElection.leaseDuration".to_string()),
+                (),
+            );
+        } else if lease_retry_period >= lease_renew_deadline {
+            checks.check(
+                &["leaderElection", "retryPeriod"],
+                Err("must be shorter than leaderElection.renewDeadline".to_string()),
+                (),
+            );
+        }
+
+        let mut label_mapping = LabelMapping::default();
+        for (label, rule) in fc.label_mapping {
+            let key_overrides = rule
+                .key_overrides
+                .into_iter()
+                .filter_map(|(provider, key)| {
+                    let path = ["labelMapping", &label, "keyOverrides", &provider];
+                    checks
+                        .check(&path, provider.parse().map(Some), None)
+                        .map(|provider| (provider, key))
+                })
+                .collect();
+            label_mapping.rules.insert(
+                label,
+                LabelMappingRule {
+                    key: rule.key,
+                    key_overrides,
+                    values: rule.values,
+                },
+            );
+        }
+
+        let default_tags = fc
+            .default_tags
+            .into_iter()
+            .map(|(k, v)| {
+                let path = ["defaultTags", &k];
+                let v = checks.check(&path, expand_env(&v, |name| std::env::var(name).ok()), v);
+                (k, v)
+            })
+            .collect();
+
+        let aws_account_roles = fc
+            .aws
+            .account_roles
+            .into_iter()
+            .filter_map(|(account, role)| {
+                let path = ["aws", "accountRoles", &account];
+                checks
+                    .check(&path, check_account_role(&account, &role).map(Some), None)
+                    .map(|()| (account, role))
+            })
+            .collect();
+
+        let requeue_success = checks.duration(
+            &["requeue", "success"],
+            &fc.requeue.success,
+            defaults.requeue_success,
+        );
+        let drift_check_interval = match fc.requeue.drift_check {
+            Some(s) => checks.duration(
+                &["requeue", "driftCheck"],
+                &s,
+                defaults.drift_check_interval,
+            ),
+            // An unset driftCheck never undercuts requeue.success.
+            None => defaults.drift_check_interval.max(requeue_success),
+        };
+        // Each drift check re-reads and may re-apply tags, so checking more
+        // often than a successful sync requeues would loop on the cloud API.
+        if drift_check_interval < requeue_success {
+            checks.check(
+                &["requeue", "driftCheck"],
+                Err("must be at least requeue.success".to_string()),
+                (),
+            );
+        }
+
+        let config = Self {
+            requeue_success,
+            requeue_not_ready: checks.duration(
+                &["requeue", "notReady"],
+                &fc.requeue.not_ready,
+                defaults.requeue_not_ready,
+            ),
+            requeue_error: checks.duration(
+                &["requeue", "error"],
+                &fc.requeue.error,
+                defaults.requeue_error,
+            ),
+            drift_check_interval,
             probe_addr: DEFAULT_PROBE_ADDR,
-            cloud_provider: fc.cloud_provider.parse().map_err(Error::Config)?,
+            cloud_provider: checks.check(
+                &["cloudProvider"],
+                fc.cloud_provider.parse(),
+                defaults.cloud_provider,
+            ),
+            lease_name,
+            lease_duration,
+            lease_renew_deadline,
+            lease_retry_period,
+            label_filter: LabelFilter {
+                include: checks.patterns(&["labelFilter", "include"], &fc.label_filter.include),
+                exclude: checks.patterns(&["labelFilter", "exclude"], &fc.label_filter.exclude),
+            },
+            label_mapping,
+            default_tags,
+            namespace_labels: checks.patterns(&["namespaceLabels"], &fc.namespace_labels),
+            workload_labels: checks.patterns(&["workloadLabels"], &fc.workload_labels),
+            tagging_mode: fc.tagging_mode,
+            watch_namespaces: fc.watch.namespaces,
+            watch_label_selector: fc.watch.label_selector.filter(|s| !s.is_empty()),
+            watch_field_selector: fc.watch.field_selector.filter(|s| !s.is_empty()),
+            dry_run: fc.dry_run,
+            aws_credentials: fc.aws.credentials,
+            aws_account_roles,
+        };
+
+        if checks.errors.is_empty() {
+            Ok(config)
+        } else {
+            checks.errors.sort_by_key(|e| e.line.unwrap_or(usize::MAX));
+            Err(checks.errors)
+        }
+    }
+
+    /// Whether resources would be tagged differently under `other`.
+    pub fn tags_differ(&self, other: &Config) -> bool {
+        self.label_filter != other.label_filter
+            || self.label_mapping != other.label_mapping
+            || self.default_tags != other.default_tags
+            || self.namespace_labels != other.namespace_labels
+            || self.workload_labels != other.workload_labels
+            || self.tagging_mode != other.tagging_mode
+    }
+
+    /// Settings which are only read at startup, and so differ between `self`
+    /// and `other` without taking effect until a restart.
+    pub fn restart_required(&self, other: &Config) -> Vec<&'static str> {
+        let mut settings = Vec::new();
+        if self.cloud_provider != other.cloud_provider {
+            settings.push("cloudProvider");
+        }
+        if self.lease_name != other.lease_name
+            || self.lease_duration != other.lease_duration
+            || self.lease_renew_deadline != other.lease_renew_deadline
+            || self.lease_retry_period != other.lease_retry_period
+        {
+            settings.push("leaderElection");
+        }
+        if self.watch_namespaces != other.watch_namespaces
+            || self.watch_label_selector != other.watch_label_selector
+            || self.watch_field_selector != other.watch_field_selector
+        {
+            settings.push("watch");
+        }
+        if self.dry_run != other.dry_run {
+            settings.push("dryRun");
+        }
+        if self.aws_credentials != other.aws_credentials {
+            settings.push("aws.credentials");
+        }
+        if self.aws_account_roles != other.aws_account_roles {
+            settings.push("aws.accountRoles");
+        }
+        settings
+    }
+
+    /// Copy the settings only read at startup from `running`, so that a
+    /// reloaded config describes what is actually running.
+    pub fn keep_startup_settings(&mut self, running: &Config) {
+        self.probe_addr = running.probe_addr;
+        self.cloud_provider = running.cloud_provider;
+        self.lease_name = running.lease_name.clone();
+        self.lease_duration = running.lease_duration;
+        self.lease_renew_deadline = running.lease_renew_deadline;
+        self.lease_retry_period = running.lease_retry_period;
+        self.watch_namespaces = running.watch_namespaces.clone();
+        self.watch_label_selector = running.watch_label_selector.clone();
+        self.watch_field_selector = running.watch_field_selector.clone();
+        self.dry_run = running.dry_run;
+        self.aws_credentials = running.aws_credentials;
+        self.aws_account_roles = running.aws_account_roles.clone();
+    }
+
+    /// Watcher configuration with the configured selectors.
+    pub fn watcher_config(&self) -> watcher::Config {
+        let mut wc = watcher::Config::default();
+        if let Some(labels) = &self.watch_label_selector {
+            wc = wc.labels(labels);
+        }
+        if let Some(fields) = &self.watch_field_selector {
+            wc = wc.fields(fields);
+        }
+        wc
+    }
+
+    /// List parameters with the configured selectors, for one-shot runs.
+    pub fn list_params(&self) -> ListParams {
+        let mut lp = ListParams::default();
+        if let Some(labels) = &self.watch_label_selector {
+            lp = lp.labels(labels);
+        }
+        if let Some(fields) = &self.watch_field_selector {
+            lp = lp.fields(fields);
+        }
+        lp
+    }
+}
+
+/// The config in use, which can be replaced while the controller runs.
+pub struct LiveConfig {
+    current: RwLock<Arc<Config>>,
+}
+
+impl LiveConfig {
+    pub fn new(config: Config) -> Self {
+        Self {
+            current: RwLock::new(Arc::new(config)),
+        }
+    }
+
+    /// The config right now. Callers hold on to it for the length of a
+    /// reconcile, so a reload part way through isn't seen.
+    pub fn get(&self) -> Arc<Config> {
+        self.current
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Replace the config for every later `get`.
+    pub fn set(&self, config: Config) {
+        *self.current.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(config);
+    }
+}
+
+/// A problem with one field of a config file.
+#[derive(Debug, PartialEq)]
+pub struct ConfigError {
+    /// 1-based line of the field, if it could be found.
+    pub line: Option<usize>,
+    /// Dotted path to the field, e.g. `requeue.success`. Empty if the file
+    /// couldn't be parsed at all.
+    pub field: String,
+    pub message: String,
+}
+
+impl ConfigError {
+    fn yaml(e: &serde_yaml::Error) -> Self {
+        let line = e.location().map(|l| l.line());
+        let message = e.to_string();
+        // serde_yaml appends the location, which is reported separately.
+        let message = match e.location() {
+            Some(l) => message
+                .strip_suffix(&format!(" at line {} column {}", l.line(), l.column()))
+                .map(str::to_string)
+                .unwrap_or(message),
+            None => message,
+        };
+        Self {
+            line,
+            field: String::new(),
+            message,
+        }
+    }
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(line) = self.line {
+            write!(f, "line {line}: ")?;
+        }
+        if !self.field.is_empty() {
+            write!(f, "{}: ", self.field)?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Collects field errors while a config file is parsed.
+struct Checks<'a> {
+    raw: &'a str,
+    errors: Vec<ConfigError>,
+}
+
+impl Checks<'_> {
+    /// Unwrap `result`, recording the error against `path` and returning
+    /// `fallback` if it failed.
+    fn check<T>(&mut self, path: &[&str], result: Result<T, String>, fallback: T) -> T {
+        result.unwrap_or_else(|message| {
+            self.errors.push(ConfigError {
+                line: key_line(self.raw, path),
+                field: path.join("."),
+                message,
+            });
+            fallback
         })
     }
+
+    fn duration(&mut self, path: &[&str], s: &str, fallback: Duration) -> Duration {
+        self.check(path, parse_duration_str(s), fallback)
+    }
+
+    /// Parse every pattern in a list, recording each invalid one.
+    fn patterns(&mut self, path: &[&str], patterns: &[String]) -> Vec<KeyPattern> {
+        patterns
+            .iter()
+            .filter_map(|p| self.check(path, KeyPattern::parse(p).map(Some), None))
+            .collect()
+    }
+}
+
+/// Deserialize a config file, reporting every unknown key and type error
+/// rather than only the first.
+///
+/// serde stops at the first error, so each bad value is reported, dropped and
+/// the file deserialized again until it deserializes or nothing more can be
+/// dropped.
+fn deserialize(raw: &str) -> Result<FileConfig, Vec<ConfigError>> {
+    let first = match serde_yaml::from_str(raw) {
+        Ok(fc) => return Ok(fc),
+        Err(e) => e,
+    };
+    // Not even YAML: the first error is the only one.
+    let Ok(mut value) = serde_yaml::from_str::<serde_yaml::Value>(raw) else {
+        return Err(vec![ConfigError::yaml(&first)]);
+    };
+    let mut errors: Vec<ConfigError> = Vec::new();
+    let mut dropped: Vec<String> = Vec::new();
+    loop {
+        let e = match serde_path_to_error::deserialize::<_, FileConfig>(value.clone()) {
+            Ok(fc) if errors.is_empty() => return Ok(fc),
+            Ok(_) => return Err(errors),
+            Err(e) => e,
+        };
+        let mut path: Vec<String> = e
+            .path()
+            .iter()
+            .filter_map(|segment| match segment {
+                serde_path_to_error::Segment::Map { key } => Some(key.clone()),
+                serde_path_to_error::Segment::Seq { index } => Some(index.to_string()),
+                _ => None,
+            })
+            .collect();
+        let message = e.into_inner().to_string();
+
+        // A required value we dropped is now missing, but was already reported:
+        // stand a placeholder in for it.
+        let missing = message
+            .strip_prefix("missing field `")
+            .and_then(|rest| rest.strip_suffix('`'));
+        if let Some(missing) = missing {
+            path.push(missing.to_string());
+            let field = path.join(".");
+            if let Some(i) = dropped.iter().position(|d| *d == field) {
+                dropped.remove(i);
+                let (key, parent) = path.split_last().expect("path has the missing key");
+                if let Some(serde_yaml::Value::Mapping(map)) = node_mut(&mut value, parent) {
+                    map.insert(key.as_str().into(), "".into());
+                    continue;
+                }
+            }
+            path.pop();
+        }
+
+        let field = path.join(".");
+        // The placeholder didn't fit either; nothing more can be learned.
+        if errors.iter().any(|e| e.field == field) {
+            return Err(errors);
+        }
+        let keys: Vec<&str> = path.iter().map(String::as_str).collect();
+        errors.push(ConfigError {
+            line: key_line(raw, &keys),
+            field: field.clone(),
+            message,
+        });
+        let Some((key, parent)) = keys.split_last() else {
+            return Err(errors);
+        };
+        match node_mut(&mut value, parent) {
+            Some(serde_yaml::Value::Mapping(map)) => {
+                if map.remove(*key).is_none() {
+                    return Err(errors);
+                }
+                dropped.push(field);
+            }
+            // Blank list items rather than removing them, so later ones keep their index.
+            Some(serde_yaml::Value::Sequence(seq)) => {
+                match key.parse().ok().and_then(|i: usize| seq.get_mut(i)) {
+                    Some(item) => *item = "".into(),
+                    None => return Err(errors),
+                }
+            }
+            _ => return Err(errors),
+        }
+    }
+}
+
+/// The value at `path` under `value`, if there is one.
+fn node_mut<'a>(
+    mut value: &'a mut serde_yaml::Value,
+    path: &[impl AsRef<str>],
+) -> Option<&'a mut serde_yaml::Value> {
+    for key in path {
+        let key = key.as_ref();
+        value = match value {
+            serde_yaml::Value::Mapping(map) => map.get_mut(key)?,
+            serde_yaml::Value::Sequence(seq) => seq.get_mut(key.parse::<usize>().ok()?)?,
+            _ => return None,
+        };
+    }
+    Some(value)
+}
+
+/// Find the 1-based line of the key at `path` in block-style YAML.
+///
+/// This is a line scan rather than a YAML parse, so keys in flow mappings or
+/// behind aliases aren't found.
+fn key_line(raw: &str, path: &[&str]) -> Option<usize> {
+    let mut parent_indent: Option<usize> = None;
+    let mut depth = 0;
+    for (i, line) in raw.lines().enumerate() {
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let indent = line.len() - trimmed.len();
+        match parent_indent {
+            // Left the parent's block without finding the key.
+            Some(parent) if indent <= parent => return None,
+            None if indent > 0 => continue,
+            _ => {}
+        }
+        let key = trimmed
+            .split_once(':')
+            .map(|(key, _)| key.trim().trim_matches(['"', '\'']));
+        if key == Some(path[depth]) {
+            depth += 1;
+            if depth == path.len() {
+                return Some(i + 1);
+            }
+            parent_indent = Some(indent);
+        }
+    }
+    None
+}
+
+/// Replace `${NAME}` references with values from `lookup`, failing on unset variables.
+fn expand_env(s: &str, lookup: impl Fn(&str) -> Option<String>) -> Result<String, String> {
+    let mut out = String::new();
+    let mut rest = s;
+    while let Some(start) = rest.find("${") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = after
+            .find('}')
+            .ok_or_else(|| format!("unterminated '${{' in '{s}'"))?;
+        let name = &after[..end];
+        let value = lookup(name).ok_or_else(|| {
+            format!("environment variable '{name}' referenced in '{s}' is not set")
+        })?;
+        out.push_str(&value);
+        rest = &after[end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Check `role` is the ARN of an IAM role in `account`, a 12 digit account ID.
+fn check_account_role(account: &str, role: &str) -> Result<(), String> {
+    if account.len() != 12 || !account.bytes().all(|b| b.is_ascii_digit()) {
+        return Err("account ID must be 12 digits (quote it to keep leading zeros)".into());
+    }
+    match role.split(':').collect::<Vec<_>>()[..] {
+        ["arn", _, "iam", "", role_account, resource]
+            if role_account == account && resource.starts_with("role/") =>
+        {
+            Ok(())
+        }
+        _ => Err(format!(
+            "'{role}' is not the ARN of an IAM role in account {account}"
+        )),
+    }
 }
 
 fn parse_duration_str(s: &str) -> Result<Duration, String> {
@@ -102,11 +806,520 @@ requeue:
         assert_eq!(cfg.requeue_success, Duration::from_secs(300));
         assert_eq!(cfg.requeue_not_ready, Duration::from_secs(30));
         assert_eq!(cfg.requeue_error, Duration::from_secs(60));
+        assert_eq!(cfg.drift_check_interval, Duration::from_secs(3600));
         assert_eq!(cfg.probe_addr, DEFAULT_PROBE_ADDR);
         assert!(matches!(
             cfg.cloud_provider,
             crate::traits::CloudProvider::Gcp
         ));
+        assert_eq!(cfg.lease_name, DEFAULT_LEASE_NAME);
+        assert_eq!(cfg.lease_duration, Duration::from_secs(15));
+        assert_eq!(cfg.lease_retry_period, Duration::from_secs(2));
+        assert_eq!(cfg.tagging_mode, TaggingMode::OptOut);
+    }
+
+    #[test]
+    fn test_from_file_parses_leader_election() {
+        let yaml = "\
+cloudProvider: \"GCP\"
+requeue:
+  success: \"5m\"
+  notReady: \"30s\"
+  error: \"1m\"
+leaderElection:
+  leaseName: \"tagger-lease\"
+  leaseDuration: \"30s\"
+  retryPeriod: \"5s\"
+";
+        let mut file = NamedTempFile::new().unwrap();
+        write!(file, "{}", yaml).unwrap();
+
+        let cfg = Config::from_file(file.path()).unwrap();
+
+        assert_eq!(cfg.lease_name, "tagger-lease");
+        assert_eq!(cfg.lease_duration, Duration::from_secs(30));
+        assert_eq!(cfg.lease_renew_deadline, Duration::from_secs(20));
+        assert_eq!(cfg.lease_retry_period, Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_from_file_rejects_retry_period_longer_than_lease() {
+        let yaml = "\
+cloudProvider: \"GCP\"
+requeue:
+  success: \"5m\"
+  notReady: \"30s\"
+  error: \"1m\"
+leaderElection:
+  leaseName: \"tagger-lease\"
+  leaseDuration: \"10s\"
+  retryPeriod: \"10s\"
+";
+        let mut file = NamedTempFile::new().unwrap();
+        write!(file, "{}", yaml).unwrap();
+
+        assert!(Config::from_file(file.path()).is_err());
+    }
+
+    #[test]
+    fn test_parse_rejects_renew_deadline_outside_lease() {
+        let yaml = "\
+cloudProvider: \"GCP\"
+requeue:
+  success: \"5m\"
+  notReady: \"30s\"
+  error: \"1m\"
+leaderElection:
+  leaseName: \"tagger-lease\"
+  leaseDuration: \"15s\"
+  renewDeadline: \"15s\"
+  retryPeriod: \"2s\"
+";
+        let Err(errors) = Config::parse(yaml) else {
+            panic!("renewDeadline must be shorter than leaseDuration");
+        };
+        assert_eq!(errors[0].field, "leaderElection.renewDeadline");
+
+        let Err(errors) =
+            Config::parse(&yaml.replace("renewDeadline: \"15s\"", "renewDeadline: \"2s\""))
+        else {
+            panic!("retryPeriod must be shorter than renewDeadline");
+        };
+        assert_eq!(errors[0].field, "leaderElection.retryPeriod");
+
+        let Ok(cfg) = Config::parse(&yaml.replace("15s\"\n  retry", "10s\"\n  retry")) else {
+            panic!("expected a valid config");
+        };
+        assert_eq!(cfg.lease_renew_deadline, Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_from_file_parses_label_filter() {
+        let yaml = "\
+cloudProvider: \"GCP\"
+requeue:
+  success: \"5m\"
+  notReady: \"30s\"
+  error: \"1m\"
+labelFilter:
+  include:
+    - \"team\"
+    - \"app.kubernetes.io/*\"
+  exclude:
+    - \"re:^app\\\\.kubernetes\\\\.io/(managed-by|version)$\"
+";
+        let mut file = NamedTempFile::new().unwrap();
+        write!(file, "{}", yaml).unwrap();
+
+        let cfg = Config::from_file(file.path()).unwrap();
+
+        assert!(cfg.label_filter.allows("team"));
+        assert!(cfg.label_filter.allows("app.kubernetes.io/name"));
+        assert!(!cfg.label_filter.allows("app.kubernetes.io/managed-by"));
+        assert!(!cfg.label_filter.allows("helm.sh/chart"));
+    }
+
+    #[test]
+    fn test_from_file_rejects_invalid_label_pattern() {
+        let yaml = "\
+cloudProvider: \"GCP\"
+requeue:
+  success: \"5m\"
+  notReady: \"30s\"
+  error: \"1m\"
+labelFilter:
+  exclude:
+    - \"re:(\"
+";
+        let mut file = NamedTempFile::new().unwrap();
+        write!(file, "{}", yaml).unwrap();
+
+        assert!(Config::from_file(file.path()).is_err());
+    }
+
+    #[test]
+    fn test_from_file_parses_label_mapping() {
+        let yaml = "\
+cloudProvider: \"GCP\"
+requeue:
+  success: \"5m\"
+  notReady: \"30s\"
+  error: \"1m\"
+labelMapping:
+  acme.io/cost-center:
+    key: \"CostCenter\"
+    keyOverrides:
+      gcp: \"cost_center\"
+    values:
+      eng: \"Engineering\"
+";
+        let mut file = NamedTempFile::new().unwrap();
+        write!(file, "{}", yaml).unwrap();
+
+        let cfg = Config::from_file(file.path()).unwrap();
+
+        let rule = &cfg.label_mapping.rules["acme.io/cost-center"];
+        assert_eq!(rule.key.as_deref(), Some("CostCenter"));
+        assert_eq!(
+            rule.key_overrides,
+            vec![(CloudProvider::Gcp, "cost_center".to_string())]
+        );
+        assert_eq!(rule.values["eng"], "Engineering");
+    }
+
+    #[test]
+    fn test_from_file_rejects_unknown_override_provider() {
+        let yaml = "\
+cloudProvider: \"GCP\"
+requeue:
+  success: \"5m\"
+  notReady: \"30s\"
+  error: \"1m\"
+labelMapping:
+  team:
+    keyOverrides:
+      oracle: \"team\"
+";
+        let mut file = NamedTempFile::new().unwrap();
+        write!(file, "{}", yaml).unwrap();
+
+        assert!(Config::from_file(file.path()).is_err());
+    }
+
+    #[test]
+    fn test_from_file_parses_default_tags() {
+        let yaml = "\
+cloudProvider: \"AWS\"
+requeue:
+  success: \"5m\"
+  notReady: \"30s\"
+  error: \"1m\"
+defaultTags:
+  environment: \"prod\"
+  managed-by: \"k8s-cloud-tagger\"
+";
+        let mut file = NamedTempFile::new().unwrap();
+        write!(file, "{}", yaml).unwrap();
+
+        let cfg = Config::from_file(file.path()).unwrap();
+
+        assert_eq!(
+            cfg.default_tags,
+            BTreeMap::from([
+                ("environment".into(), "prod".into()),
+                ("managed-by".into(), "k8s-cloud-tagger".into()),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_from_file_parses_namespace_labels() {
+        let yaml = "\
+cloudProvider: \"AWS\"
+requeue:
+  success: \"5m\"
+  notReady: \"30s\"
+  error: \"1m\"
+namespaceLabels:
+  - \"team\"
+  - \"acme.io/*\"
+workloadLabels:
+  - \"app.kubernetes.io/name\"
+";
+        let mut file = NamedTempFile::new().unwrap();
+        write!(file, "{}", yaml).unwrap();
+
+        let cfg = Config::from_file(file.path()).unwrap();
+
+        assert_eq!(cfg.namespace_labels.len(), 2);
+        assert!(cfg.namespace_labels[1].matches("acme.io/cost-center"));
+        assert_eq!(cfg.workload_labels.len(), 1);
+    }
+
+    #[test]
+    fn test_from_file_parses_tagging_mode() {
+        let yaml = "\
+cloudProvider: \"AWS\"
+requeue:
+  success: \"5m\"
+  notReady: \"30s\"
+  error: \"1m\"
+taggingMode: \"optIn\"
+";
+        let mut file = NamedTempFile::new().unwrap();
+        write!(file, "{}", yaml).unwrap();
+
+        let cfg = Config::from_file(file.path()).unwrap();
+
+        assert_eq!(cfg.tagging_mode, TaggingMode::OptIn);
+    }
+
+    #[test]
+    fn test_from_file_parses_dry_run() {
+        let yaml = "\
+cloudProvider: \"AWS\"
+requeue:
+  success: \"5m\"
+  notReady: \"30s\"
+  error: \"1m\"
+dryRun: true
+";
+        let mut file = NamedTempFile::new().unwrap();
+        write!(file, "{}", yaml).unwrap();
+
+        let cfg = Config::from_file(file.path()).unwrap();
+
+        assert!(cfg.dry_run);
+    }
+
+    #[test]
+    fn test_from_file_parses_aws_credentials() {
+        let yaml = "\
+cloudProvider: \"AWS\"
+requeue:
+  success: \"5m\"
+  notReady: \"30s\"
+  error: \"1m\"
+aws:
+  credentials: podIdentity
+";
+        let mut file = NamedTempFile::new().unwrap();
+        write!(file, "{}", yaml).unwrap();
+
+        let cfg = Config::from_file(file.path()).unwrap();
+
+        assert_eq!(cfg.aws_credentials, AwsCredentialSource::PodIdentity);
+    }
+
+    #[test]
+    fn test_parse_aws_account_roles() {
+        let yaml = "\
+cloudProvider: \"AWS\"
+requeue:
+  success: \"5m\"
+  notReady: \"30s\"
+  error: \"1m\"
+aws:
+  accountRoles:
+    \"111122223333\": \"arn:aws:iam::111122223333:role/k8s-cloud-tagger\"
+";
+        let Ok(cfg) = Config::parse(yaml) else {
+            panic!("expected a valid config");
+        };
+        assert_eq!(
+            cfg.aws_account_roles,
+            BTreeMap::from([(
+                "111122223333".to_string(),
+                "arn:aws:iam::111122223333:role/k8s-cloud-tagger".to_string()
+            )])
+        );
+
+        let yaml = yaml.replace(
+            "role/k8s-cloud-tagger\"",
+            "role/k8s-cloud-tagger\"\n    \"1234\": \"arn:aws:iam::1234:role/tagger\"\n    \"444455556666\": \"arn:aws:iam::111122223333:role/tagger\"",
+        );
+        let Err(errors) = Config::parse(&yaml) else {
+            panic!("expected invalid account roles");
+        };
+        let fields: Vec<&str> = errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(
+            fields,
+            ["aws.accountRoles.1234", "aws.accountRoles.444455556666"]
+        );
+        assert_eq!(errors[0].line, Some(9));
+    }
+
+    #[test]
+    fn test_from_file_parses_watch_scope() {
+        let yaml = "\
+cloudProvider: \"AWS\"
+requeue:
+  success: \"5m\"
+  notReady: \"30s\"
+  error: \"1m\"
+watch:
+  namespaces:
+    - \"tenant-a\"
+    - \"tenant-b\"
+  labelSelector: \"tenant=a\"
+  fieldSelector: \"\"
+";
+        let mut file = NamedTempFile::new().unwrap();
+        write!(file, "{}", yaml).unwrap();
+
+        let cfg = Config::from_file(file.path()).unwrap();
+
+        assert_eq!(cfg.watch_namespaces, vec!["tenant-a", "tenant-b"]);
+        let wc = cfg.watcher_config();
+        assert_eq!(wc.label_selector.as_deref(), Some("tenant=a"));
+        assert_eq!(wc.field_selector, None);
+        let lp = cfg.list_params();
+        assert_eq!(lp.label_selector.as_deref(), Some("tenant=a"));
+        assert_eq!(lp.field_selector, None);
+    }
+
+    #[test]
+    fn test_from_file_parses_drift_check() {
+        let yaml = "\
+cloudProvider: \"AWS\"
+requeue:
+  success: \"5m\"
+  notReady: \"30s\"
+  error: \"1m\"
+  driftCheck: \"15m\"
+";
+        let mut file = NamedTempFile::new().unwrap();
+        write!(file, "{}", yaml).unwrap();
+
+        let cfg = Config::from_file(file.path()).unwrap();
+
+        assert_eq!(cfg.drift_check_interval, Duration::from_secs(900));
+    }
+
+    #[test]
+    fn test_parse_rejects_drift_check_below_success() {
+        for drift_check in ["0s", "1m"] {
+            let yaml = format!(
+                "\
+cloudProvider: \"AWS\"
+requeue:
+  success: \"5m\"
+  notReady: \"30s\"
+  error: \"1m\"
+  driftCheck: \"{drift_check}\"
+"
+            );
+
+            let errors = Config::parse(&yaml).err().unwrap();
+
+            assert_eq!(errors.len(), 1, "{drift_check}: {errors:?}");
+            assert_eq!(errors[0].field, "requeue.driftCheck");
+            assert_eq!(errors[0].line, Some(6));
+        }
+    }
+
+    #[test]
+    fn test_parse_defaults_drift_check_to_at_least_success() {
+        let yaml = "\
+cloudProvider: \"AWS\"
+requeue:
+  success: \"90m\"
+  notReady: \"30s\"
+  error: \"1m\"
+";
+
+        let config = Config::parse(yaml).unwrap();
+
+        assert_eq!(config.requeue_success, Duration::from_secs(5400));
+        assert_eq!(config.drift_check_interval, Duration::from_secs(5400));
+    }
+
+    #[test]
+    fn test_parse_reports_every_error_with_its_line() {
+        let yaml = "\
+cloudProvider: \"OpenStack\"
+requeue:
+  success: \"5min\"
+  notReady: \"30s\"
+  error: \"1m\"
+labelFilter:
+  exclude:
+    - \"re:[\"
+labelMapping:
+  app.kubernetes.io/team:
+    keyOverrides:
+      alibaba: Team
+";
+
+        let Err(errors) = Config::parse(yaml) else {
+            panic!("config should be invalid");
+        };
+        let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+
+        assert_eq!(errors.len(), 4);
+        assert_eq!(
+            errors[0],
+            "line 1: cloudProvider: invalid cloud provider: OpenStack"
+        );
+        assert!(errors[1].starts_with("line 3: requeue.success: "));
+        assert!(errors[2].starts_with("line 7: labelFilter.exclude: "));
+        assert!(
+            errors[3]
+                .starts_with("line 12: labelMapping.app.kubernetes.io/team.keyOverrides.alibaba: ")
+        );
+    }
+
+    #[test]
+    fn test_parse_reports_yaml_errors_with_their_line() {
+        let yaml = "\
+cloudProvider: \"GCP\"
+requeue:
+  success: \"5m\"
+  notReady: [
+";
+
+        let Err(errors) = Config::parse(yaml) else {
+            panic!("config should be invalid");
+        };
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line, Some(4));
+        assert!(errors[0].field.is_empty());
+        assert!(!errors[0].message.contains("at line"));
+    }
+
+    #[test]
+    fn test_parse_reports_every_unknown_key() {
+        let yaml = "\
+cloudProvider: \"AWS\"
+requeue:
+  success: \"5m\"
+  notReady: \"30s\"
+  error: \"1m\"
+  driftChek: \"15m\"
+dryrun: true
+";
+
+        let errors = Config::parse(yaml).err().unwrap();
+
+        assert_eq!(errors.len(), 2, "{errors:?}");
+        assert_eq!(errors[0].field, "requeue.driftChek");
+        assert_eq!(errors[0].line, Some(6));
+        assert_eq!(errors[1].field, "dryrun");
+        assert_eq!(errors[1].line, Some(7));
+    }
+
+    #[test]
+    fn test_key_line() {
+        let yaml = "\
+# comment
+requeue:
+  success: 5m
+leaderElection:
+  leaseName: x
+  # success: here too
+  retryPeriod: 2s
+";
+
+        assert_eq!(key_line(yaml, &["requeue"]), Some(2));
+        assert_eq!(key_line(yaml, &["requeue", "success"]), Some(3));
+        assert_eq!(key_line(yaml, &["leaderElection", "retryPeriod"]), Some(7));
+        assert_eq!(key_line(yaml, &["leaderElection", "success"]), None);
+        assert_eq!(key_line(yaml, &["success"]), None);
+    }
+
+    #[test]
+    fn test_expand_env() {
+        let lookup = |name: &str| (name == "CLUSTER_NAME").then(|| "prod-eu".to_string());
+
+        assert_eq!(expand_env("prod", lookup).unwrap(), "prod");
+        assert_eq!(expand_env("${CLUSTER_NAME}", lookup).unwrap(), "prod-eu");
+        assert_eq!(
+            expand_env("k8s-${CLUSTER_NAME}-disks", lookup).unwrap(),
+            "k8s-prod-eu-disks"
+        );
+        assert!(expand_env("${MISSING}", lookup).is_err());
+        assert!(expand_env("${CLUSTER_NAME", lookup).is_err());
     }
 
     #[test]
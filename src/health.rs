@@ -1,6 +1,8 @@
+use axum::extract::State;
 use axum::{Router, http::StatusCode, routing::get};
 use std::net::SocketAddr;
 use tokio::net::TcpListener;
+use tokio::sync::watch;
 
 /// Liveness probe - is the process alive?
 /// Always returns 200 OK.
@@ -9,11 +11,13 @@ async fn healthz() -> StatusCode {
 }
 
 /// Readiness probe - can this instance handle traffic?
-/// TODO:
-///     Return 503 until leader election acquired.
-///     https://github.com/upgrades-dev/k8s-cloud-tagger/issues/29
-async fn readyz() -> StatusCode {
-    StatusCode::OK
+/// Returns 503 until this replica has acquired leadership.
+async fn readyz(State(leader): State<watch::Receiver<bool>>) -> StatusCode {
+    if *leader.borrow() {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    }
 }
 
 /// Prometheus metrics endpoint
@@ -25,11 +29,12 @@ async fn metrics() -> (StatusCode, String) {
     }
 }
 
-pub async fn serve(addr: SocketAddr) -> anyhow::Result<()> {
+pub async fn serve(addr: SocketAddr, leader: watch::Receiver<bool>) -> anyhow::Result<()> {
     let app = Router::new()
         .route("/healthz", get(healthz))
         .route("/readyz", get(readyz))
-        .route("/metrics", get(metrics));
+        .route("/metrics", get(metrics))
+        .with_state(leader);
 
     let listener = TcpListener::bind(addr).await?;
     tracing::debug!(%addr, "Health server listening");
@@ -0,0 +1,328 @@
+//! Label processing applied between Kubernetes and the cloud client.
+//!
+//! Everything here works on Kubernetes label keys, before the provider
+//! sanitisers turn them into cloud tag keys.
+
+use crate::cloud::Labels;
+use crate::traits::CloudProvider;
+use regex::Regex;
+use std::collections::BTreeMap;
+
+/// Prefix of annotations whose values are written as tags, e.g.
+/// `cloud-tagger.upgrades.dev/tag.owner: jane@example.com` writes `owner`.
+///
+/// Annotation values aren't limited like label values, so they can carry
+/// URLs, email addresses and other free text.
+pub const TAG_ANNOTATION_PREFIX: &str = "cloud-tagger.upgrades.dev/tag.";
+
+/// Tags requested through `TAG_ANNOTATION_PREFIX` annotations, keyed by tag key.
+pub fn annotation_tags(annotations: &BTreeMap<String, String>) -> Labels {
+    annotations
+        .iter()
+        .filter_map(|(k, v)| {
+            let key = k.strip_prefix(TAG_ANNOTATION_PREFIX)?;
+            (!key.is_empty()).then(|| (key.to_string(), v.clone()))
+        })
+        .collect()
+}
+
+/// A rule matching label keys.
+///
+/// Written in the config file as a string:
+/// - `re:<regex>` is a regular expression, e.g. `re:^app\.kubernetes\.io/(name|part-of)$`
+/// - anything containing `*` or `?` is a glob, e.g. `helm.sh/*` to match a prefix
+/// - anything else is an exact key, e.g. `team`
+#[derive(Clone, Debug)]
+pub enum KeyPattern {
+    Exact(String),
+    Regex(Regex),
+}
+
+/// Patterns are equal if they were parsed from equivalent rules.
+impl PartialEq for KeyPattern {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Exact(a), Self::Exact(b)) => a == b,
+            (Self::Regex(a), Self::Regex(b)) => a.as_str() == b.as_str(),
+            _ => false,
+        }
+    }
+}
+
+impl KeyPattern {
+    pub fn parse(pattern: &str) -> Result<Self, String> {
+        if let Some(re) = pattern.strip_prefix("re:") {
+            return Regex::new(re)
+                .map(Self::Regex)
+                .map_err(|e| format!("invalid label pattern '{pattern}': {e}"));
+        }
+        if pattern.contains(['*', '?']) {
+            return Regex::new(&glob_to_regex(pattern))
+                .map(Self::Regex)
+                .map_err(|e| format!("invalid label pattern '{pattern}': {e}"));
+        }
+        Ok(Self::Exact(pattern.to_string()))
+    }
+
+    pub fn matches(&self, key: &str) -> bool {
+        match self {
+            Self::Exact(k) => k == key,
+            Self::Regex(re) => re.is_match(key),
+        }
+    }
+}
+
+/// Translate a glob (`*` any run of characters, `?` one character) to an anchored regex.
+fn glob_to_regex(glob: &str) -> String {
+    let mut re = String::from("^");
+    for c in glob.chars() {
+        match c {
+            '*' => re.push_str(".*"),
+            '?' => re.push('.'),
+            c => re.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+        }
+    }
+    re.push('$');
+    re
+}
+
+/// Decides which label keys are propagated to the cloud.
+///
+/// A key is kept if it matches any `include` rule (or `include` is empty) and
+/// matches no `exclude` rule. Exclusions win.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LabelFilter {
+    pub include: Vec<KeyPattern>,
+    pub exclude: Vec<KeyPattern>,
+}
+
+impl LabelFilter {
+    pub fn allows(&self, key: &str) -> bool {
+        let included = self.include.is_empty() || self.include.iter().any(|p| p.matches(key));
+        included && !self.exclude.iter().any(|p| p.matches(key))
+    }
+
+    pub fn apply(&self, labels: Labels) -> Labels {
+        labels
+            .into_iter()
+            .filter(|(k, _)| {
+                let allowed = self.allows(k);
+                if !allowed {
+                    tracing::debug!(key = %k, "Label filtered out");
+                }
+                allowed
+            })
+            .collect()
+    }
+}
+
+/// How one Kubernetes label is written to the cloud.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LabelMappingRule {
+    /// Cloud tag key to write instead of the label key.
+    pub key: Option<String>,
+    /// Per-provider replacements for `key`, e.g. a lowercase key for GCP,
+    /// which would otherwise lowercase `key` during sanitisation.
+    pub key_overrides: Vec<(CloudProvider, String)>,
+    /// Value translations. Values without an entry are written unchanged.
+    pub values: BTreeMap<String, String>,
+}
+
+impl LabelMappingRule {
+    fn key_for(&self, provider: CloudProvider) -> Option<&str> {
+        self.key_overrides
+            .iter()
+            .find(|(p, _)| *p == provider)
+            .map(|(_, k)| k.as_str())
+            .or(self.key.as_deref())
+    }
+}
+
+/// Renames label keys and translates values, keyed by Kubernetes label key.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LabelMapping {
+    pub rules: BTreeMap<String, LabelMappingRule>,
+}
+
+impl LabelMapping {
+    /// Map labels for the given provider.
+    ///
+    /// If a renamed key collides with another label's key, the renamed label wins.
+    pub fn apply(&self, labels: Labels, provider: CloudProvider) -> Labels {
+        let mut unmapped = Labels::new();
+        let mut mapped = Labels::new();
+        for (key, value) in labels {
+            let Some(rule) = self.rules.get(&key) else {
+                unmapped.insert(key, value);
+                continue;
+            };
+            let value = rule.values.get(&value).cloned().unwrap_or(value);
+            let new_key = rule.key_for(provider).map_or(key.clone(), str::to_string);
+            tracing::debug!(k8s_key = %key, key = %new_key, %value, "Mapped label");
+            mapped.insert(new_key, value);
+        }
+        unmapped.extend(mapped);
+        unmapped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(include: &[&str], exclude: &[&str]) -> LabelFilter {
+        let parse = |ps: &[&str]| -> Vec<KeyPattern> {
+            ps.iter().map(|p| KeyPattern::parse(p).unwrap()).collect()
+        };
+        LabelFilter {
+            include: parse(include),
+            exclude: parse(exclude),
+        }
+    }
+
+    #[test]
+    fn exact_pattern() {
+        let p = KeyPattern::parse("team").unwrap();
+        assert!(p.matches("team"));
+        assert!(!p.matches("team-name"));
+    }
+
+    #[test]
+    fn glob_pattern() {
+        let p = KeyPattern::parse("helm.sh/*").unwrap();
+        assert!(p.matches("helm.sh/chart"));
+        assert!(!p.matches("helmXsh/chart"), "dot must be literal");
+        assert!(!p.matches("x.helm.sh/chart"), "glob is anchored");
+
+        let p = KeyPattern::parse("env?").unwrap();
+        assert!(p.matches("env1"));
+        assert!(!p.matches("env12"));
+    }
+
+    #[test]
+    fn regex_pattern() {
+        let p = KeyPattern::parse(r"re:^app\.kubernetes\.io/(name|part-of)$").unwrap();
+        assert!(p.matches("app.kubernetes.io/name"));
+        assert!(!p.matches("app.kubernetes.io/managed-by"));
+    }
+
+    #[test]
+    fn invalid_regex_is_an_error() {
+        assert!(KeyPattern::parse("re:(").is_err());
+    }
+
+    #[test]
+    fn empty_filter_allows_everything() {
+        assert!(LabelFilter::default().allows("anything"));
+    }
+
+    #[test]
+    fn include_and_exclude() {
+        let f = filter(
+            &["team", "app.kubernetes.io/*"],
+            &["app.kubernetes.io/managed-by"],
+        );
+
+        let labels = Labels::from([
+            ("team".into(), "payments".into()),
+            ("app.kubernetes.io/name".into(), "api".into()),
+            ("app.kubernetes.io/managed-by".into(), "Helm".into()),
+            ("helm.sh/chart".into(), "api-1.0.0".into()),
+        ]);
+
+        assert_eq!(
+            f.apply(labels),
+            Labels::from([
+                ("team".into(), "payments".into()),
+                ("app.kubernetes.io/name".into(), "api".into()),
+            ])
+        );
+    }
+
+    fn cost_center_mapping() -> LabelMapping {
+        LabelMapping {
+            rules: BTreeMap::from([
+                (
+                    "acme.io/cost-center".into(),
+                    LabelMappingRule {
+                        key: Some("CostCenter".into()),
+                        key_overrides: vec![(CloudProvider::Gcp, "cost_center".into())],
+                        values: BTreeMap::from([("eng".into(), "Engineering".into())]),
+                    },
+                ),
+                (
+                    "team".into(),
+                    LabelMappingRule {
+                        key: Some("Team".into()),
+                        ..Default::default()
+                    },
+                ),
+            ]),
+        }
+    }
+
+    #[test]
+    fn mapping_renames_keys_and_translates_values() {
+        let labels = Labels::from([
+            ("acme.io/cost-center".into(), "eng".into()),
+            ("team".into(), "payments".into()),
+            ("env".into(), "prod".into()),
+        ]);
+
+        assert_eq!(
+            cost_center_mapping().apply(labels, CloudProvider::Aws),
+            Labels::from([
+                ("CostCenter".into(), "Engineering".into()),
+                ("Team".into(), "payments".into()),
+                ("env".into(), "prod".into()),
+            ])
+        );
+    }
+
+    #[test]
+    fn mapping_uses_provider_key_override() {
+        let labels = Labels::from([("acme.io/cost-center".into(), "sales".into())]);
+
+        assert_eq!(
+            cost_center_mapping().apply(labels, CloudProvider::Gcp),
+            Labels::from([("cost_center".into(), "sales".into())])
+        );
+    }
+
+    #[test]
+    fn renamed_key_wins_collision() {
+        let labels = Labels::from([
+            ("Team".into(), "old".into()),
+            ("team".into(), "payments".into()),
+        ]);
+
+        assert_eq!(
+            cost_center_mapping().apply(labels, CloudProvider::Azure),
+            Labels::from([("Team".into(), "payments".into())])
+        );
+    }
+
+    #[test]
+    fn annotation_tags_strip_prefix() {
+        let annotations = BTreeMap::from([
+            (
+                "cloud-tagger.upgrades.dev/tag.jira".into(),
+                "https://jira.example.com/browse/OPS-123".into(),
+            ),
+            ("cloud-tagger.upgrades.dev/tag.".into(), "no key".into()),
+            ("cloud-tagger.upgrades.dev/managed-tags".into(), "[]".into()),
+            (
+                "kubectl.kubernetes.io/last-applied-configuration".into(),
+                "{}".into(),
+            ),
+        ]);
+
+        assert_eq!(
+            annotation_tags(&annotations),
+            Labels::from([(
+                "jira".into(),
+                "https://jira.example.com/browse/OPS-123".into()
+            )])
+        );
+    }
+}
@@ -0,0 +1,613 @@
+//! Lease-based leader election.
+//!
+//! Every replica runs a [`LeaderElector`] that competes for a single
+//! `coordination.k8s.io/v1` Lease in the controller's namespace. Only the
+//! replica holding the Lease runs the controllers; the others wait as hot
+//! standbys and report not-ready on `/readyz`.
+//!
+//! The protocol follows client-go's `leaderelection` package:
+//!
+//! - A Lease with no holder may be taken over by any candidate.
+//! - A held Lease may be taken over once `leaseDurationSeconds` has passed
+//!   since this replica last saw its holder or `renewTime` change. Expiry is
+//!   measured on the local clock, never against the remote timestamp, so clock
+//!   skew between nodes cannot make a live Lease look expired.
+//! - The holder renews the Lease every retry period. If it cannot renew within
+//!   the renew deadline, which is shorter than the lease duration, it steps
+//!   down before any standby can consider the Lease expired.
+//! - Writes use `replace`, so the Lease's `resourceVersion` guards against two
+//!   candidates acquiring it at the same time (the loser gets a 409 Conflict).
+//! - On shutdown the holder clears `holderIdentity`, so a standby can take over
+//!   on its next retry instead of waiting for the Lease to expire.
+
+use crate::config::Config;
+use crate::error::Error;
+use k8s_openapi::api::coordination::v1::{Lease, LeaseSpec};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{MicroTime, ObjectMeta};
+use k8s_openapi::jiff::Timestamp;
+use kube::api::PostParams;
+use kube::{Api, Client};
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::time::Instant;
+
+/// Competes for a Lease and publishes whether this replica is the leader.
+pub struct LeaderElector {
+    api: Api<Lease>,
+    lease_name: String,
+    identity: String,
+    lease_duration: Duration,
+    renew_deadline: Duration,
+    retry_period: Duration,
+    leader: watch::Sender<bool>,
+    observed: Mutex<Option<Observed>>,
+}
+
+/// The last Lease record this replica saw, and when it first saw it.
+struct Observed {
+    holder: Option<String>,
+    renew_time: Option<MicroTime>,
+    at: Instant,
+}
+
+impl LeaderElector {
+    /// Create an elector for the Lease named in `config`, in the client's
+    /// default namespace (the pod's namespace when running in-cluster).
+    pub fn new(client: Client, config: &Config, identity: String) -> Self {
+        let api = Api::default_namespaced(client);
+        let (leader, _) = watch::channel(false);
+        Self {
+            api,
+            lease_name: config.lease_name.clone(),
+            identity,
+            lease_duration: config.lease_duration,
+            renew_deadline: config.lease_renew_deadline,
+            retry_period: config.lease_retry_period,
+            leader,
+            observed: Mutex::new(None),
+        }
+    }
+
+    /// Subscribe to leadership changes. The value is `true` while this
+    /// replica holds the Lease.
+    pub fn subscribe(&self) -> watch::Receiver<bool> {
+        self.leader.subscribe()
+    }
+
+    /// Run the election loop forever.
+    ///
+    /// Transient API errors are logged and retried. If the leader cannot renew
+    /// the Lease within the renew deadline it steps down, before a standby
+    /// could consider the Lease expired and take over.
+    pub async fn run(&self) {
+        let mut last_renewed: Option<Instant> = None;
+        loop {
+            let attempt = tokio::time::timeout(self.renew_deadline, self.try_acquire_or_renew())
+                .await
+                .map_err(|_| "timed out".to_string())
+                .and_then(|r| r.map_err(|e| e.to_string()));
+            match attempt {
+                Ok(true) => {
+                    last_renewed = Some(Instant::now());
+                    self.set_leader(true);
+                }
+                Ok(false) => {
+                    last_renewed = None;
+                    self.set_leader(false);
+                }
+                Err(e) => {
+                    tracing::warn!(lease = %self.lease_name, %e, "Leader election error");
+                    if last_renewed.is_some_and(|t| t.elapsed() >= self.renew_deadline) {
+                        last_renewed = None;
+                        self.set_leader(false);
+                    }
+                }
+            }
+            tokio::time::sleep(self.retry_period).await;
+        }
+    }
+
+    /// Give up the Lease if we hold it, so a standby can take over immediately.
+    pub async fn release(&self) {
+        if !*self.leader.borrow() {
+            return;
+        }
+        self.set_leader(false);
+
+        let result = async {
+            let Some(mut lease) = self.api.get_opt(&self.lease_name).await? else {
+                return Ok(());
+            };
+            let spec = lease.spec.get_or_insert_with(Default::default);
+            if spec.holder_identity.as_deref() != Some(self.identity.as_str()) {
+                return Ok(());
+            }
+            spec.holder_identity = None;
+            spec.lease_duration_seconds = Some(1);
+            spec.renew_time = Some(MicroTime(Timestamp::now()));
+            self.api
+                .replace(&self.lease_name, &PostParams::default(), &lease)
+                .await?;
+            Ok::<_, Error>(())
+        }
+        .await;
+
+        match result {
+            Ok(()) => tracing::info!(lease = %self.lease_name, "Released leadership"),
+            Err(e) => tracing::warn!(lease = %self.lease_name, %e, "Failed to release lease"),
+        }
+    }
+
+    /// Make one attempt to acquire or renew the Lease.
+    ///
+    /// Returns whether this replica holds the Lease afterwards. Losing a write
+    /// race to another candidate is not an error.
+    async fn try_acquire_or_renew(&self) -> Result<bool, Error> {
+        let now = Timestamp::now();
+
+        let Some(mut lease) = self.api.get_opt(&self.lease_name).await? else {
+            let lease = Lease {
+                metadata: ObjectMeta {
+                    name: Some(self.lease_name.clone()),
+                    ..Default::default()
+                },
+                spec: Some(LeaseSpec {
+                    holder_identity: Some(self.identity.clone()),
+                    lease_duration_seconds: Some(self.lease_duration_seconds()),
+                    acquire_time: Some(MicroTime(now)),
+                    renew_time: Some(MicroTime(now)),
+                    lease_transitions: Some(0),
+                    ..Default::default()
+                }),
+            };
+            return ignore_conflict(self.api.create(&PostParams::default(), &lease).await);
+        };
+
+        let spec = lease.spec.get_or_insert_with(Default::default);
+        let held_by_us = spec.holder_identity.as_deref() == Some(self.identity.as_str());
+
+        if !held_by_us {
+            let observed_at = self.observe(spec);
+            if !self.is_expired(spec, observed_at) {
+                return Ok(false);
+            }
+            tracing::info!(
+                lease = %self.lease_name,
+                previous = spec.holder_identity.as_deref().unwrap_or("<none>"),
+                "Acquiring expired or released lease"
+            );
+            spec.holder_identity = Some(self.identity.clone());
+            spec.acquire_time = Some(MicroTime(now));
+            spec.lease_transitions = Some(spec.lease_transitions.unwrap_or(0) + 1);
+        }
+        spec.lease_duration_seconds = Some(self.lease_duration_seconds());
+        spec.renew_time = Some(MicroTime(now));
+
+        ignore_conflict(
+            self.api
+                .replace(&self.lease_name, &PostParams::default(), &lease)
+                .await,
+        )
+    }
+
+    /// Record the Lease's holder and renew time, returning when this replica
+    /// first saw them in their current state.
+    fn observe(&self, spec: &LeaseSpec) -> Instant {
+        let mut observed = self.observed.lock().unwrap_or_else(|e| e.into_inner());
+        match &*observed {
+            Some(o) if o.holder == spec.holder_identity && o.renew_time == spec.renew_time => o.at,
+            _ => {
+                let at = Instant::now();
+                *observed = Some(Observed {
+                    holder: spec.holder_identity.clone(),
+                    renew_time: spec.renew_time.clone(),
+                    at,
+                });
+                at
+            }
+        }
+    }
+
+    /// A Lease is free to take if it has no holder, or its holder has not
+    /// renewed it for a lease duration since `observed_at`.
+    fn is_expired(&self, spec: &LeaseSpec, observed_at: Instant) -> bool {
+        if spec.holder_identity.as_deref().is_none_or(str::is_empty) {
+            return true;
+        }
+        let duration = spec
+            .lease_duration_seconds
+            .and_then(|secs| u64::try_from(secs).ok())
+            .map_or(self.lease_duration, Duration::from_secs);
+        observed_at.elapsed() > duration
+    }
+
+    fn lease_duration_seconds(&self) -> i32 {
+        i32::try_from(self.lease_duration.as_secs()).unwrap_or(i32::MAX)
+    }
+
+    fn set_leader(&self, is_leader: bool) {
+        self.leader.send_if_modified(|current| {
+            if *current == is_leader {
+                return false;
+            }
+            *current = is_leader;
+            if is_leader {
+                tracing::info!(lease = %self.lease_name, identity = %self.identity, "Acquired leadership");
+            } else {
+                tracing::info!(lease = %self.lease_name, identity = %self.identity, "Lost leadership");
+            }
+            true
+        });
+    }
+}
+
+/// Run `controllers` while this replica is the leader.
+///
+/// Waits until leadership is acquired before polling `controllers`. Returns an
+/// error if leadership is lost, so the process exits and rejoins the election
+/// from a clean state rather than continuing with possibly stale work.
+pub async fn run_while_leader<F>(
+    mut leader: watch::Receiver<bool>,
+    controllers: F,
+) -> anyhow::Result<()>
+where
+    F: Future<Output = ()>,
+{
+    leader.wait_for(|is_leader| *is_leader).await?;
+    tracing::info!("Starting controllers");
+
+    tokio::select! {
+        _ = controllers => Ok(()),
+        _ = leader.wait_for(|is_leader| !*is_leader) => anyhow::bail!("Lost leadership"),
+    }
+}
+
+/// Treat a 409 Conflict as "another candidate won this round".
+fn ignore_conflict(result: Result<Lease, kube::Error>) -> Result<bool, Error> {
+    match result {
+        Ok(_) => Ok(true),
+        Err(kube::Error::Api(status)) if status.is_conflict() => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::{Method, Request, Response, StatusCode};
+    use k8s_openapi::jiff::SignedDuration;
+    use kube::client::Body;
+    use tower_test::mock;
+
+    type Handle = mock::Handle<Request<Body>, Response<Body>>;
+
+    fn elector() -> (LeaderElector, Handle) {
+        let (mock_service, handle) = mock::pair::<Request<Body>, Response<Body>>();
+        let client = Client::new(mock_service, "default");
+        let elector = LeaderElector::new(client, &Config::default(), "pod-a".into());
+        (elector, handle)
+    }
+
+    fn lease(holder: Option<&str>, renewed: Timestamp) -> Lease {
+        Lease {
+            metadata: ObjectMeta {
+                name: Some("k8s-cloud-tagger".into()),
+                namespace: Some("default".into()),
+                resource_version: Some("1".into()),
+                ..Default::default()
+            },
+            spec: Some(LeaseSpec {
+                holder_identity: holder.map(Into::into),
+                lease_duration_seconds: Some(15),
+                renew_time: Some(MicroTime(renewed)),
+                lease_transitions: Some(3),
+                ..Default::default()
+            }),
+        }
+    }
+
+    fn json_response<T: serde::Serialize>(status: StatusCode, body: &T) -> Response<Body> {
+        Response::builder()
+            .status(status)
+            .body(Body::from(serde_json::to_vec(body).unwrap()))
+            .unwrap()
+    }
+
+    fn status_response(code: u16, reason: &str) -> Response<Body> {
+        let body = serde_json::json!({
+            "kind": "Status",
+            "apiVersion": "v1",
+            "status": "Failure",
+            "reason": reason,
+            "code": code,
+        });
+        json_response(StatusCode::from_u16(code).unwrap(), &body)
+    }
+
+    async fn expect_get(handle: &mut Handle, response: Response<Body>) {
+        let (request, send) = handle.next_request().await.expect("expected GET");
+        assert_eq!(request.method(), Method::GET);
+        assert!(
+            request
+                .uri()
+                .path()
+                .ends_with("/namespaces/default/leases/k8s-cloud-tagger")
+        );
+        send.send_response(response);
+    }
+
+    /// Accept a write request, echoing the submitted Lease back, and return it.
+    async fn expect_write(handle: &mut Handle, method: Method) -> Lease {
+        let (request, send) = handle.next_request().await.expect("expected write");
+        assert_eq!(request.method(), method);
+        let body = request.into_body().collect_bytes().await.unwrap();
+        let lease: Lease = serde_json::from_slice(&body).unwrap();
+        send.send_response(json_response(StatusCode::OK, &lease));
+        lease
+    }
+
+    #[tokio::test]
+    async fn creates_lease_when_missing() {
+        let (elector, mut handle) = elector();
+
+        let server = tokio::spawn(async move {
+            expect_get(&mut handle, status_response(404, "NotFound")).await;
+            expect_write(&mut handle, Method::POST).await
+        });
+
+        assert!(elector.try_acquire_or_renew().await.unwrap());
+
+        let created = server.await.unwrap();
+        let spec = created.spec.unwrap();
+        assert_eq!(spec.holder_identity.as_deref(), Some("pod-a"));
+        assert_eq!(spec.lease_duration_seconds, Some(15));
+        assert_eq!(spec.lease_transitions, Some(0));
+    }
+
+    #[tokio::test]
+    async fn renews_own_lease() {
+        let (elector, mut handle) = elector();
+        let renewed = Timestamp::now() - SignedDuration::from_secs(5);
+
+        let server = tokio::spawn(async move {
+            expect_get(
+                &mut handle,
+                json_response(StatusCode::OK, &lease(Some("pod-a"), renewed)),
+            )
+            .await;
+            expect_write(&mut handle, Method::PUT).await
+        });
+
+        assert!(elector.try_acquire_or_renew().await.unwrap());
+
+        let spec = server.await.unwrap().spec.unwrap();
+        assert_eq!(spec.holder_identity.as_deref(), Some("pod-a"));
+        assert_eq!(
+            spec.lease_transitions,
+            Some(3),
+            "renewal is not a transition"
+        );
+        assert!(spec.renew_time.unwrap().0 > renewed);
+    }
+
+    #[tokio::test]
+    async fn does_not_take_lease_held_by_another() {
+        let (elector, mut handle) = elector();
+
+        let server = tokio::spawn(async move {
+            expect_get(
+                &mut handle,
+                json_response(StatusCode::OK, &lease(Some("pod-b"), Timestamp::now())),
+            )
+            .await;
+        });
+
+        assert!(!elector.try_acquire_or_renew().await.unwrap());
+        server.await.unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn takes_over_expired_lease() {
+        let (elector, mut handle) = elector();
+        // The remote timestamp is ignored: expiry runs from when we saw it.
+        let stale = Timestamp::now() - SignedDuration::from_secs(60);
+
+        let server = tokio::spawn(async move {
+            expect_get(
+                &mut handle,
+                json_response(StatusCode::OK, &lease(Some("pod-b"), stale)),
+            )
+            .await;
+            tokio::time::sleep(Duration::from_secs(16)).await;
+            expect_get(
+                &mut handle,
+                json_response(StatusCode::OK, &lease(Some("pod-b"), stale)),
+            )
+            .await;
+            expect_write(&mut handle, Method::PUT).await
+        });
+
+        assert!(!elector.try_acquire_or_renew().await.unwrap());
+        tokio::time::sleep(Duration::from_secs(16)).await;
+        assert!(elector.try_acquire_or_renew().await.unwrap());
+
+        let spec = server.await.unwrap().spec.unwrap();
+        assert_eq!(spec.holder_identity.as_deref(), Some("pod-a"));
+        assert_eq!(spec.lease_transitions, Some(4));
+    }
+
+    #[tokio::test]
+    async fn takes_over_released_lease() {
+        let (elector, mut handle) = elector();
+
+        let server = tokio::spawn(async move {
+            expect_get(
+                &mut handle,
+                json_response(StatusCode::OK, &lease(None, Timestamp::now())),
+            )
+            .await;
+            expect_write(&mut handle, Method::PUT).await
+        });
+
+        assert!(elector.try_acquire_or_renew().await.unwrap());
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn conflict_means_another_candidate_won() {
+        let (elector, mut handle) = elector();
+
+        let server = tokio::spawn(async move {
+            expect_get(
+                &mut handle,
+                json_response(StatusCode::OK, &lease(None, Timestamp::now())),
+            )
+            .await;
+            let (_, send) = handle.next_request().await.expect("expected PUT");
+            send.send_response(status_response(409, "Conflict"));
+        });
+
+        assert!(!elector.try_acquire_or_renew().await.unwrap());
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn api_error_is_returned() {
+        let (elector, mut handle) = elector();
+
+        let server = tokio::spawn(async move {
+            expect_get(&mut handle, status_response(403, "Forbidden")).await;
+        });
+
+        assert!(elector.try_acquire_or_renew().await.is_err());
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn release_clears_holder() {
+        let (elector, mut handle) = elector();
+        let mut leader = elector.subscribe();
+        elector.set_leader(true);
+
+        let server = tokio::spawn(async move {
+            expect_get(
+                &mut handle,
+                json_response(StatusCode::OK, &lease(Some("pod-a"), Timestamp::now())),
+            )
+            .await;
+            expect_write(&mut handle, Method::PUT).await
+        });
+
+        elector.release().await;
+
+        let spec = server.await.unwrap().spec.unwrap();
+        assert_eq!(spec.holder_identity, None);
+        assert_eq!(spec.lease_duration_seconds, Some(1));
+        assert!(!*leader.borrow_and_update());
+    }
+
+    #[tokio::test]
+    async fn release_is_noop_when_not_leader() {
+        let (elector, handle) = elector();
+
+        // No request may be made: the mock panics on drop if one is pending.
+        elector.release().await;
+        drop(handle);
+    }
+
+    #[tokio::test]
+    async fn run_while_leader_waits_then_stops_on_loss() {
+        let (tx, rx) = watch::channel(false);
+        let started = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let flag = started.clone();
+
+        let task = tokio::spawn(run_while_leader(rx, async move {
+            flag.store(true, std::sync::atomic::Ordering::SeqCst);
+            std::future::pending::<()>().await
+        }));
+
+        tokio::task::yield_now().await;
+        assert!(!started.load(std::sync::atomic::Ordering::SeqCst));
+
+        tx.send(true).unwrap();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(started.load(std::sync::atomic::Ordering::SeqCst));
+
+        tx.send(false).unwrap();
+        let result = task.await.unwrap();
+        assert!(result.is_err(), "losing leadership should be an error");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn leader_steps_down_before_lease_is_takeable() {
+        let (elector, mut handle) = elector();
+        let mut leader = elector.subscribe();
+
+        tokio::spawn(async move {
+            expect_get(
+                &mut handle,
+                json_response(StatusCode::OK, &lease(Some("pod-a"), Timestamp::now())),
+            )
+            .await;
+            expect_write(&mut handle, Method::PUT).await;
+            // Every later renewal fails.
+            while let Some((_, send)) = handle.next_request().await {
+                send.send_response(status_response(500, "InternalError"));
+            }
+        });
+
+        tokio::select! {
+            _ = elector.run() => unreachable!("run never returns"),
+            held = async {
+                leader.wait_for(|is_leader| *is_leader).await.unwrap();
+                let renewed = Instant::now();
+                leader.wait_for(|is_leader| !*is_leader).await.unwrap();
+                renewed.elapsed()
+            } => {
+                let config = Config::default();
+                assert!(held >= config.lease_renew_deadline);
+                // A standby that saw the last renewal could not take over yet.
+                assert!(held < config.lease_duration, "stepped down after {held:?}");
+            }
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn renewal_resets_observed_expiry() {
+        let (elector, _handle) = elector();
+        let first = lease(Some("pod-b"), Timestamp::now());
+        let seen = elector.observe(first.spec.as_ref().unwrap());
+
+        tokio::time::advance(Duration::from_secs(10)).await;
+        let renewed = lease(Some("pod-b"), Timestamp::now());
+        assert_eq!(elector.observe(first.spec.as_ref().unwrap()), seen);
+        let seen_renewed = elector.observe(renewed.spec.as_ref().unwrap());
+        assert!(seen_renewed > seen);
+
+        tokio::time::advance(Duration::from_secs(10)).await;
+        assert!(elector.is_expired(first.spec.as_ref().unwrap(), seen));
+        assert!(!elector.is_expired(renewed.spec.as_ref().unwrap(), seen_renewed));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn expiry() {
+        let (elector, _handle) = elector();
+        let now = Instant::now();
+        let held = lease(Some("pod-b"), Timestamp::now());
+        let spec = held.spec.as_ref().unwrap();
+
+        assert!(elector.is_expired(&LeaseSpec::default(), now));
+        assert!(elector.is_expired(
+            lease(Some(""), Timestamp::now()).spec.as_ref().unwrap(),
+            now
+        ));
+        assert!(!elector.is_expired(spec, now));
+
+        tokio::time::advance(Duration::from_secs(15)).await;
+        assert!(!elector.is_expired(spec, now));
+        tokio::time::advance(Duration::from_secs(1)).await;
+        assert!(elector.is_expired(spec, now));
+    }
+}
@@ -1,72 +1,321 @@
+mod audit;
 mod cloud;
 mod config;
 mod error;
 mod health;
+mod labels;
+mod leader;
+mod managed;
 mod metrics;
 mod reconciler;
+mod reload;
 mod resources;
+mod stores;
+mod sync;
 mod tls;
 mod traits;
 
-use crate::cloud::MeteredClient;
+use crate::cloud::{CloudClient, DryRunClient, MeteredClient};
+use crate::config::LiveConfig;
+use crate::leader::{LeaderElector, run_while_leader};
 use crate::reconciler::Context;
 use crate::reconciler::{error_policy, reconcile};
-use futures::StreamExt;
-use k8s_openapi::api::core::v1::PersistentVolumeClaim;
+use crate::resources::snapshot::VolumeSnapshot;
+use crate::stores::{Stores, in_changed_namespace, scoped_apis};
+use anyhow::Context as _;
+use futures::future::join_all;
+use futures::{Stream, StreamExt, stream};
+use k8s_openapi::NamespaceResourceScope;
+use k8s_openapi::api::core::v1::{Namespace, PersistentVolume, PersistentVolumeClaim, Service};
 use kube::runtime::Controller;
 use kube::runtime::events::Reporter;
-use kube::runtime::watcher::Config;
-use kube::{Api, Client};
+use kube::runtime::watcher;
+use kube::{Api, Client, Resource};
+use serde::de::DeserializeOwned;
+use std::fmt::Debug;
 use std::sync::Arc;
 use tokio::signal;
+use tokio::signal::unix::{SignalKind, signal as unix_signal};
+use tokio::sync::watch;
 use tracing_subscriber::fmt::format::FmtSpan;
 
 macro_rules! controller {
-    ($t:ty, $client:expr, $ctx: expr) => {
-        Controller::new(Api::<$t>::all($client), Config::default())
+    ($controller:expr, $ctx: expr, $retag: expr) => {
+        $controller
+            .reconcile_all_on(retag_trigger(&$retag))
             .run(reconcile, error_policy, $ctx.clone())
             .for_each(|_| async move {})
     };
 }
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    tls::install_crypto_provider();
-
-    tracing_subscriber::fmt()
-        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
-        .with_span_events(FmtSpan::CLOSE)
-        .init();
+/// A stream yielding each time `rx` is sent to, for `Controller::reconcile_all_on`.
+fn retag_trigger(rx: &watch::Receiver<()>) -> impl Stream<Item = ()> + Send + Sync + 'static {
+    stream::unfold(rx.clone(), |mut rx| async move {
+        rx.changed().await.ok()?;
+        Some(((), rx))
+    })
+}
 
-    tracing::info!("Starting k8s-cloud-tagger");
+/// A controller for the namespaced objects in `api`, also reconciling the
+/// objects in a namespace when it changes, as they inherit its labels and
+/// enabled annotation.
+fn namespaced_controller<K>(
+    api: Api<K>,
+    watcher: &watcher::Config,
+    client: &Client,
+) -> Controller<K>
+where
+    K: Resource<Scope = NamespaceResourceScope, DynamicType = ()>
+        + Clone
+        + DeserializeOwned
+        + Debug
+        + Send
+        + Sync
+        + 'static,
+{
+    let namespaces = match api.namespace() {
+        Some(ns) => watcher::Config::default().fields(&format!("metadata.name={ns}")),
+        None => watcher::Config::default(),
+    };
+    let controller = Controller::new(api, watcher.clone());
+    let store = controller.store();
+    controller.watches(
+        Api::<Namespace>::all(client.clone()),
+        namespaces,
+        in_changed_namespace(store),
+    )
+}
 
-    let cfg = config::Config::load()?;
+/// Whether a context's cloud client may write tags.
+enum Writes {
+    /// Write unless the config file turns on dry-run mode.
+    Configured,
+    /// Log writes instead of making them, whatever the config file says.
+    DryRun,
+    /// Never asked to write, so the client goes undecorated.
+    ReadOnly,
+}
 
-    let probe_addr = cfg.probe_addr;
+/// Build the reconciler context: config, Kubernetes client and cloud client.
+async fn context(
+    writes: Writes,
+    instance: Option<String>,
+) -> anyhow::Result<Context<Box<dyn CloudClient>>> {
+    let mut cfg = config::Config::load()?;
+    match writes {
+        Writes::Configured => {}
+        Writes::DryRun => cfg.dry_run = true,
+        Writes::ReadOnly => cfg.dry_run = false,
+    }
 
     let client = Client::try_default().await?;
 
     let reporter = Reporter {
         controller: "k8s-cloud-tagger".to_string(),
-        instance: std::env::var("POD_NAME").ok(),
+        instance,
     };
 
-    let cloud = cloud::create_client(&cfg.cloud_provider).await?;
+    let mut cloud = cloud::create_client(&cfg).await?;
+    if cfg.dry_run {
+        tracing::warn!("Dry run: logging tag changes without writing them");
+        cloud = Box::new(DryRunClient::new(cloud));
+    }
 
-    let ctx = Arc::new(Context {
-        client: client.clone(),
-        config: cfg,
+    Ok(Context {
+        client,
+        config: LiveConfig::new(cfg),
         cloud: MeteredClient::new(cloud),
         reporter,
-    });
+        stores: Stores::default(),
+    })
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    tls::install_crypto_provider();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let subcommand = args.first().filter(|arg| !arg.starts_with('-')).cloned();
 
-    let pvc_ctrl = controller!(PersistentVolumeClaim, client, ctx);
+    // Subcommands print their results on stdout, so log to stderr.
+    let subscriber = tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_span_events(FmtSpan::CLOSE);
+    if subcommand.is_some() {
+        subscriber.with_writer(std::io::stderr).init();
+    } else {
+        subscriber.init();
+    }
+
+    let instance = std::env::var("POD_NAME")
+        .or_else(|_| std::env::var("HOSTNAME"))
+        .ok();
 
-    tokio::select! {
-        result = health::serve(probe_addr) => result?,
-        _ = pvc_ctrl => {}
-        _ = signal::ctrl_c() => tracing::debug!("Shutting down"),
+    match subcommand.as_deref() {
+        None => {
+            let writes = match args.as_slice() {
+                [] => Writes::Configured,
+                [flag] if flag == "--dry-run" => Writes::DryRun,
+                _ => anyhow::bail!("{USAGE}"),
+            };
+            run_controller(context(writes, instance.clone()).await?, instance).await
+        }
+        Some("sync") => {
+            let opts = sync::Options::parse(&args[1..]).context(USAGE)?;
+            let writes = if opts.dry_run {
+                Writes::DryRun
+            } else {
+                Writes::Configured
+            };
+            let ctx = with_stores(context(writes, instance).await?);
+            sync::run(&ctx, &opts).await
+        }
+        Some("audit") => {
+            let opts = audit::Options::parse(&args[1..]).context(USAGE)?;
+            // Audit only reads, and needs the real tags even when dryRun is set.
+            let ctx = with_stores(context(Writes::ReadOnly, instance).await?);
+            audit::run(&ctx, &opts).await
+        }
+        Some("validate-config") => match &args[1..] {
+            [path] => validate_config(path),
+            _ => anyhow::bail!("{USAGE}"),
+        },
+        Some(other) => anyhow::bail!("unknown subcommand '{other}'\n{USAGE}"),
     }
+}
+
+const USAGE: &str = "\
+Usage: k8s-cloud-tagger [--dry-run]
+       k8s-cloud-tagger sync [--dry-run] [--concurrency <n>]
+       k8s-cloud-tagger audit [--format json|csv] [--concurrency <n>]
+       k8s-cloud-tagger validate-config <path>";
+
+/// Check a config file without starting anything, printing every problem found.
+fn validate_config(path: &str) -> anyhow::Result<()> {
+    let raw = std::fs::read_to_string(path).with_context(|| format!("failed to read {path}"))?;
+    match config::Config::parse(&raw) {
+        Ok(_) => {
+            println!("{path}: OK");
+            Ok(())
+        }
+        Err(errors) => {
+            for error in &errors {
+                println!("{path}: {error}");
+            }
+            anyhow::bail!("{} error(s) in {path}", errors.len())
+        }
+    }
+}
+
+/// Watch the stores a one-shot command's lookups read, in the background.
+fn with_stores(mut ctx: Context<Box<dyn CloudClient>>) -> Context<Box<dyn CloudClient>> {
+    let (stores, reflectors) = Stores::watch(&ctx.client, &ctx.config.get());
+    tokio::spawn(reflectors);
+    ctx.stores = stores;
+    ctx
+}
+
+/// Run the long-lived controller, reconciling while this replica is the leader.
+async fn run_controller(
+    mut ctx: Context<Box<dyn CloudClient>>,
+    instance: Option<String>,
+) -> anyhow::Result<()> {
+    tracing::info!("Starting k8s-cloud-tagger");
+
+    // Settings read here only take effect on restart; see Config::keep_startup_settings.
+    let config = ctx.config.get();
+    let probe_addr = config.probe_addr;
+    let client = ctx.client.clone();
+
+    let identity = instance.unwrap_or_else(|| "k8s-cloud-tagger".to_string());
+    let elector = LeaderElector::new(client.clone(), &config, identity);
+    let leader = elector.subscribe();
+
+    // Only the leader watches the stores, alongside its controllers.
+    let (stores, reflectors) = Stores::watch(&client, &config);
+    ctx.stores = stores;
+    let ctx = Arc::new(ctx);
+
+    // Config reloads that change tags re-tag every resource.
+    let (retag, retag_rx) = watch::channel(());
+    let watcher = config.watcher_config();
+    let namespaces = config.watch_namespaces.clone();
+    if !namespaces.is_empty() {
+        tracing::info!(?namespaces, "Watching namespaces");
+    }
+
+    let pvc_ctrl = join_all(
+        scoped_apis::<PersistentVolumeClaim>(&client, &namespaces)
+            .into_iter()
+            .map(|api| controller!(namespaced_controller(api, &watcher, &client), ctx, retag_rx)),
+    );
+
+    // PersistentVolumes are cluster-scoped, so unclaimed ones only belong to a
+    // cluster-wide tagger.
+    let pv_ctrl = async {
+        if namespaces.is_empty() {
+            controller!(
+                Controller::new(
+                    Api::<PersistentVolume>::all(client.clone()),
+                    watcher.clone()
+                ),
+                ctx,
+                retag_rx
+            )
+            .await;
+        } else {
+            tracing::info!("Watching namespaces only, not tagging unclaimed PersistentVolumes");
+        }
+    };
+    let svc_ctrl = join_all(
+        scoped_apis::<Service>(&client, &namespaces)
+            .into_iter()
+            .map(|api| controller!(namespaced_controller(api, &watcher, &client), ctx, retag_rx)),
+    );
+
+    // VolumeSnapshot is a CRD from the CSI external-snapshotter and may not be installed.
+    let snapshots_installed = kube::discovery::group(&client, "snapshot.storage.k8s.io")
+        .await
+        .is_ok();
+    if !snapshots_installed {
+        tracing::info!("VolumeSnapshot CRDs not installed, not tagging snapshots");
+    }
+    let snapshot_ctrl = async {
+        if snapshots_installed {
+            join_all(
+                scoped_apis::<VolumeSnapshot>(&client, &namespaces)
+                    .into_iter()
+                    .map(|api| {
+                        controller!(namespaced_controller(api, &watcher, &client), ctx, retag_rx)
+                    }),
+            )
+            .await;
+        }
+    };
+
+    let controllers = async {
+        tokio::join!(pvc_ctrl, pv_ctrl, svc_ctrl, snapshot_ctrl, reflectors);
+    };
+
+    let mut sigterm = unix_signal(SignalKind::terminate())?;
+
+    let result = tokio::select! {
+        result = health::serve(probe_addr, leader.clone()) => result,
+        _ = reload::run(ctx.clone(), config::Config::path(), retag) => Ok(()),
+        _ = elector.run() => Ok(()),
+        result = run_while_leader(leader, controllers) => result,
+        _ = signal::ctrl_c() => {
+            tracing::debug!("Shutting down");
+            Ok(())
+        }
+        _ = sigterm.recv() => {
+            tracing::debug!("Received SIGTERM, shutting down");
+            Ok(())
+        }
+    };
+
+    // Hand the lease over straight away rather than making standbys wait for it to expire.
+    elector.release().await;
 
-    Ok(())
+    result
 }
@@ -0,0 +1,198 @@
+//! Tracks which cloud tags the controller owns.
+//!
+//! Cloud resources may carry tags written by people or other tools, so we
+//! only ever delete tags we wrote ourselves. The keys written on the last
+//! successful reconcile are recorded in an annotation on the Kubernetes
+//! object; a key that drops out of that set has become stale.
+//!
+//! Alongside the keys, a [`LastApplied`] record shows which cloud resource was
+//! tagged, with what, and when. Reconciles whose tags hash the same as the
+//! last sync skip the cloud entirely until the record is due a drift check.
+
+use crate::error::Error;
+use jiff::Timestamp;
+use kube::api::{Patch, PatchParams};
+use kube::{Client, Resource, ResourceExt};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Annotation holding a JSON array of the (sanitised) cloud tag keys last written.
+pub const MANAGED_TAGS_ANNOTATION: &str = "cloud-tagger.upgrades.dev/managed-tags";
+
+/// Annotation holding the JSON-encoded [`LastApplied`] record.
+pub const LAST_APPLIED_ANNOTATION: &str = "cloud-tagger.upgrades.dev/last-applied";
+
+/// Field manager used for server-side apply.
+const FIELD_MANAGER: &str = "k8s-cloud-tagger";
+
+/// Read the tag keys recorded on a resource.
+///
+/// A missing or malformed annotation means we don't know of any tags we own,
+/// so nothing will be removed.
+pub fn managed_keys<T: ResourceExt>(resource: &T) -> BTreeSet<String> {
+    let Some(value) = resource.annotations().get(MANAGED_TAGS_ANNOTATION) else {
+        return BTreeSet::new();
+    };
+    serde_json::from_str(value).unwrap_or_else(|e| {
+        tracing::warn!(
+            name = %resource.name_any(),
+            %e,
+            "Ignoring malformed {MANAGED_TAGS_ANNOTATION} annotation"
+        );
+        BTreeSet::new()
+    })
+}
+
+/// The last successful sync of a resource's tags to the cloud.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LastApplied {
+    /// Cloud provider of the tagged resource, e.g. `AWS`.
+    pub provider: String,
+    /// Provider-specific ID of the tagged resource.
+    pub resource_id: String,
+    /// Region of the tagged resource, if it was resolved separately from its ID.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    /// Account of the tagged resource, if it was resolved separately from its ID.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account: Option<String>,
+    /// [`tags_hash`] of the (sanitised) tags applied.
+    pub tags_hash: String,
+    /// When the tags were last written or confirmed up to date.
+    pub synced_at: Timestamp,
+}
+
+/// Read the last sync recorded on a resource, if any.
+///
+/// A malformed annotation is treated as missing, so the next reconcile checks
+/// the cloud and overwrites it.
+pub fn last_applied<T: ResourceExt>(resource: &T) -> Option<LastApplied> {
+    let value = resource.annotations().get(LAST_APPLIED_ANNOTATION)?;
+    serde_json::from_str(value)
+        .inspect_err(|e| {
+            tracing::warn!(
+                name = %resource.name_any(),
+                %e,
+                "Ignoring malformed {LAST_APPLIED_ANNOTATION} annotation"
+            );
+        })
+        .ok()
+}
+
+/// Hex-encoded SHA-256 of a tag set, stable across restarts and versions.
+pub fn tags_hash(tags: &BTreeMap<String, String>) -> String {
+    let json = serde_json::to_vec(tags).expect("a map of strings always serialises");
+    format!("{:x}", Sha256::digest(json))
+}
+
+/// Record the tag keys we now own and the sync that wrote them, using
+/// server-side apply so only our annotations are touched.
+///
+/// Both annotations go in one apply: fields this manager applied before but
+/// leaves out would be removed.
+pub async fn record_sync<T>(
+    client: &Client,
+    resource: &T,
+    keys: &BTreeSet<String>,
+    last_applied: &LastApplied,
+) -> Result<(), Error>
+where
+    T: Resource<DynamicType = ()> + ResourceExt,
+{
+    let keys = serde_json::to_string(keys).expect("a set of strings always serialises");
+    let last_applied =
+        serde_json::to_string(last_applied).expect("a LastApplied record always serialises");
+    let patch = serde_json::json!({
+        "apiVersion": T::api_version(&()),
+        "kind": T::kind(&()),
+        "metadata": {
+            "name": resource.name_any(),
+            "annotations": {
+                MANAGED_TAGS_ANNOTATION: keys,
+                LAST_APPLIED_ANNOTATION: last_applied,
+            },
+        },
+    });
+
+    // Goes through the raw request builder because `Api` can't be built
+    // generically over cluster- and namespace-scoped resources.
+    let request = kube::core::Request::new(T::url_path(&(), resource.namespace().as_deref()))
+        .patch(
+            &resource.name_any(),
+            &PatchParams::apply(FIELD_MANAGER),
+            &Patch::Apply(patch),
+        )
+        .map_err(kube::Error::BuildRequest)?;
+    client.request::<serde_json::Value>(request).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use k8s_openapi::api::core::v1::PersistentVolumeClaim;
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
+    use std::collections::BTreeMap;
+
+    fn pvc_with_annotation(value: Option<&str>) -> PersistentVolumeClaim {
+        PersistentVolumeClaim {
+            metadata: ObjectMeta {
+                name: Some("data".into()),
+                annotations: value
+                    .map(|v| BTreeMap::from([(MANAGED_TAGS_ANNOTATION.into(), v.into())])),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn reads_recorded_keys() {
+        let pvc = pvc_with_annotation(Some(r#"["env","team"]"#));
+
+        assert_eq!(
+            managed_keys(&pvc),
+            BTreeSet::from(["env".to_string(), "team".to_string()])
+        );
+    }
+
+    #[test]
+    fn missing_or_malformed_annotation_owns_nothing() {
+        assert!(managed_keys(&pvc_with_annotation(None)).is_empty());
+        assert!(managed_keys(&pvc_with_annotation(Some("env,team"))).is_empty());
+    }
+
+    #[test]
+    fn last_applied_round_trips() {
+        let record = LastApplied {
+            provider: "AWS".into(),
+            resource_id: "vol-0123456789cafe0".into(),
+            region: Some("eu-west-2".into()),
+            account: None,
+            tags_hash: tags_hash(&BTreeMap::from([("env".into(), "prod".into())])),
+            synced_at: "2026-10-17T12:00:00Z".parse().unwrap(),
+        };
+        let mut pvc = pvc_with_annotation(None);
+        pvc.metadata.annotations = Some(BTreeMap::from([(
+            LAST_APPLIED_ANNOTATION.into(),
+            serde_json::to_string(&record).unwrap(),
+        )]));
+
+        assert_eq!(last_applied(&pvc), Some(record));
+        assert_eq!(last_applied(&pvc_with_annotation(None)), None);
+    }
+
+    #[test]
+    fn tags_hash_depends_on_keys_and_values() {
+        let tags = BTreeMap::from([("env".to_string(), "prod".to_string())]);
+
+        assert_eq!(tags_hash(&tags), tags_hash(&tags.clone()));
+        assert_ne!(
+            tags_hash(&tags),
+            tags_hash(&BTreeMap::from([("env".into(), "dev".into())]))
+        );
+        assert_ne!(tags_hash(&tags), tags_hash(&BTreeMap::new()));
+    }
+}
@@ -5,6 +5,16 @@ use std::sync::LazyLock;
 pub mod labels {
     pub const SUCCESS: &str = "success";
     pub const ERROR: &str = "error";
+    /// Skip reasons
+    pub const OPTED_OUT: &str = "opted_out";
+    pub const NOT_OPTED_IN: &str = "not_opted_in";
+    /// Tag write outcomes
+    pub const WRITTEN: &str = "written";
+    pub const UNCHANGED: &str = "unchanged";
+    pub const WOULD_WRITE: &str = "would_write";
+    /// Config reload outcomes
+    pub const APPLIED: &str = "applied";
+    pub const REJECTED: &str = "rejected";
 }
 
 /// Total reconciliations by resource and outcome (success/error)
@@ -33,6 +43,37 @@ pub static RECONCILE_ACTIVE: LazyLock<IntGaugeVec> = LazyLock::new(|| {
     register_int_gauge_vec!("reconcile_active", "Active reconciliations", &["resource"]).unwrap()
 });
 
+/// Reconciliations skipped because tagging is disabled, by resource and reason
+pub static RECONCILE_SKIPPED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "reconcile_skipped_total",
+        "Reconciliations skipped because tagging is disabled",
+        &["resource", "reason"]
+    )
+    .unwrap()
+});
+
+/// Cloud tag writes by resource and outcome (written, skipped as unchanged, or
+/// would have been written in a dry run)
+pub static TAG_WRITES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "tag_writes_total",
+        "Cloud tag writes, and writes skipped because the tags were up to date",
+        &["resource", "outcome"]
+    )
+    .unwrap()
+});
+
+/// Config file reloads by outcome (applied, or rejected as invalid)
+pub static CONFIG_RELOADS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "config_reloads_total",
+        "Config file reloads, and changed files rejected as invalid",
+        &["outcome"]
+    )
+    .unwrap()
+});
+
 /// Errors by resource and error type
 pub static ERRORS: LazyLock<IntCounterVec> = LazyLock::new(|| {
     register_int_counter_vec!(
@@ -1,24 +1,82 @@
 use crate::cloud::{CloudClient, MeteredClient};
-use crate::config::Config;
+use crate::config::{Config, LiveConfig, TaggingMode};
 use crate::error::Error;
-use crate::metrics::{ERRORS, RECONCILE_ACTIVE, RECONCILE_COUNT, RECONCILE_DURATION, labels};
-use crate::traits::CloudTaggable;
+use crate::labels::annotation_tags;
+use crate::managed::{LastApplied, last_applied, managed_keys, record_sync, tags_hash};
+use crate::metrics::{
+    ERRORS, RECONCILE_ACTIVE, RECONCILE_COUNT, RECONCILE_DURATION, RECONCILE_SKIPPED, TAG_WRITES,
+    labels,
+};
+use crate::stores::Stores;
+use crate::traits::{CloudResource, CloudTaggable};
+use jiff::{SignedDuration, Timestamp};
 use kube::runtime::controller::Action;
 use kube::runtime::events::{Event, EventType, Recorder, Reporter};
 use kube::{Client, Resource, ResourceExt};
+use std::collections::{BTreeMap, BTreeSet};
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
+
+/// Annotation enabling (`"true"`) or disabling (`"false"`) tagging for a
+/// resource, or for every resource in a namespace. A resource's annotation
+/// overrides its namespace's.
+pub const ENABLED_ANNOTATION: &str = "cloud-tagger.upgrades.dev/enabled";
 
 /// Shared state for the reconciler, passed to every reconciliation call.
 pub struct Context<C: CloudClient> {
     /// Kubernetes API client.
     pub client: Client,
-    /// Controller configuration (requeue intervals, etc.).
-    pub config: Config,
+    /// Controller configuration (requeue intervals, etc.), reloaded when the
+    /// config file changes.
+    pub config: LiveConfig,
     /// Cloud provider API client with metrics instrumentation.
     pub cloud: MeteredClient<C>,
     /// Event reporter identity (controller name and pod instance).
     pub reporter: Reporter,
+    /// Cached cluster objects read while reconciling.
+    pub stores: Stores,
+}
+
+/// What a reconcile did with a resource.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    /// Tags were written to (or removed from) the cloud resource.
+    Tagged,
+    /// The cloud resource's tags were already up to date.
+    Unchanged,
+    /// Tagging is disabled for the resource, by annotation or tagging mode.
+    Skipped,
+    /// The cloud resource isn't known yet (e.g. an unbound PVC).
+    NotReady,
+    /// The resource is being deleted or can't be tagged.
+    Ignored,
+}
+
+impl Outcome {
+    /// When the controller should look at the resource again.
+    fn action(self, config: &Config) -> Action {
+        match self {
+            // Skipped resources are requeued rather than waiting for a change:
+            // a namespace annotation can change without the resource changing.
+            Outcome::Tagged | Outcome::Unchanged | Outcome::Skipped => {
+                Action::requeue(config.requeue_success)
+            }
+            Outcome::NotReady => Action::requeue(config.requeue_not_ready),
+            Outcome::Ignored => Action::await_change(),
+        }
+    }
+}
+
+impl std::fmt::Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Outcome::Tagged => write!(f, "tagged"),
+            Outcome::Unchanged => write!(f, "unchanged"),
+            Outcome::Skipped => write!(f, "skipped"),
+            Outcome::NotReady => write!(f, "not ready"),
+            Outcome::Ignored => write!(f, "ignored"),
+        }
+    }
 }
 
 /// Main reconcile entry point, called by the kube-rs controller runtime.
@@ -56,7 +114,17 @@ where
         }
     }
 
-    result
+    result.map(|outcome| outcome.action(&ctx.config.get()))
+}
+
+/// Reconcile a resource once, outside the controller runtime and its metrics.
+pub async fn reconcile_once<T, C>(resource: &T, ctx: &Context<C>) -> Result<Outcome, Error>
+where
+    T: CloudTaggable + ResourceExt,
+    C: CloudClient,
+{
+    let (kind, namespace, name) = resource_ref(resource);
+    do_reconcile(resource, ctx, &kind, &namespace, &name).await
 }
 
 async fn do_reconcile<T, C>(
@@ -65,66 +133,315 @@ async fn do_reconcile<T, C>(
     kind: &str,
     namespace: &str,
     name: &str,
-) -> Result<Action, Error>
+) -> Result<Outcome, Error>
 where
     T: CloudTaggable + ResourceExt,
     C: CloudClient,
 {
+    let config = ctx.config.get();
+
     // Skip resources that are being deleted.
     if resource.meta().deletion_timestamp.is_some() {
         tracing::debug!(%kind, %namespace, %name, "Resource is being deleted, skipping");
-        return Ok(Action::await_change());
+        return Ok(Outcome::Ignored);
+    }
+
+    if !resource.is_taggable() {
+        tracing::debug!(%kind, %namespace, %name, "Resource is not taggable, skipping");
+        return Ok(Outcome::Ignored);
+    }
+
+    if let Some(reason) = skip_reason(resource, ctx, &config).await? {
+        tracing::debug!(%kind, %namespace, %name, %reason, "Tagging disabled, skipping");
+        RECONCILE_SKIPPED.with_label_values(&[kind, reason]).inc();
+        publish_event(
+            ctx,
+            resource,
+            Event {
+                type_: EventType::Normal,
+                reason: "Skipped".into(),
+                note: Some(match reason {
+                    labels::OPTED_OUT => format!("Tagging disabled by {ENABLED_ANNOTATION}"),
+                    _ => format!("Tagging is opt-in; set {ENABLED_ANNOTATION}: \"true\" to enable"),
+                }),
+                action: "SkipCloudResource".into(),
+                secondary: None,
+            },
+        )
+        .await;
+        return Ok(Outcome::Skipped);
     }
 
     // Resolve the cloud resource (may need intermediate lookups)
-    let cloud_resource = resource.resolve_cloud_resource(&ctx.client).await?;
+    let cloud_resource = match resource
+        .resolve_cloud_resource(&ctx.client, &ctx.stores, &config)
+        .await
+    {
+        Ok(cloud_resource) => cloud_resource,
+        // Misconfigured objects won't resolve until someone fixes them, so say
+        // so on the resource as well as in the logs.
+        Err(e @ Error::Config(_)) => {
+            publish_event(
+                ctx,
+                resource,
+                Event {
+                    type_: EventType::Warning,
+                    reason: "InvalidConfig".into(),
+                    note: Some(e.to_string()),
+                    action: "ResolveCloudResource".into(),
+                    secondary: None,
+                },
+            )
+            .await;
+            return Err(e);
+        }
+        Err(e) => return Err(e),
+    };
 
     match cloud_resource {
-        Some(cr) => {
+        Some(mut cr) => {
+            cr.labels = desired_tags(resource, cr.labels, &config);
+
             tracing::info!(
                 %kind, %namespace, %name,
                 provider = %cr.provider,
+                cloud_kind = %cr.kind,
                 resource_id = %cr.resource_id,
                 labels = ?cr.labels,
                 "Ready to tag cloud resource"
             );
 
-            // Calls the cloud provider API and sets tags on the resource.
-            ctx.cloud.set_tags(&cr.resource_id, &cr.labels).await?;
+            let desired = ctx.cloud.sanitise(&cr.labels);
+            let hash = tags_hash(&desired);
+            let owned = managed_keys(resource);
+            let current: BTreeSet<String> = desired.keys().cloned().collect();
+
+            let recent = last_applied(resource).is_some_and(|previous| {
+                is_recent_sync(&previous, &cr, &hash, config.drift_check_interval)
+            });
+
+            // Trust a recent sync of the same tags without asking the cloud.
+            if owned == current && recent {
+                tracing::debug!(
+                    %kind, %namespace, %name,
+                    resource_id = %cr.resource_id,
+                    "Tags unchanged since last sync, skipping"
+                );
+                TAG_WRITES
+                    .with_label_values(&[kind, labels::UNCHANGED])
+                    .inc();
+                return Ok(Outcome::Unchanged);
+            }
 
-            // Publish a Kubernetes event explaining that we successfully tagged the resource.
-            let recorder = Recorder::new(ctx.client.clone(), ctx.reporter.clone());
+            // Compare against the tags already on the resource, so unchanged
+            // resources cost one read rather than a write.
+            let existing = ctx
+                .cloud
+                .get_tags(cr.kind, &cr.resource_id, &cr.location)
+                .await?;
+            let changes = changed_tags(&desired, &existing);
+            let drifted = !changes.is_empty();
 
-            // Events are best-effort, don't trigger reconciliation again.
-            if let Err(e) = recorder
-                .publish(
-                    &Event {
+            // Tags we wrote previously whose label has since gone, if still present.
+            let stale: BTreeSet<String> = owned
+                .difference(&current)
+                .filter(|k| existing.contains_key(*k))
+                .cloned()
+                .collect();
+
+            let outcome = if drifted || !stale.is_empty() {
+                if drifted {
+                    if config.dry_run {
+                        tracing::info!(
+                            %kind, %namespace, %name,
+                            resource_id = %cr.resource_id,
+                            ?changes,
+                            "Dry run: would set tags"
+                        );
+                    }
+                    // Calls the cloud provider API and sets tags on the resource.
+                    ctx.cloud
+                        .set_tags(cr.kind, &cr.resource_id, &cr.location, &cr.labels)
+                        .await?;
+                }
+                if !stale.is_empty() {
+                    tracing::info!(
+                        %kind, %namespace, %name,
+                        resource_id = %cr.resource_id,
+                        ?stale,
+                        "Removing stale tags"
+                    );
+                    ctx.cloud
+                        .remove_tags(cr.kind, &cr.resource_id, &cr.location, &stale)
+                        .await?;
+                }
+                let (write, reason, verb) = if config.dry_run {
+                    (labels::WOULD_WRITE, "DryRun", "Would tag")
+                } else {
+                    (labels::WRITTEN, "Tagged", "Tagged")
+                };
+                TAG_WRITES.with_label_values(&[kind, write]).inc();
+
+                // Publish a Kubernetes event explaining that we successfully tagged the resource.
+                publish_event(
+                    ctx,
+                    resource,
+                    Event {
                         type_: EventType::Normal,
-                        reason: "Tagged".into(),
+                        reason: reason.into(),
                         note: Some(format!(
-                            "Tagged {} with {} label(s)",
+                            "{verb} {} with {} label(s)",
                             cr.resource_id,
                             cr.labels.len(),
                         )),
                         action: "TagCloudResource".into(),
                         secondary: None,
                     },
-                    &resource.object_ref(&()),
                 )
-                .await
-            {
-                tracing::warn!(%kind, %namespace, %name, %e, "Failed to publish event");
+                .await;
+                Outcome::Tagged
+            } else {
+                tracing::debug!(
+                    %kind, %namespace, %name,
+                    resource_id = %cr.resource_id,
+                    "Tags up to date, skipping write"
+                );
+                TAG_WRITES
+                    .with_label_values(&[kind, labels::UNCHANGED])
+                    .inc();
+                Outcome::Unchanged
+            };
+
+            // Nothing was written in a dry run, so there's no sync to record.
+            // Otherwise only patch the object when the record would change:
+            // different keys, a different resource or tags, or a stale sync.
+            if !config.dry_run && (owned != current || !recent) {
+                let synced = LastApplied {
+                    provider: cr.provider.to_string(),
+                    resource_id: cr.resource_id.clone(),
+                    region: cr.location.region.clone(),
+                    account: cr.location.account.clone(),
+                    tags_hash: hash,
+                    synced_at: Timestamp::now(),
+                };
+                record_sync(&ctx.client, resource, &current, &synced).await?;
             }
 
-            Ok(Action::requeue(ctx.config.requeue_success))
+            Ok(outcome)
         }
         None => {
             tracing::debug!(%kind, %namespace, %name, "Not ready");
-            Ok(Action::requeue(ctx.config.requeue_not_ready))
+            Ok(Outcome::NotReady)
+        }
+    }
+}
+
+/// The tags a resource's cloud resource should carry, given the labels it
+/// resolved to: filtered and mapped labels over the default tags, under any
+/// tags requested by annotation.
+pub fn desired_tags<T: ResourceExt>(
+    resource: &T,
+    labels: BTreeMap<String, String>,
+    config: &Config,
+) -> BTreeMap<String, String> {
+    let labels = config.label_filter.apply(labels);
+    let labels = config.label_mapping.apply(labels, config.cloud_provider);
+    // Defaults go underneath, so the resource's own labels win. Tags
+    // requested by annotation are explicit, so win over labels.
+    let mut tags = config.default_tags.clone();
+    tags.extend(labels);
+    tags.extend(annotation_tags(resource.annotations()));
+    tags
+}
+
+/// Tags in `desired` that are missing from `existing` or have a different value.
+fn changed_tags(
+    desired: &BTreeMap<String, String>,
+    existing: &BTreeMap<String, String>,
+) -> BTreeMap<String, String> {
+    desired
+        .iter()
+        .filter(|(k, v)| existing.get(*k) != Some(*v))
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .collect()
+}
+
+/// Whether `previous` recorded these tags on this cloud resource within `max_age`.
+fn is_recent_sync(
+    previous: &LastApplied,
+    cr: &CloudResource,
+    hash: &str,
+    max_age: Duration,
+) -> bool {
+    let max_age = SignedDuration::try_from(max_age).unwrap_or(SignedDuration::MAX);
+    previous.provider == cr.provider.to_string()
+        && previous.resource_id == cr.resource_id
+        && previous.region == cr.location.region
+        && previous.account == cr.location.account
+        && previous.tags_hash == hash
+        && Timestamp::now().duration_since(previous.synced_at) < max_age
+}
+
+/// Decide whether tagging is disabled for a resource, returning the metric
+/// label for why.
+///
+/// The resource's [`ENABLED_ANNOTATION`] wins, then its namespace's, then the
+/// configured [`TaggingMode`].
+pub async fn skip_reason<T, C>(
+    resource: &T,
+    ctx: &Context<C>,
+    config: &Config,
+) -> Result<Option<&'static str>, Error>
+where
+    T: CloudTaggable + ResourceExt,
+    C: CloudClient,
+{
+    let mut enabled = enabled_annotation(resource.annotations());
+    if enabled.is_none()
+        && let Some(namespace) = resource.namespace()
+    {
+        enabled = ctx
+            .stores
+            .namespace(&ctx.client, &namespace)
+            .await?
+            .and_then(|ns| enabled_annotation(&ns.annotations.unwrap_or_default()));
+    }
+
+    Ok(match enabled {
+        Some(true) => None,
+        Some(false) => Some(labels::OPTED_OUT),
+        None => match config.tagging_mode {
+            TaggingMode::OptOut => None,
+            TaggingMode::OptIn => Some(labels::NOT_OPTED_IN),
+        },
+    })
+}
+
+fn enabled_annotation(annotations: &BTreeMap<String, String>) -> Option<bool> {
+    let value = annotations.get(ENABLED_ANNOTATION)?;
+    match value.as_str() {
+        "true" => Some(true),
+        "false" => Some(false),
+        _ => {
+            tracing::warn!(%value, "Ignoring {ENABLED_ANNOTATION} annotation, expected \"true\" or \"false\"");
+            None
         }
     }
 }
 
+/// Events are best-effort: failures are logged and don't fail reconciliation.
+async fn publish_event<T, C>(ctx: &Context<C>, resource: &T, event: Event)
+where
+    T: CloudTaggable + ResourceExt,
+    C: CloudClient,
+{
+    let recorder = Recorder::new(ctx.client.clone(), ctx.reporter.clone());
+    if let Err(e) = recorder.publish(&event, &resource.object_ref(&())).await {
+        let (kind, namespace, name) = resource_ref(resource);
+        tracing::warn!(%kind, %namespace, %name, %e, "Failed to publish event");
+    }
+}
+
 /// Called by the controller runtime when reconciliation returns an error.
 pub fn error_policy<T, C>(resource: Arc<T>, error: &Error, ctx: Arc<Context<C>>) -> Action
 where
@@ -133,7 +450,7 @@ where
 {
     let (kind, namespace, name) = resource_ref(resource.as_ref());
     tracing::error!(%kind, %namespace, %name, %error, "Reconciliation error");
-    Action::requeue(ctx.config.requeue_error)
+    Action::requeue(ctx.config.get().requeue_error)
 }
 
 fn resource_ref<T>(resource: &T) -> (String, String, String)
@@ -152,7 +469,7 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::traits::{CloudProvider, CloudResource};
+    use crate::traits::{CloudProvider, CloudResource, Location, ResourceKind};
     use async_trait::async_trait;
     use bytes::Bytes;
     use jiff::Timestamp;
@@ -170,6 +487,8 @@ mod tests {
         tag_calls: Arc<AtomicUsize>,
         last_resource_id: Arc<Mutex<String>>,
         last_labels: Arc<Mutex<BTreeMap<String, String>>>,
+        removed_keys: Arc<Mutex<Option<BTreeSet<String>>>>,
+        existing_tags: BTreeMap<String, String>,
         should_fail: bool,
     }
 
@@ -179,6 +498,8 @@ mod tests {
                 tag_calls: Arc::new(AtomicUsize::new(0)),
                 last_resource_id: Arc::new(Mutex::new(String::new())),
                 last_labels: Arc::new(Mutex::new(BTreeMap::new())),
+                removed_keys: Arc::new(Mutex::new(None)),
+                existing_tags: BTreeMap::new(),
                 should_fail: false,
             }
         }
@@ -190,9 +511,24 @@ mod tests {
             "mock"
         }
 
+        fn sanitise(&self, labels: &BTreeMap<String, String>) -> BTreeMap<String, String> {
+            labels.clone()
+        }
+
+        async fn get_tags(
+            &self,
+            _kind: ResourceKind,
+            _resource_id: &str,
+            _location: &Location,
+        ) -> Result<BTreeMap<String, String>, Error> {
+            Ok(self.existing_tags.clone())
+        }
+
         async fn set_tags(
             &self,
+            _kind: ResourceKind,
             resource_id: &str,
+            _location: &Location,
             labels: &BTreeMap<String, String>,
         ) -> Result<(), Error> {
             self.tag_calls.fetch_add(1, Ordering::Relaxed);
@@ -203,6 +539,17 @@ mod tests {
             }
             Ok(())
         }
+
+        async fn remove_tags(
+            &self,
+            _kind: ResourceKind,
+            _resource_id: &str,
+            _location: &Location,
+            keys: &BTreeSet<String>,
+        ) -> Result<(), Error> {
+            *self.removed_keys.lock().unwrap() = Some(keys.clone());
+            Ok(())
+        }
     }
 
     // =========================================================================
@@ -214,6 +561,7 @@ mod tests {
         meta: ObjectMeta,
         cloud_resource: Option<CloudResource>,
         resolve_error: bool,
+        taggable: bool,
     }
 
     impl Resource for MockResource {
@@ -241,9 +589,15 @@ mod tests {
     }
 
     impl CloudTaggable for MockResource {
+        fn is_taggable(&self) -> bool {
+            self.taggable
+        }
+
         async fn resolve_cloud_resource(
             &self,
             _client: &Client,
+            _stores: &Stores,
+            _config: &Config,
         ) -> Result<Option<CloudResource>, Error> {
             if self.resolve_error {
                 return Err(Error::CloudApi("resolve failed".into()));
@@ -273,15 +627,71 @@ mod tests {
         Client::new(mock_service, "default")
     }
 
+    /// Like [`mock_client`], but GETs of the `default` namespace return it with
+    /// the given [`ENABLED_ANNOTATION`] value.
+    fn mock_client_with_namespace(enabled: &'static str) -> Client {
+        let mock_service = tower::service_fn(move |req: http::Request<kube::client::Body>| {
+            let body = if req.uri().path() == "/api/v1/namespaces/default" {
+                serde_json::json!({
+                    "apiVersion": "meta.k8s.io/v1",
+                    "kind": "PartialObjectMetadata",
+                    "metadata": {
+                        "name": "default",
+                        "annotations": { ENABLED_ANNOTATION: enabled },
+                    },
+                })
+                .to_string()
+            } else {
+                r#"{"kind":"Status","status":"Success"}"#.to_string()
+            };
+            async move {
+                Ok::<_, std::convert::Infallible>(
+                    http::Response::builder()
+                        .status(200)
+                        .body(kube::client::Body::from(Bytes::from(body)))
+                        .unwrap(),
+                )
+            }
+        });
+        Client::new(mock_service, "default")
+    }
+
+    /// Like [`mock_client`], counting the PATCH requests made through it.
+    fn mock_client_counting_patches() -> (Client, Arc<AtomicUsize>) {
+        let patches = Arc::new(AtomicUsize::new(0));
+        let counter = patches.clone();
+        let mock_service = tower::service_fn(move |req: http::Request<kube::client::Body>| {
+            if req.method() == http::Method::PATCH {
+                counter.fetch_add(1, Ordering::Relaxed);
+            }
+            async {
+                Ok::<_, std::convert::Infallible>(
+                    http::Response::builder()
+                        .status(200)
+                        .body(kube::client::Body::from(Bytes::from(
+                            r#"{"kind":"Status","status":"Success"}"#,
+                        )))
+                        .unwrap(),
+                )
+            }
+        });
+        (Client::new(mock_service, "default"), patches)
+    }
+
     fn test_ctx(cloud: MockCloud) -> Context<MockCloud> {
+        test_ctx_with_config(cloud, Config::default())
+    }
+
+    fn test_ctx_with_config(cloud: MockCloud, config: Config) -> Context<MockCloud> {
         Context {
             client: mock_client(),
-            config: Default::default(),
+            config: LiveConfig::new(config),
             cloud: MeteredClient::new(cloud),
             reporter: Reporter {
                 controller: "test".into(),
                 instance: None,
             },
+            stores: Stores::default(),
         }
     }
 
@@ -294,13 +704,16 @@ mod tests {
             },
             cloud_resource,
             resolve_error: false,
+            taggable: true,
         }
     }
 
     fn sample_cloud_resource() -> CloudResource {
         CloudResource {
             provider: CloudProvider::Mock,
+            kind: ResourceKind::Disk,
             resource_id: "vol-abc123".into(),
+            location: Location::default(),
             labels: BTreeMap::from([("upgrades.dev/app".into(), "k8s-cloud-tagger".into())]),
         }
     }
@@ -320,7 +733,7 @@ mod tests {
 
         let result = do_reconcile(&resource, &ctx, "mockresource", "default", "my-pvc").await;
 
-        assert!(result.is_ok(), "reconcile should succeed");
+        assert_eq!(result.unwrap(), Outcome::Tagged);
         assert_eq!(
             calls.load(Ordering::Relaxed),
             1,
@@ -342,7 +755,7 @@ mod tests {
 
         let result = do_reconcile(&resource, &ctx, "mockresource", "default", "pending-pvc").await;
 
-        assert!(result.is_ok(), "reconcile should succeed (requeue)");
+        assert_eq!(result.unwrap(), Outcome::NotReady);
         assert_eq!(
             calls.load(Ordering::Relaxed),
             0,
@@ -399,6 +812,25 @@ mod tests {
         let _action = error_policy(resource, &error, ctx);
     }
 
+    #[tokio::test]
+    async fn skips_untaggable_resource() {
+        let cloud = MockCloud::default();
+        let calls = cloud.tag_calls.clone();
+        let ctx = test_ctx(cloud);
+        let mut resource = mock_resource("clusterip-svc", Some(sample_cloud_resource()));
+        resource.taggable = false;
+
+        let result =
+            do_reconcile(&resource, &ctx, "mockresource", "default", "clusterip-svc").await;
+
+        assert!(result.is_ok(), "reconcile should succeed");
+        assert_eq!(
+            calls.load(Ordering::Relaxed),
+            0,
+            "cloud API should not be called for untaggable resource"
+        );
+    }
+
     #[tokio::test]
     async fn skips_deleted_resource() {
         let cloud = MockCloud::default();
@@ -418,4 +850,401 @@ mod tests {
             "cloud API should not be called for deleted resource"
         );
     }
+
+    #[tokio::test]
+    async fn removes_stale_managed_tags() {
+        let cloud = MockCloud {
+            existing_tags: BTreeMap::from([("team".into(), "platform".into())]),
+            ..Default::default()
+        };
+        let removed = cloud.removed_keys.clone();
+        let ctx = test_ctx(cloud);
+        let mut resource = mock_resource("my-pvc", Some(sample_cloud_resource()));
+        resource.meta.annotations = Some(BTreeMap::from([(
+            crate::managed::MANAGED_TAGS_ANNOTATION.into(),
+            r#"["team","upgrades.dev/app"]"#.into(),
+        )]));
+
+        let result = do_reconcile(&resource, &ctx, "mockresource", "default", "my-pvc").await;
+
+        assert!(result.is_ok(), "reconcile should succeed");
+        assert_eq!(
+            *removed.lock().unwrap(),
+            Some(BTreeSet::from(["team".to_string()]))
+        );
+    }
+
+    #[tokio::test]
+    async fn does_not_remove_tags_it_does_not_own() {
+        let cloud = MockCloud::default();
+        let removed = cloud.removed_keys.clone();
+        let ctx = test_ctx(cloud);
+        let resource = mock_resource("my-pvc", Some(sample_cloud_resource()));
+
+        let result = do_reconcile(&resource, &ctx, "mockresource", "default", "my-pvc").await;
+
+        assert!(result.is_ok(), "reconcile should succeed");
+        assert_eq!(*removed.lock().unwrap(), None, "nothing should be removed");
+    }
+
+    #[tokio::test]
+    async fn skips_write_when_tags_up_to_date() {
+        let cloud = MockCloud {
+            existing_tags: BTreeMap::from([
+                ("upgrades.dev/app".into(), "k8s-cloud-tagger".into()),
+                ("owner".into(), "someone-else".into()),
+            ]),
+            ..Default::default()
+        };
+        let calls = cloud.tag_calls.clone();
+        let removed = cloud.removed_keys.clone();
+        let ctx = test_ctx(cloud);
+        let mut resource = mock_resource("my-pvc", Some(sample_cloud_resource()));
+        resource.meta.annotations = Some(BTreeMap::from([(
+            crate::managed::MANAGED_TAGS_ANNOTATION.into(),
+            r#"["team","upgrades.dev/app"]"#.into(),
+        )]));
+
+        let result = do_reconcile(&resource, &ctx, "mockresource", "default", "my-pvc").await;
+
+        assert!(result.is_ok(), "reconcile should succeed");
+        assert_eq!(calls.load(Ordering::Relaxed), 0, "tags are up to date");
+        assert_eq!(*removed.lock().unwrap(), None, "stale tag already gone");
+    }
+
+    #[tokio::test]
+    async fn writes_when_tag_value_drifted() {
+        let cloud = MockCloud {
+            existing_tags: BTreeMap::from([("upgrades.dev/app".into(), "edited".into())]),
+            ..Default::default()
+        };
+        let calls = cloud.tag_calls.clone();
+        let ctx = test_ctx(cloud);
+        let resource = mock_resource("my-pvc", Some(sample_cloud_resource()));
+
+        let result = do_reconcile(&resource, &ctx, "mockresource", "default", "my-pvc").await;
+
+        assert!(result.is_ok(), "reconcile should succeed");
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+    }
+
+    fn annotate_last_applied(resource: &mut MockResource, synced_at: Timestamp) {
+        let record = LastApplied {
+            provider: "Mock".into(),
+            resource_id: "vol-abc123".into(),
+            region: None,
+            account: None,
+            tags_hash: tags_hash(&sample_cloud_resource().labels),
+            synced_at,
+        };
+        let annotations = resource.meta.annotations.get_or_insert_default();
+        annotations.insert(
+            crate::managed::MANAGED_TAGS_ANNOTATION.into(),
+            r#"["upgrades.dev/app"]"#.into(),
+        );
+        annotations.insert(
+            crate::managed::LAST_APPLIED_ANNOTATION.into(),
+            serde_json::to_string(&record).unwrap(),
+        );
+    }
+
+    #[tokio::test]
+    async fn skips_cloud_when_recently_synced() {
+        let cloud = MockCloud::default();
+        let calls = cloud.tag_calls.clone();
+        let ctx = test_ctx(cloud);
+        let mut resource = mock_resource("my-pvc", Some(sample_cloud_resource()));
+        annotate_last_applied(&mut resource, Timestamp::now());
+
+        let result = do_reconcile(&resource, &ctx, "mockresource", "default", "my-pvc").await;
+
+        assert_eq!(result.unwrap(), Outcome::Unchanged);
+        assert_eq!(calls.load(Ordering::Relaxed), 0, "last sync is trusted");
+    }
+
+    #[tokio::test]
+    async fn checks_cloud_when_last_sync_is_old_or_different() {
+        let cloud = MockCloud::default();
+        let calls = cloud.tag_calls.clone();
+        let ctx = test_ctx(cloud);
+
+        let mut old = mock_resource("my-pvc", Some(sample_cloud_resource()));
+        annotate_last_applied(&mut old, "2026-01-01T00:00:00Z".parse().unwrap());
+        let result = do_reconcile(&old, &ctx, "mockresource", "default", "my-pvc").await;
+        assert!(result.is_ok(), "reconcile should succeed");
+        assert_eq!(calls.load(Ordering::Relaxed), 1, "drift check is due");
+
+        let mut cr = sample_cloud_resource();
+        cr.labels.insert("team".into(), "platform".into());
+        let mut changed = mock_resource("my-pvc", Some(cr));
+        annotate_last_applied(&mut changed, Timestamp::now());
+        let result = do_reconcile(&changed, &ctx, "mockresource", "default", "my-pvc").await;
+        assert!(result.is_ok(), "reconcile should succeed");
+        assert_eq!(calls.load(Ordering::Relaxed), 2, "labels changed");
+
+        let mut cr = sample_cloud_resource();
+        cr.location.account = Some("111122223333".into());
+        let mut moved = mock_resource("my-pvc", Some(cr));
+        annotate_last_applied(&mut moved, Timestamp::now());
+        let result = do_reconcile(&moved, &ctx, "mockresource", "default", "my-pvc").await;
+        assert!(result.is_ok(), "reconcile should succeed");
+        assert_eq!(calls.load(Ordering::Relaxed), 3, "account changed");
+    }
+
+    #[tokio::test]
+    async fn records_sync_only_when_due() {
+        let cloud = MockCloud {
+            existing_tags: sample_cloud_resource().labels,
+            ..Default::default()
+        };
+        let (client, patches) = mock_client_counting_patches();
+        let ctx = Context {
+            client,
+            ..test_ctx(cloud)
+        };
+
+        let mut recent = mock_resource("my-pvc", Some(sample_cloud_resource()));
+        annotate_last_applied(&mut recent, Timestamp::now());
+        let result = do_reconcile(&recent, &ctx, "mockresource", "default", "my-pvc").await;
+        assert_eq!(result.unwrap(), Outcome::Unchanged);
+        assert_eq!(patches.load(Ordering::Relaxed), 0, "record is current");
+
+        let mut old = mock_resource("my-pvc", Some(sample_cloud_resource()));
+        annotate_last_applied(&mut old, "2026-01-01T00:00:00Z".parse().unwrap());
+        let result = do_reconcile(&old, &ctx, "mockresource", "default", "my-pvc").await;
+        assert_eq!(result.unwrap(), Outcome::Unchanged);
+        assert_eq!(
+            patches.load(Ordering::Relaxed),
+            1,
+            "drift check is recorded"
+        );
+    }
+
+    #[tokio::test]
+    async fn dry_run_counts_writes_it_would_make() {
+        let config = Config {
+            dry_run: true,
+            ..Config::default()
+        };
+        let ctx = test_ctx_with_config(MockCloud::default(), config);
+        let resource = mock_resource("my-pvc", Some(sample_cloud_resource()));
+
+        let result = do_reconcile(&resource, &ctx, "dryrunresource", "default", "my-pvc").await;
+
+        assert_eq!(result.unwrap(), Outcome::Tagged);
+        let writes = |outcome| {
+            TAG_WRITES
+                .with_label_values(&["dryrunresource", outcome])
+                .get()
+        };
+        assert_eq!(writes(labels::WOULD_WRITE), 1);
+        assert_eq!(writes(labels::WRITTEN), 0);
+    }
+
+    #[test]
+    fn changed_tags_lists_missing_and_different_values() {
+        let tags = |pairs: &[(&str, &str)]| -> BTreeMap<String, String> {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        };
+        let desired = tags(&[("env", "prod"), ("team", "platform"), ("tier", "db")]);
+        let existing = tags(&[("env", "prod"), ("team", "data"), ("owner", "jane")]);
+
+        assert_eq!(
+            changed_tags(&desired, &existing),
+            tags(&[("team", "platform"), ("tier", "db")])
+        );
+    }
+
+    #[tokio::test]
+    async fn only_permitted_labels_are_tagged() {
+        let cloud = MockCloud::default();
+        let last_labels = cloud.last_labels.clone();
+        let ctx = test_ctx_with_config(
+            cloud,
+            Config {
+                label_filter: crate::labels::LabelFilter {
+                    include: vec![],
+                    exclude: vec![
+                        crate::labels::KeyPattern::parse("helm.sh/*").unwrap(),
+                        crate::labels::KeyPattern::parse("app.kubernetes.io/managed-by").unwrap(),
+                    ],
+                },
+                ..Default::default()
+            },
+        );
+        let mut cr = sample_cloud_resource();
+        cr.labels.insert("helm.sh/chart".into(), "api-1.0.0".into());
+        cr.labels
+            .insert("app.kubernetes.io/managed-by".into(), "Helm".into());
+        let resource = mock_resource("my-pvc", Some(cr));
+
+        let result = do_reconcile(&resource, &ctx, "mockresource", "default", "my-pvc").await;
+
+        assert!(result.is_ok(), "reconcile should succeed");
+        assert_eq!(
+            *last_labels.lock().unwrap(),
+            BTreeMap::from([("upgrades.dev/app".into(), "k8s-cloud-tagger".into())])
+        );
+    }
+
+    #[tokio::test]
+    async fn mapped_labels_are_tagged() {
+        let cloud = MockCloud::default();
+        let last_labels = cloud.last_labels.clone();
+        let mut config = Config::default();
+        config.label_mapping.rules.insert(
+            "upgrades.dev/app".into(),
+            crate::labels::LabelMappingRule {
+                key: Some("App".into()),
+                values: BTreeMap::from([("k8s-cloud-tagger".into(), "Tagger".into())]),
+                ..Default::default()
+            },
+        );
+        let ctx = test_ctx_with_config(cloud, config);
+        let resource = mock_resource("my-pvc", Some(sample_cloud_resource()));
+
+        let result = do_reconcile(&resource, &ctx, "mockresource", "default", "my-pvc").await;
+
+        assert!(result.is_ok(), "reconcile should succeed");
+        assert_eq!(
+            *last_labels.lock().unwrap(),
+            BTreeMap::from([("App".into(), "Tagger".into())])
+        );
+    }
+
+    #[tokio::test]
+    async fn default_tags_are_merged_under_labels() {
+        let cloud = MockCloud::default();
+        let last_labels = cloud.last_labels.clone();
+        let ctx = test_ctx_with_config(
+            cloud,
+            Config {
+                default_tags: BTreeMap::from([
+                    ("environment".into(), "prod".into()),
+                    ("upgrades.dev/app".into(), "default".into()),
+                ]),
+                ..Default::default()
+            },
+        );
+        let resource = mock_resource("my-pvc", Some(sample_cloud_resource()));
+
+        let result = do_reconcile(&resource, &ctx, "mockresource", "default", "my-pvc").await;
+
+        assert!(result.is_ok(), "reconcile should succeed");
+        assert_eq!(
+            *last_labels.lock().unwrap(),
+            BTreeMap::from([
+                ("environment".into(), "prod".into()),
+                ("upgrades.dev/app".into(), "k8s-cloud-tagger".into()),
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn annotation_tags_are_tagged() {
+        let cloud = MockCloud::default();
+        let last_labels = cloud.last_labels.clone();
+        let mut config = Config::default();
+        config.label_filter.exclude = vec![crate::labels::KeyPattern::parse("owner").unwrap()];
+        let ctx = test_ctx_with_config(cloud, config);
+        let mut resource = mock_resource("my-pvc", Some(sample_cloud_resource()));
+        resource.meta.annotations = Some(BTreeMap::from([(
+            "cloud-tagger.upgrades.dev/tag.owner".into(),
+            "jane@example.com".into(),
+        )]));
+
+        let result = do_reconcile(&resource, &ctx, "mockresource", "default", "my-pvc").await;
+
+        assert!(result.is_ok(), "reconcile should succeed");
+        assert_eq!(
+            *last_labels.lock().unwrap(),
+            BTreeMap::from([
+                ("owner".into(), "jane@example.com".into()),
+                ("upgrades.dev/app".into(), "k8s-cloud-tagger".into()),
+            ])
+        );
+    }
+
+    fn annotate_enabled(resource: &mut MockResource, value: &str) {
+        resource
+            .meta
+            .annotations
+            .get_or_insert_default()
+            .insert(ENABLED_ANNOTATION.into(), value.into());
+    }
+
+    #[tokio::test]
+    async fn skips_opted_out_resource() {
+        let cloud = MockCloud::default();
+        let calls = cloud.tag_calls.clone();
+        let ctx = test_ctx(cloud);
+        let mut resource = mock_resource("shared-pvc", Some(sample_cloud_resource()));
+        annotate_enabled(&mut resource, "false");
+
+        let result = do_reconcile(&resource, &ctx, "mockresource", "default", "shared-pvc").await;
+
+        assert_eq!(result.unwrap(), Outcome::Skipped);
+        assert_eq!(calls.load(Ordering::Relaxed), 0);
+    }
+
+    #[tokio::test]
+    async fn skips_unannotated_resource_in_opt_in_mode() {
+        let cloud = MockCloud::default();
+        let calls = cloud.tag_calls.clone();
+        let ctx = test_ctx_with_config(
+            cloud,
+            Config {
+                tagging_mode: TaggingMode::OptIn,
+                ..Default::default()
+            },
+        );
+        let resource = mock_resource("my-pvc", Some(sample_cloud_resource()));
+
+        let result = do_reconcile(&resource, &ctx, "mockresource", "default", "my-pvc").await;
+
+        assert!(result.is_ok(), "reconcile should succeed");
+        assert_eq!(calls.load(Ordering::Relaxed), 0);
+    }
+
+    #[tokio::test]
+    async fn namespace_opts_in_its_resources() {
+        let cloud = MockCloud::default();
+        let calls = cloud.tag_calls.clone();
+        let mut ctx = test_ctx_with_config(
+            cloud,
+            Config {
+                tagging_mode: TaggingMode::OptIn,
+                ..Default::default()
+            },
+        );
+        ctx.client = mock_client_with_namespace("true");
+        let resource = mock_resource("my-pvc", Some(sample_cloud_resource()));
+
+        let result = do_reconcile(&resource, &ctx, "mockresource", "default", "my-pvc").await;
+
+        assert!(result.is_ok(), "reconcile should succeed");
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn resource_annotation_overrides_namespace() {
+        let cloud = MockCloud::default();
+        let calls = cloud.tag_calls.clone();
+        let mut ctx = test_ctx(cloud);
+        ctx.client = mock_client_with_namespace("false");
+
+        let opted_out = mock_resource("my-pvc", Some(sample_cloud_resource()));
+        let result = do_reconcile(&opted_out, &ctx, "mockresource", "default", "my-pvc").await;
+        assert!(result.is_ok(), "reconcile should succeed");
+        assert_eq!(calls.load(Ordering::Relaxed), 0, "namespace opted out");
+
+        let mut opted_in = mock_resource("my-pvc", Some(sample_cloud_resource()));
+        annotate_enabled(&mut opted_in, "true");
+        let result = do_reconcile(&opted_in, &ctx, "mockresource", "default", "my-pvc").await;
+        assert!(result.is_ok(), "reconcile should succeed");
+        assert_eq!(calls.load(Ordering::Relaxed), 1, "resource opted back in");
+    }
 }
@@ -0,0 +1,194 @@
+//! Reload the config file while the controller runs.
+//!
+//! Kubernetes updates a mounted ConfigMap by swapping a symlink to a new
+//! directory, which file watchers see inconsistently, so the file is polled and
+//! its contents compared instead.
+
+use crate::cloud::CloudClient;
+use crate::config::{Config, LiveConfig};
+use crate::metrics::{CONFIG_RELOADS, labels};
+use crate::reconciler::Context;
+use k8s_openapi::api::core::v1::ObjectReference;
+use kube::runtime::events::{Event, EventType, Recorder};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Kubernetes rejects Event notes longer than this many bytes.
+const MAX_NOTE_LEN: usize = 1024;
+
+/// What loading a changed config file did.
+#[derive(Debug, PartialEq)]
+enum Reload {
+    /// The new config is in use. `retag` is set if resources would now be
+    /// tagged differently.
+    Applied { retag: bool },
+    /// The new config is invalid, so the old one is still in use.
+    Rejected(String),
+}
+
+/// Poll the config file at `path`, swapping valid changes into `ctx.config`.
+/// Sends on `retag` when every resource needs reconciling again.
+pub async fn run<C: CloudClient>(ctx: Arc<Context<C>>, path: PathBuf, retag: watch::Sender<()>) {
+    let mut last = std::fs::read_to_string(&path).ok();
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    loop {
+        interval.tick().await;
+        let raw = match std::fs::read_to_string(&path) {
+            Ok(raw) => raw,
+            Err(e) => {
+                tracing::warn!(path = %path.display(), %e, "Failed to read config file");
+                continue;
+            }
+        };
+        if last.as_ref() == Some(&raw) {
+            continue;
+        }
+
+        match reload(&ctx.config, &raw) {
+            Reload::Applied { retag: needs_retag } => {
+                tracing::info!(path = %path.display(), retag = needs_retag, "Reloaded config");
+                CONFIG_RELOADS.with_label_values(&[labels::APPLIED]).inc();
+                if needs_retag {
+                    retag.send_replace(());
+                }
+            }
+            Reload::Rejected(errors) => {
+                tracing::error!(path = %path.display(), %errors, "Invalid config, keeping the current one");
+                CONFIG_RELOADS.with_label_values(&[labels::REJECTED]).inc();
+                publish_rejected(&ctx, &errors).await;
+            }
+        }
+        last = Some(raw);
+    }
+}
+
+/// Parse `raw` and, if valid, make it the live config.
+fn reload(live: &LiveConfig, raw: &str) -> Reload {
+    let mut config = match Config::parse(raw) {
+        Ok(config) => config,
+        Err(errors) => {
+            let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+            return Reload::Rejected(errors.join("; "));
+        }
+    };
+
+    let running = live.get();
+    let ignored = running.restart_required(&config);
+    if !ignored.is_empty() {
+        tracing::warn!(?ignored, "Config changes need a restart to take effect");
+    }
+    config.keep_startup_settings(&running);
+
+    let retag = running.tags_differ(&config);
+    live.set(config);
+    Reload::Applied { retag }
+}
+
+/// Publish a Warning Event on the controller's Pod. Best-effort, like the
+/// reconciler's Events.
+async fn publish_rejected<C: CloudClient>(ctx: &Context<C>, errors: &str) {
+    let Some(pod) = ctx.reporter.instance.clone() else {
+        return;
+    };
+    let reference = ObjectReference {
+        api_version: Some("v1".into()),
+        kind: Some("Pod".into()),
+        name: Some(pod),
+        namespace: Some(ctx.client.default_namespace().to_string()),
+        ..Default::default()
+    };
+
+    let mut note = format!("Keeping the current config: {errors}");
+    if note.len() > MAX_NOTE_LEN {
+        let mut end = MAX_NOTE_LEN;
+        while !note.is_char_boundary(end) {
+            end -= 1;
+        }
+        note.truncate(end);
+    }
+
+    let recorder = Recorder::new(ctx.client.clone(), ctx.reporter.clone());
+    let event = Event {
+        type_: EventType::Warning,
+        reason: "InvalidConfig".into(),
+        note: Some(note),
+        action: "ReloadConfig".into(),
+        secondary: None,
+    };
+    if let Err(e) = recorder.publish(&event, &reference).await {
+        tracing::warn!(%e, "Failed to publish event");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TaggingMode;
+    use crate::traits::CloudProvider;
+
+    const VALID: &str = "\
+cloudProvider: \"AWS\"
+requeue:
+  success: \"5m\"
+  notReady: \"30s\"
+  error: \"1m\"
+";
+
+    fn running() -> LiveConfig {
+        LiveConfig::new(Config::parse(VALID).unwrap_or_else(|_| panic!("valid config")))
+    }
+
+    #[test]
+    fn applies_requeue_changes_without_retagging() {
+        let live = running();
+
+        let result = reload(&live, &VALID.replace("success: \"5m\"", "success: \"10m\""));
+
+        assert_eq!(result, Reload::Applied { retag: false });
+        assert_eq!(live.get().requeue_success, Duration::from_secs(600));
+    }
+
+    #[test]
+    fn retags_when_tag_settings_change() {
+        let live = running();
+
+        let result = reload(&live, &format!("{VALID}taggingMode: optIn\n"));
+
+        assert_eq!(result, Reload::Applied { retag: true });
+        assert_eq!(live.get().tagging_mode, TaggingMode::OptIn);
+    }
+
+    #[test]
+    fn keeps_current_config_when_invalid() {
+        let live = running();
+
+        let result = reload(&live, &VALID.replace("\"5m\"", "\"5min\""));
+
+        assert!(matches!(result, Reload::Rejected(errors) if errors.contains("requeue.success")));
+        assert_eq!(live.get().requeue_success, Duration::from_secs(300));
+    }
+
+    #[test]
+    fn keeps_startup_settings() {
+        let live = running();
+
+        let result = reload(
+            &live,
+            &format!(
+                "{}dryRun: true\nwatch:\n  namespaces: [\"team-a\"]\n",
+                VALID.replace("AWS", "GCP")
+            ),
+        );
+
+        assert_eq!(result, Reload::Applied { retag: false });
+        let config = live.get();
+        assert_eq!(config.cloud_provider, CloudProvider::Aws);
+        assert!(!config.dry_run);
+        assert!(config.watch_namespaces.is_empty());
+    }
+}
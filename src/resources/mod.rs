@@ -1 +1,5 @@
+mod pv;
 mod pvc;
+mod service;
+pub mod snapshot;
+mod workload;
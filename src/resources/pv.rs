@@ -0,0 +1,488 @@
+use crate::config::Config;
+use crate::error::Error;
+use crate::stores::Stores;
+use crate::traits::{CloudProvider, CloudResource, CloudTaggable, Location, ResourceKind};
+use k8s_openapi::api::core::v1::PersistentVolume;
+use kube::{Client, ResourceExt};
+use std::collections::BTreeMap;
+
+/// Label carrying the PV's storage class name.
+pub const STORAGE_CLASS_LABEL: &str = "cloud-tagger.upgrades.dev/storage-class";
+/// Label carrying the PV's reclaim policy (`Delete` or `Retain`).
+pub const RECLAIM_POLICY_LABEL: &str = "cloud-tagger.upgrades.dev/reclaim-policy";
+
+/// Annotation on a PV or StorageClass naming the AWS account its EBS volumes
+/// are in, to pick a role from `aws.accountRoles`.
+pub const AWS_ACCOUNT_ANNOTATION: &str = "cloud-tagger.upgrades.dev/aws-account";
+
+/// Node affinity key naming the region a volume is in.
+const REGION_KEY: &str = "topology.kubernetes.io/region";
+/// Node affinity keys naming the availability zone an EBS volume is in.
+const AWS_ZONE_KEYS: [&str; 2] = [
+    "topology.ebs.csi.aws.com/zone",
+    "topology.kubernetes.io/zone",
+];
+
+pub(super) fn provider_from_csi_driver(driver: &str) -> CloudProvider {
+    match driver {
+        "ebs.csi.aws.com" => CloudProvider::Aws,
+        "disk.csi.azure.com" => CloudProvider::Azure,
+        "pd.csi.storage.gke.io" => CloudProvider::Gcp,
+        _ => CloudProvider::Other,
+    }
+}
+
+/// PersistentVolumes are tagged directly only while no claim is bound to them.
+///
+/// A bound PV is tagged through its PersistentVolumeClaim instead, with the
+/// claim's labels rather than [`pv_labels`]. This keeps a single
+/// writer per disk while still covering statically provisioned PVs that were
+/// never claimed and `Retain` PVs left behind after their claim was deleted.
+impl CloudTaggable for PersistentVolume {
+    fn is_taggable(&self) -> bool {
+        self.status.as_ref().and_then(|s| s.phase.as_deref()) != Some("Bound")
+    }
+
+    fn resolve_cloud_resource(
+        &self,
+        client: &Client,
+        stores: &Stores,
+        config: &Config,
+    ) -> impl Future<Output = Result<Option<CloudResource>, Error>> + Send {
+        let pv = self.clone();
+        let client = client.clone();
+        let stores = stores.clone();
+        let account_roles = !config.aws_account_roles.is_empty();
+
+        async move {
+            let account = if account_roles {
+                aws_account(&client, &stores, &pv).await?
+            } else {
+                None
+            };
+            let resolved = extract_resource_id(&pv).map(|(provider, resource_id)| CloudResource {
+                provider,
+                kind: ResourceKind::Disk,
+                resource_id,
+                location: volume_location(&pv, provider, account),
+                labels: pv_labels(&pv),
+            });
+            if let Some(cr) = &resolved {
+                tracing::debug!(resource_id = %cr.resource_id, "Found unclaimed volume");
+            }
+            Ok(resolved)
+        }
+    }
+}
+
+/// Labels to propagate from a PV: its own labels plus its storage class and
+/// reclaim policy.
+pub(super) fn pv_labels(pv: &PersistentVolume) -> BTreeMap<String, String> {
+    let mut labels = pv.metadata.labels.clone().unwrap_or_default();
+    let Some(spec) = pv.spec.as_ref() else {
+        return labels;
+    };
+    if let Some(class) = spec.storage_class_name.as_ref().filter(|c| !c.is_empty()) {
+        labels.insert(STORAGE_CLASS_LABEL.to_string(), class.clone());
+    }
+    if let Some(policy) = &spec.persistent_volume_reclaim_policy {
+        labels.insert(RECLAIM_POLICY_LABEL.to_string(), policy.clone());
+    }
+    labels
+}
+
+/// The AWS account of an EBS volume, from the [`AWS_ACCOUNT_ANNOTATION`] on the
+/// PV or else on its StorageClass. Only worth a StorageClass lookup when
+/// `aws.accountRoles` is configured.
+///
+/// A malformed annotation is an error rather than ignored, so the volume isn't
+/// tagged with the wrong account's role.
+pub(super) async fn aws_account(
+    client: &Client,
+    stores: &Stores,
+    pv: &PersistentVolume,
+) -> Result<Option<String>, Error> {
+    let Some(spec) = pv.spec.as_ref() else {
+        return Ok(None);
+    };
+    if spec
+        .csi
+        .as_ref()
+        .map(|csi| provider_from_csi_driver(&csi.driver))
+        != Some(CloudProvider::Aws)
+    {
+        return Ok(None);
+    }
+    if let Some(account) = pv.annotations().get(AWS_ACCOUNT_ANNOTATION) {
+        return check_aws_account(account, "PersistentVolume", &pv.name_any()).map(Some);
+    }
+    let Some(class) = spec.storage_class_name.as_ref().filter(|c| !c.is_empty()) else {
+        return Ok(None);
+    };
+    let Some(meta) = stores.storage_class(client, class).await? else {
+        return Ok(None);
+    };
+    meta.annotations
+        .as_ref()
+        .and_then(|annotations| annotations.get(AWS_ACCOUNT_ANNOTATION))
+        .map(|account| check_aws_account(account, "StorageClass", class))
+        .transpose()
+}
+
+/// Check an [`AWS_ACCOUNT_ANNOTATION`] value is an AWS account ID.
+fn check_aws_account(account: &str, kind: &str, name: &str) -> Result<String, Error> {
+    if account.len() == 12 && account.bytes().all(|b| b.is_ascii_digit()) {
+        return Ok(account.to_string());
+    }
+    Err(Error::Config(format!(
+        "{AWS_ACCOUNT_ANNOTATION} annotation on {kind} {name} is '{account}', not a 12-digit AWS account ID"
+    )))
+}
+
+/// The PV's provider and resource ID.
+pub(super) fn extract_resource_id(pv: &PersistentVolume) -> Option<(CloudProvider, String)> {
+    let spec = pv.spec.as_ref()?;
+
+    // CSI is the most common and modern.
+    if let Some(csi) = &spec.csi {
+        let provider = provider_from_csi_driver(&csi.driver);
+        return Some((provider, csi.volume_handle.clone()));
+    }
+
+    // Google Compute Engine Persistent Disk (found on older GKE clusters)
+    if let Some(pd_name) = &spec.gce_persistent_disk {
+        return Some((CloudProvider::Gcp, pd_name.pd_name.clone()));
+    }
+
+    // hostPath - used by Kind/local-path-provisioner for test environments
+    if let Some(host_path) = &spec.host_path {
+        return Some((CloudProvider::Other, host_path.path.clone()));
+    }
+
+    let pv_name = pv.metadata.name.as_deref().unwrap_or("<unknown>");
+    tracing::warn!(pv = %pv_name, "No supported volume source found (expected CSI, GCE PD, or hostPath)");
+
+    None
+}
+
+/// Where the PV's disk lives, for providers whose volume handles don't say.
+///
+/// The EBS CSI driver's handles are bare volume IDs, so an EBS volume's region
+/// comes from the PV's node affinity and its account from [`aws_account`].
+/// Whatever isn't known is left to the cloud client's own region and account.
+pub(super) fn volume_location(
+    pv: &PersistentVolume,
+    provider: CloudProvider,
+    account: Option<String>,
+) -> Location {
+    if provider != CloudProvider::Aws {
+        return Location::default();
+    }
+    let region = node_affinity_value(pv, REGION_KEY)
+        .map(str::to_string)
+        .or_else(|| {
+            AWS_ZONE_KEYS
+                .iter()
+                .find_map(|key| node_affinity_value(pv, key))
+                .and_then(zone_region)
+        });
+    Location { region, account }
+}
+
+/// The first value the PV's required node affinity allows for `key`.
+fn node_affinity_value<'a>(pv: &'a PersistentVolume, key: &str) -> Option<&'a str> {
+    let required = pv
+        .spec
+        .as_ref()?
+        .node_affinity
+        .as_ref()?
+        .required
+        .as_ref()?;
+    required
+        .node_selector_terms
+        .iter()
+        .flat_map(|term| term.match_expressions.iter().flatten())
+        .filter(|expr| expr.key == key && expr.operator == "In")
+        .find_map(|expr| expr.values.as_ref()?.first())
+        .map(String::as_str)
+}
+
+/// The region of an availability zone, e.g. `us-east-1` for `us-east-1a`, or
+/// `us-west-2` for the Local Zone `us-west-2-lax-1a`.
+fn zone_region(zone: &str) -> Option<String> {
+    let mut parts = Vec::new();
+    for part in zone.split('-') {
+        let number: String = part.chars().take_while(char::is_ascii_digit).collect();
+        if !number.is_empty() {
+            parts.push(number);
+            return Some(parts.join("-"));
+        }
+        parts.push(part.to_string());
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use k8s_openapi::api::core::v1::{
+        CSIPersistentVolumeSource, NodeSelector, NodeSelectorRequirement, NodeSelectorTerm,
+        PersistentVolumeSpec, PersistentVolumeStatus, VolumeNodeAffinity,
+    };
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
+    use tower_test::mock;
+
+    fn mock_client() -> Client {
+        let (mock_service, _handle) =
+            mock::pair::<http::Request<kube::client::Body>, http::Response<kube::client::Body>>();
+        Client::new(mock_service, "default")
+    }
+
+    fn mock_pv(phase: &str) -> PersistentVolume {
+        PersistentVolume {
+            metadata: ObjectMeta {
+                name: Some("static-pv".into()),
+                labels: Some(BTreeMap::from([("team".into(), "storage".into())])),
+                ..Default::default()
+            },
+            spec: Some(PersistentVolumeSpec {
+                csi: Some(CSIPersistentVolumeSource {
+                    driver: "ebs.csi.aws.com".into(),
+                    volume_handle: "vol-0123456789cafe0".into(),
+                    ..Default::default()
+                }),
+                storage_class_name: Some("gp3".into()),
+                persistent_volume_reclaim_policy: Some("Retain".into()),
+                ..Default::default()
+            }),
+            status: Some(PersistentVolumeStatus {
+                phase: Some(phase.into()),
+                ..Default::default()
+            }),
+        }
+    }
+
+    #[tokio::test]
+    async fn available_pv_returns_resource_with_metadata_labels() {
+        let pv = mock_pv("Available");
+
+        let cr = pv
+            .resolve_cloud_resource(&mock_client(), &Stores::default(), &Config::default())
+            .await
+            .unwrap()
+            .expect("expected CloudResource");
+
+        assert_eq!(cr.provider, CloudProvider::Aws);
+        assert_eq!(cr.resource_id, "vol-0123456789cafe0");
+        assert_eq!(
+            cr.labels,
+            BTreeMap::from([
+                ("team".into(), "storage".into()),
+                (STORAGE_CLASS_LABEL.into(), "gp3".into()),
+                (RECLAIM_POLICY_LABEL.into(), "Retain".into()),
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn released_pv_returns_resource() {
+        let pv = mock_pv("Released");
+
+        let result = pv
+            .resolve_cloud_resource(&mock_client(), &Stores::default(), &Config::default())
+            .await
+            .unwrap();
+
+        assert!(result.is_some());
+    }
+
+    #[test]
+    fn bound_pv_is_left_to_its_claim() {
+        assert!(!mock_pv("Bound").is_taggable());
+        assert!(mock_pv("Available").is_taggable());
+        assert!(mock_pv("Released").is_taggable());
+    }
+
+    #[test]
+    fn pv_labels_skips_empty_storage_class() {
+        let mut pv = mock_pv("Available");
+        pv.spec.as_mut().unwrap().storage_class_name = Some(String::new());
+
+        let labels = pv_labels(&pv);
+
+        assert!(!labels.contains_key(STORAGE_CLASS_LABEL));
+        assert_eq!(labels[RECLAIM_POLICY_LABEL], "Retain");
+    }
+
+    fn with_node_affinity(mut pv: PersistentVolume, key: &str, value: &str) -> PersistentVolume {
+        pv.spec.as_mut().unwrap().node_affinity = Some(VolumeNodeAffinity {
+            required: Some(NodeSelector {
+                node_selector_terms: vec![NodeSelectorTerm {
+                    match_expressions: Some(vec![NodeSelectorRequirement {
+                        key: key.into(),
+                        operator: "In".into(),
+                        values: Some(vec![value.into()]),
+                    }]),
+                    ..Default::default()
+                }],
+            }),
+        });
+        pv
+    }
+
+    #[test]
+    fn aws_location_takes_region_from_node_affinity() {
+        let region = |pv: &PersistentVolume| volume_location(pv, CloudProvider::Aws, None).region;
+
+        let pv = with_node_affinity(
+            mock_pv("Available"),
+            "topology.ebs.csi.aws.com/zone",
+            "eu-west-2b",
+        );
+        assert_eq!(region(&pv).as_deref(), Some("eu-west-2"));
+
+        let pv = with_node_affinity(mock_pv("Available"), REGION_KEY, "cn-north-1");
+        assert_eq!(region(&pv).as_deref(), Some("cn-north-1"));
+
+        let pv = with_node_affinity(mock_pv("Available"), "kubernetes.io/hostname", "node-1");
+        assert_eq!(region(&pv), None);
+    }
+
+    #[test]
+    fn aws_volume_handle_is_kept() {
+        let pv = with_node_affinity(mock_pv("Available"), REGION_KEY, "eu-west-2");
+        assert_eq!(extract_resource_id(&pv).unwrap().1, "vol-0123456789cafe0");
+
+        let mut pv = mock_pv("Available");
+        pv.spec
+            .as_mut()
+            .unwrap()
+            .csi
+            .as_mut()
+            .unwrap()
+            .volume_handle = "arn:aws:ebs:eu-west-2:123456789012:volume/vol-0123456789cafe0".into();
+        assert_eq!(
+            extract_resource_id(&pv).unwrap().1,
+            "arn:aws:ebs:eu-west-2:123456789012:volume/vol-0123456789cafe0"
+        );
+    }
+
+    #[test]
+    fn zone_region_strips_the_zone() {
+        assert_eq!(zone_region("us-east-1a").as_deref(), Some("us-east-1"));
+        assert_eq!(
+            zone_region("us-gov-west-1b").as_deref(),
+            Some("us-gov-west-1")
+        );
+        assert_eq!(
+            zone_region("us-west-2-lax-1a").as_deref(),
+            Some("us-west-2")
+        );
+        assert_eq!(zone_region("zone"), None);
+    }
+
+    #[test]
+    fn aws_location_carries_the_account() {
+        let pv = with_node_affinity(mock_pv("Available"), REGION_KEY, "eu-west-2");
+        assert_eq!(
+            volume_location(&pv, CloudProvider::Aws, Some("111122223333".into())),
+            Location {
+                region: Some("eu-west-2".into()),
+                account: Some("111122223333".into()),
+            }
+        );
+
+        assert_eq!(
+            volume_location(&pv, CloudProvider::Gcp, Some("111122223333".into())),
+            Location::default()
+        );
+    }
+
+    #[tokio::test]
+    async fn aws_account_from_pv_or_storage_class() {
+        let mut pv = mock_pv("Available");
+        pv.metadata.annotations = Some(BTreeMap::from([(
+            AWS_ACCOUNT_ANNOTATION.into(),
+            "111122223333".into(),
+        )]));
+        assert_eq!(
+            aws_account(&mock_client(), &Stores::default(), &pv)
+                .await
+                .unwrap()
+                .as_deref(),
+            Some("111122223333")
+        );
+
+        let (mock_service, mut handle) =
+            mock::pair::<http::Request<kube::client::Body>, http::Response<kube::client::Body>>();
+        tokio::spawn(async move {
+            let (request, send) = handle.next_request().await.expect("expected a request");
+            assert_eq!(
+                request.uri().path(),
+                "/apis/storage.k8s.io/v1/storageclasses/gp3"
+            );
+            let class = serde_json::json!({
+                "apiVersion": "meta.k8s.io/v1",
+                "kind": "PartialObjectMetadata",
+                "metadata": {
+                    "name": "gp3",
+                    "annotations": { AWS_ACCOUNT_ANNOTATION: "444455556666" },
+                },
+            });
+            send.send_response(
+                http::Response::builder()
+                    .body(kube::client::Body::from(
+                        serde_json::to_vec(&class).unwrap(),
+                    ))
+                    .unwrap(),
+            );
+        });
+        let client = Client::new(mock_service, "default");
+        assert_eq!(
+            aws_account(&client, &Stores::default(), &mock_pv("Available"))
+                .await
+                .unwrap()
+                .as_deref(),
+            Some("444455556666")
+        );
+    }
+
+    #[tokio::test]
+    async fn malformed_aws_account_is_an_error() {
+        let mut pv = mock_pv("Available");
+        pv.metadata.annotations = Some(BTreeMap::from([(
+            AWS_ACCOUNT_ANNOTATION.into(),
+            "1111-2222-3333".into(),
+        )]));
+        let err = aws_account(&mock_client(), &Stores::default(), &pv)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::Config(_)), "{err}");
+
+        let (mock_service, mut handle) =
+            mock::pair::<http::Request<kube::client::Body>, http::Response<kube::client::Body>>();
+        tokio::spawn(async move {
+            let (_, send) = handle.next_request().await.expect("expected a request");
+            let class = serde_json::json!({
+                "apiVersion": "meta.k8s.io/v1",
+                "kind": "PartialObjectMetadata",
+                "metadata": {
+                    "name": "gp3",
+                    "annotations": { AWS_ACCOUNT_ANNOTATION: "44445555666" },
+                },
+            });
+            send.send_response(
+                http::Response::builder()
+                    .body(kube::client::Body::from(
+                        serde_json::to_vec(&class).unwrap(),
+                    ))
+                    .unwrap(),
+            );
+        });
+        let client = Client::new(mock_service, "default");
+        let err = aws_account(&client, &Stores::default(), &mock_pv("Available"))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("StorageClass gp3"), "{err}");
+    }
+}
@@ -1,25 +1,53 @@
+use super::pv::{aws_account, extract_resource_id, volume_location};
+use super::workload::workload_labels;
+use crate::config::Config;
 use crate::error::Error;
-use crate::traits::{CloudProvider, CloudResource, CloudTaggable};
+use crate::labels::KeyPattern;
+use crate::stores::Stores;
+use crate::traits::{CloudResource, CloudTaggable, ResourceKind};
 use k8s_openapi::api::core::v1::{PersistentVolume, PersistentVolumeClaim};
 use kube::{Api, Client};
-
-fn provider_from_csi_driver(driver: &str) -> CloudProvider {
-    match driver {
-        "ebs.csi.aws.com" => CloudProvider::Aws,
-        "disk.csi.azure.com" => CloudProvider::Azure,
-        "pd.csi.storage.gke.io" => CloudProvider::Gcp,
-        _ => CloudProvider::Other,
-    }
+use std::collections::BTreeMap;
+
+/// Labels of the claim's namespace that match any of `patterns`.
+async fn namespace_labels(
+    client: &Client,
+    stores: &Stores,
+    namespace: &str,
+    patterns: &[KeyPattern],
+) -> Result<BTreeMap<String, String>, Error> {
+    let ns = stores.namespace(client, namespace).await?;
+    Ok(ns
+        .and_then(|ns| ns.labels)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|(k, _)| patterns.iter().any(|p| p.matches(k)))
+        .collect())
 }
 
+/// PVCs are tagged through their bound PV's disk.
+///
+/// Labels are merged from, lowest precedence first: selected labels of the
+/// claim's namespace (`namespaceLabels` in the config), selected labels of the
+/// workload using the claim (`workloadLabels`), then the claim's own labels.
+/// The bound PV's own labels are not propagated; they apply only while the PV
+/// is unclaimed and tagged directly.
 impl CloudTaggable for PersistentVolumeClaim {
     fn resolve_cloud_resource(
         &self,
         client: &Client,
+        stores: &Stores,
+        config: &Config,
     ) -> impl Future<Output = Result<Option<CloudResource>, Error>> + Send {
         let pv_name = self.spec.as_ref().and_then(|s| s.volume_name.clone());
         let labels = self.metadata.labels.clone().unwrap_or_default();
+        let namespace = self.metadata.namespace.clone().unwrap_or_default();
+        let namespace_patterns = config.namespace_labels.clone();
+        let workload_patterns = config.workload_labels.clone();
+        let account_roles = !config.aws_account_roles.is_empty();
+        let pvc = self.clone();
         let client = client.clone();
+        let stores = stores.clone();
 
         async move {
             let Some(pv_name) = pv_name else {
@@ -27,9 +55,14 @@ impl CloudTaggable for PersistentVolumeClaim {
                 return Ok(None);
             };
 
-            let pvs: Api<PersistentVolume> = Api::all(client);
+            let pvs: Api<PersistentVolume> = Api::all(client.clone());
             let pv = pvs.get(&pv_name).await?;
 
+            let account = if account_roles {
+                aws_account(&client, &stores, &pv).await?
+            } else {
+                None
+            };
             let Some((provider, resource_id)) = extract_resource_id(&pv) else {
                 tracing::debug!(pv = %pv_name, "No supported volume source found");
                 return Ok(None);
@@ -37,51 +70,42 @@ impl CloudTaggable for PersistentVolumeClaim {
 
             tracing::debug!(%resource_id, "Found volume");
 
+            let mut merged = if namespace_patterns.is_empty() {
+                BTreeMap::new()
+            } else {
+                namespace_labels(&client, &stores, &namespace, &namespace_patterns).await?
+            };
+            if !workload_patterns.is_empty() {
+                merged.extend(workload_labels(client, &stores, &pvc, &workload_patterns).await?);
+            }
+            merged.extend(labels);
+
             Ok(Some(CloudResource {
                 provider,
+                kind: ResourceKind::Disk,
                 resource_id,
-                labels,
+                location: volume_location(&pv, provider, account),
+                labels: merged,
             }))
         }
     }
 }
 
-fn extract_resource_id(pv: &PersistentVolume) -> Option<(CloudProvider, String)> {
-    let spec = pv.spec.as_ref()?;
-
-    // CSI is the most common and modern.
-    if let Some(csi) = &spec.csi {
-        let provider = provider_from_csi_driver(&csi.driver);
-        return Some((provider, csi.volume_handle.clone()));
-    }
-
-    // Google Compute Engine Persistent Disk (found on older GKE clusters)
-    if let Some(pd_name) = &spec.gce_persistent_disk {
-        return Some((CloudProvider::Gcp, pd_name.pd_name.clone()));
-    }
-
-    // hostPath - used by Kind/local-path-provisioner for test environments
-    if let Some(host_path) = &spec.host_path {
-        return Some((CloudProvider::Other, host_path.path.clone()));
-    }
-
-    let pv_name = pv.metadata.name.as_deref().unwrap_or("<unknown>");
-    tracing::warn!(pv = %pv_name, "No supported volume source found (expected CSI, GCE PD, or hostPath)");
-
-    None
-}
-
 #[cfg(test)]
 mod tests {
+    use crate::config::Config;
+    use crate::labels::KeyPattern;
+    use crate::stores::Stores;
     use crate::traits::{CloudProvider, CloudTaggable};
     use http::{Request, Response, StatusCode};
     use k8s_openapi::api::core::v1::{
-        CSIPersistentVolumeSource, HostPathVolumeSource, PersistentVolume, PersistentVolumeClaim,
-        PersistentVolumeClaimSpec, PersistentVolumeSpec,
+        CSIPersistentVolumeSource, HostPathVolumeSource, Namespace, PersistentVolume,
+        PersistentVolumeClaim, PersistentVolumeClaimSpec, PersistentVolumeSpec,
     };
     use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
     use kube::Client;
     use kube::client::Body;
+    use std::collections::BTreeMap;
     use tower_test::mock;
 
     fn mock_client() -> (Client, mock::Handle<Request<Body>, Response<Body>>) {
@@ -211,7 +235,10 @@ mod tests {
         let (client, _handle) = mock_client();
         let pvc = mock_pvc(None);
 
-        let result = pvc.resolve_cloud_resource(&client).await.unwrap();
+        let result = pvc
+            .resolve_cloud_resource(&client, &Stores::default(), &Config::default())
+            .await
+            .unwrap();
 
         assert!(result.is_none());
     }
@@ -224,7 +251,10 @@ mod tests {
 
         respond_with_pv(handle, pv);
 
-        let result = pvc.resolve_cloud_resource(&client).await.unwrap();
+        let result = pvc
+            .resolve_cloud_resource(&client, &Stores::default(), &Config::default())
+            .await
+            .unwrap();
 
         assert!(result.is_none());
     }
@@ -240,7 +270,10 @@ mod tests {
 
         respond_with_pv(handle, pv);
 
-        let result = pvc.resolve_cloud_resource(&client).await.unwrap();
+        let result = pvc
+            .resolve_cloud_resource(&client, &Stores::default(), &Config::default())
+            .await
+            .unwrap();
 
         let cr = result.expect("expected CloudResource");
         assert_eq!(
@@ -261,7 +294,10 @@ mod tests {
 
         respond_with_pv(handle, pv);
 
-        let result = pvc.resolve_cloud_resource(&client).await.unwrap();
+        let result = pvc
+            .resolve_cloud_resource(&client, &Stores::default(), &Config::default())
+            .await
+            .unwrap();
 
         let cr = result.expect("expected CloudResource");
         assert_eq!(
@@ -282,7 +318,10 @@ mod tests {
 
         respond_with_pv(handle, pv);
 
-        let result = pvc.resolve_cloud_resource(&client).await.unwrap();
+        let result = pvc
+            .resolve_cloud_resource(&client, &Stores::default(), &Config::default())
+            .await
+            .unwrap();
 
         let cr = result.expect("expected CloudResource");
         assert_eq!(
@@ -300,13 +339,42 @@ mod tests {
 
         respond_with_pv(handle, pv);
 
-        let result = pvc.resolve_cloud_resource(&client).await.unwrap();
+        let result = pvc
+            .resolve_cloud_resource(&client, &Stores::default(), &Config::default())
+            .await
+            .unwrap();
 
         let cr = result.expect("expected CloudResource");
         assert_eq!(cr.resource_id, "/var/local-path-provisioner/pvc-abc123");
         assert_eq!(cr.provider, CloudProvider::Other);
     }
 
+    #[tokio::test]
+    async fn volume_labels_are_not_propagated() {
+        let (client, handle) = mock_client();
+        let mut pvc = mock_pvc(Some("test-pv"));
+        pvc.metadata.labels = Some(BTreeMap::from([("team".into(), "payments".into())]));
+        let mut pv = mock_pv_gcp_csi("test-pv", "projects/p/zones/z/disks/d");
+        pv.metadata.labels = Some(BTreeMap::from([
+            ("team".into(), "storage".into()),
+            ("tier".into(), "ssd".into()),
+        ]));
+        pv.spec.as_mut().unwrap().storage_class_name = Some("standard-rwo".into());
+
+        respond_with_pv(handle, pv);
+
+        let cr = pvc
+            .resolve_cloud_resource(&client, &Stores::default(), &Config::default())
+            .await
+            .unwrap()
+            .expect("expected CloudResource");
+
+        assert_eq!(
+            cr.labels,
+            BTreeMap::from([("team".into(), "payments".into())])
+        );
+    }
+
     #[tokio::test]
     async fn pv_not_found_returns_error() {
         let (client, mut handle) = mock_client();
@@ -325,8 +393,70 @@ mod tests {
             send.send_response(response);
         });
 
-        let result = pvc.resolve_cloud_resource(&client).await;
+        let result = pvc
+            .resolve_cloud_resource(&client, &Stores::default(), &Config::default())
+            .await;
 
         assert!(result.is_err());
     }
+
+    #[tokio::test]
+    async fn inherits_selected_namespace_labels() {
+        let (client, mut handle) = mock_client();
+        let mut pvc = mock_pvc(Some("test-pv"));
+        pvc.metadata.labels = Some(BTreeMap::from([("team".into(), "payments".into())]));
+        let pv = mock_pv_gcp_csi("test-pv", "projects/p/zones/z/disks/d");
+        let ns = Namespace {
+            metadata: ObjectMeta {
+                name: Some("default".into()),
+                labels: Some(BTreeMap::from([
+                    ("team".into(), "platform".into()),
+                    ("cost-center".into(), "cc-42".into()),
+                    ("kubernetes.io/metadata.name".into(), "default".into()),
+                ])),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        tokio::spawn(async move {
+            for (path, body) in [
+                (
+                    "persistentvolumes/test-pv",
+                    serde_json::to_vec(&pv).unwrap(),
+                ),
+                ("namespaces/default", serde_json::to_vec(&ns).unwrap()),
+            ] {
+                let (request, send) = handle.next_request().await.expect("expected a request");
+                assert!(request.uri().path().ends_with(path), "{}", request.uri());
+                send.send_response(
+                    Response::builder()
+                        .status(StatusCode::OK)
+                        .body(Body::from(body))
+                        .unwrap(),
+                );
+            }
+        });
+
+        let config = Config {
+            namespace_labels: vec![
+                KeyPattern::parse("team").unwrap(),
+                KeyPattern::parse("cost-center").unwrap(),
+            ],
+            ..Default::default()
+        };
+        let cr = pvc
+            .resolve_cloud_resource(&client, &Stores::default(), &config)
+            .await
+            .unwrap()
+            .expect("expected CloudResource");
+
+        assert_eq!(
+            cr.labels,
+            BTreeMap::from([
+                ("team".into(), "payments".into()),
+                ("cost-center".into(), "cc-42".into()),
+            ])
+        );
+    }
 }
@@ -0,0 +1,176 @@
+use crate::config::Config;
+use crate::error::Error;
+use crate::stores::Stores;
+use crate::traits::{CloudProvider, CloudResource, CloudTaggable, Location, ResourceKind};
+use k8s_openapi::api::core::v1::Service;
+use kube::Client;
+
+/// Guess the provider from a load balancer hostname.
+///
+/// Only AWS publishes hostnames; GCP and Azure publish a bare IP address, which
+/// doesn't identify the provider. Those are left to the configured cloud client.
+fn provider_from_ingress_hostname(hostname: &str) -> CloudProvider {
+    if hostname.ends_with(".amazonaws.com") {
+        CloudProvider::Aws
+    } else {
+        CloudProvider::Other
+    }
+}
+
+/// Services of `type: LoadBalancer` are tagged through their cloud load balancer.
+///
+/// The resource ID is the first `status.loadBalancer.ingress` entry (hostname
+/// if set, otherwise IP). The cloud client resolves it to the underlying
+/// resource, since the Service doesn't record the provider's resource ID.
+/// A Service changed to another type is ignored, and the tags already on its
+/// load balancer stay there.
+impl CloudTaggable for Service {
+    fn is_taggable(&self) -> bool {
+        self.spec.as_ref().and_then(|s| s.type_.as_deref()) == Some("LoadBalancer")
+    }
+
+    fn resolve_cloud_resource(
+        &self,
+        _client: &Client,
+        _stores: &Stores,
+        _config: &Config,
+    ) -> impl Future<Output = Result<Option<CloudResource>, Error>> + Send {
+        let ingress = self
+            .status
+            .as_ref()
+            .and_then(|s| s.load_balancer.as_ref())
+            .and_then(|lb| lb.ingress.as_ref())
+            .and_then(|ingress| ingress.first());
+
+        let resolved = ingress.and_then(|ingress| {
+            if let Some(hostname) = ingress.hostname.as_ref().filter(|h| !h.is_empty()) {
+                return Some((provider_from_ingress_hostname(hostname), hostname.clone()));
+            }
+            let ip = ingress.ip.as_ref().filter(|ip| !ip.is_empty())?;
+            Some((CloudProvider::Other, ip.clone()))
+        });
+        let labels = self.metadata.labels.clone().unwrap_or_default();
+
+        async move {
+            let Some((provider, resource_id)) = resolved else {
+                // The cloud provider hasn't provisioned the load balancer yet.
+                return Ok(None);
+            };
+
+            tracing::debug!(%resource_id, "Found load balancer");
+
+            Ok(Some(CloudResource {
+                provider,
+                kind: ResourceKind::LoadBalancer,
+                resource_id,
+                location: Location::default(),
+                labels,
+            }))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use k8s_openapi::api::core::v1::{
+        LoadBalancerIngress, LoadBalancerStatus, ServiceSpec, ServiceStatus,
+    };
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
+    use std::collections::BTreeMap;
+    use tower_test::mock;
+
+    fn mock_client() -> Client {
+        let (mock_service, _handle) =
+            mock::pair::<http::Request<kube::client::Body>, http::Response<kube::client::Body>>();
+        Client::new(mock_service, "default")
+    }
+
+    fn mock_service(type_: &str, ingress: Option<LoadBalancerIngress>) -> Service {
+        Service {
+            metadata: ObjectMeta {
+                name: Some("web".into()),
+                namespace: Some("default".into()),
+                labels: Some(BTreeMap::from([("team".into(), "web".into())])),
+                ..Default::default()
+            },
+            spec: Some(ServiceSpec {
+                type_: Some(type_.into()),
+                ..Default::default()
+            }),
+            status: Some(ServiceStatus {
+                load_balancer: Some(LoadBalancerStatus {
+                    ingress: ingress.map(|i| vec![i]),
+                }),
+                ..Default::default()
+            }),
+        }
+    }
+
+    #[test]
+    fn only_load_balancers_are_taggable() {
+        assert!(mock_service("LoadBalancer", None).is_taggable());
+        assert!(!mock_service("ClusterIP", None).is_taggable());
+        assert!(!mock_service("NodePort", None).is_taggable());
+    }
+
+    #[tokio::test]
+    async fn pending_load_balancer_returns_none() {
+        let svc = mock_service("LoadBalancer", None);
+
+        let result = svc
+            .resolve_cloud_resource(&mock_client(), &Stores::default(), &Config::default())
+            .await
+            .unwrap();
+
+        assert!(result.is_none());
+    }
+
+    #[tokio::test]
+    async fn aws_hostname_returns_aws_resource() {
+        let svc = mock_service(
+            "LoadBalancer",
+            Some(LoadBalancerIngress {
+                hostname: Some(
+                    "k8s-default-web-0123456789-abcdef0123456789.elb.us-east-1.amazonaws.com"
+                        .into(),
+                ),
+                ..Default::default()
+            }),
+        );
+
+        let cr = svc
+            .resolve_cloud_resource(&mock_client(), &Stores::default(), &Config::default())
+            .await
+            .unwrap()
+            .expect("expected CloudResource");
+
+        assert_eq!(cr.provider, CloudProvider::Aws);
+        assert_eq!(cr.kind, ResourceKind::LoadBalancer);
+        assert_eq!(
+            cr.resource_id,
+            "k8s-default-web-0123456789-abcdef0123456789.elb.us-east-1.amazonaws.com"
+        );
+        assert_eq!(cr.labels["team"], "web");
+    }
+
+    #[tokio::test]
+    async fn ip_returns_load_balancer_resource() {
+        let svc = mock_service(
+            "LoadBalancer",
+            Some(LoadBalancerIngress {
+                ip: Some("203.0.113.10".into()),
+                ..Default::default()
+            }),
+        );
+
+        let cr = svc
+            .resolve_cloud_resource(&mock_client(), &Stores::default(), &Config::default())
+            .await
+            .unwrap()
+            .expect("expected CloudResource");
+
+        assert_eq!(cr.kind, ResourceKind::LoadBalancer);
+        assert_eq!(cr.resource_id, "203.0.113.10");
+    }
+}
@@ -0,0 +1,413 @@
+//! CSI `VolumeSnapshot` support.
+//!
+//! `VolumeSnapshot` and `VolumeSnapshotContent` are CRDs installed by the CSI
+//! external-snapshotter, so they aren't part of `k8s-openapi`. Only the fields
+//! we read are modelled here.
+
+use super::pv::{aws_account, provider_from_csi_driver, volume_location};
+use crate::config::Config;
+use crate::error::Error;
+use crate::stores::Stores;
+use crate::traits::{CloudProvider, CloudResource, CloudTaggable, Location, ResourceKind};
+use k8s_openapi::api::core::v1::{PersistentVolume, PersistentVolumeClaim};
+use kube::{Api, Client, CustomResource};
+use serde::{Deserialize, Serialize};
+
+#[derive(CustomResource, Deserialize, Serialize, Clone, Debug, Default)]
+#[kube(
+    group = "snapshot.storage.k8s.io",
+    version = "v1",
+    kind = "VolumeSnapshot",
+    namespaced,
+    status = "VolumeSnapshotStatus",
+    schema = "disabled"
+)]
+#[serde(rename_all = "camelCase")]
+pub struct VolumeSnapshotSpec {
+    pub source: VolumeSnapshotSource,
+    pub volume_snapshot_class_name: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct VolumeSnapshotSource {
+    /// Set for dynamically provisioned snapshots of a PVC.
+    pub persistent_volume_claim_name: Option<String>,
+    /// Set for pre-provisioned snapshots.
+    pub volume_snapshot_content_name: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct VolumeSnapshotStatus {
+    pub bound_volume_snapshot_content_name: Option<String>,
+    pub ready_to_use: Option<bool>,
+}
+
+#[derive(CustomResource, Deserialize, Serialize, Clone, Debug, Default)]
+#[kube(
+    group = "snapshot.storage.k8s.io",
+    version = "v1",
+    kind = "VolumeSnapshotContent",
+    status = "VolumeSnapshotContentStatus",
+    schema = "disabled"
+)]
+#[serde(rename_all = "camelCase")]
+pub struct VolumeSnapshotContentSpec {
+    /// Name of the CSI driver that created the snapshot.
+    pub driver: String,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct VolumeSnapshotContentStatus {
+    /// The provider's snapshot ID, e.g. `snap-0123456789cafe0` on AWS.
+    pub snapshot_handle: Option<String>,
+}
+
+/// VolumeSnapshots are tagged through their bound VolumeSnapshotContent.
+///
+/// The snapshot carries the source PVC's labels, with the VolumeSnapshot's own
+/// labels taking precedence. On AWS, the snapshot is in its source volume's
+/// region and account. A source PVC that has since been deleted just
+/// contributes no labels, and leaves the cloud client's own region.
+impl CloudTaggable for VolumeSnapshot {
+    fn resolve_cloud_resource(
+        &self,
+        client: &Client,
+        stores: &Stores,
+        config: &Config,
+    ) -> impl Future<Output = Result<Option<CloudResource>, Error>> + Send {
+        let content_name = self
+            .status
+            .as_ref()
+            .and_then(|s| s.bound_volume_snapshot_content_name.clone());
+        let pvc_name = self.spec.source.persistent_volume_claim_name.clone();
+        let namespace = self.metadata.namespace.clone().unwrap_or_default();
+        let labels = self.metadata.labels.clone().unwrap_or_default();
+        let account_roles = !config.aws_account_roles.is_empty();
+        let client = client.clone();
+        let stores = stores.clone();
+
+        async move {
+            let Some(content_name) = content_name else {
+                // The snapshot controller hasn't created the content yet.
+                return Ok(None);
+            };
+
+            let contents: Api<VolumeSnapshotContent> = Api::all(client.clone());
+            let content = contents.get(&content_name).await?;
+
+            let Some(snapshot_handle) = content.status.and_then(|s| s.snapshot_handle) else {
+                // The CSI driver hasn't cut the snapshot yet.
+                return Ok(None);
+            };
+            let provider = provider_from_csi_driver(&content.spec.driver);
+
+            let pvc = match pvc_name {
+                Some(pvc_name) => {
+                    let pvcs: Api<PersistentVolumeClaim> =
+                        Api::namespaced(client.clone(), &namespace);
+                    pvcs.get_opt(&pvc_name).await?
+                }
+                None => None,
+            };
+            let pv_name = pvc
+                .as_ref()
+                .and_then(|pvc| pvc.spec.as_ref())
+                .and_then(|spec| spec.volume_name.clone());
+            let mut merged = pvc.and_then(|pvc| pvc.metadata.labels).unwrap_or_default();
+            merged.extend(labels);
+
+            let location = match pv_name {
+                Some(pv_name) if provider == CloudProvider::Aws => {
+                    let pvs: Api<PersistentVolume> = Api::all(client.clone());
+                    match pvs.get_opt(&pv_name).await? {
+                        Some(pv) => {
+                            let account = if account_roles {
+                                aws_account(&client, &stores, &pv).await?
+                            } else {
+                                None
+                            };
+                            volume_location(&pv, provider, account)
+                        }
+                        None => Location::default(),
+                    }
+                }
+                _ => Location::default(),
+            };
+
+            tracing::debug!(%snapshot_handle, "Found snapshot");
+
+            Ok(Some(CloudResource {
+                provider,
+                kind: ResourceKind::Snapshot,
+                resource_id: snapshot_handle,
+                location,
+                labels: merged,
+            }))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::{Request, Response, StatusCode};
+    use k8s_openapi::api::core::v1::{
+        CSIPersistentVolumeSource, NodeSelector, NodeSelectorRequirement, NodeSelectorTerm,
+        PersistentVolumeClaimSpec, PersistentVolumeSpec, VolumeNodeAffinity,
+    };
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
+    use kube::client::Body;
+    use std::collections::BTreeMap;
+    use tower_test::mock;
+
+    type Handle = mock::Handle<Request<Body>, Response<Body>>;
+
+    fn mock_client() -> (Client, Handle) {
+        let (mock_service, handle) = mock::pair::<Request<Body>, Response<Body>>();
+        (Client::new(mock_service, "default"), handle)
+    }
+
+    fn ok<T: Serialize>(body: &T) -> Response<Body> {
+        Response::builder()
+            .status(StatusCode::OK)
+            .body(Body::from(serde_json::to_vec(body).unwrap()))
+            .unwrap()
+    }
+
+    fn not_found() -> Response<Body> {
+        Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Body::from(
+                r#"{"kind":"Status","status":"Failure","reason":"NotFound","code":404}"#
+                    .as_bytes()
+                    .to_vec(),
+            ))
+            .unwrap()
+    }
+
+    async fn respond(handle: &mut Handle, path_contains: &str, response: Response<Body>) {
+        let (request, send) = handle.next_request().await.expect("expected a request");
+        assert!(
+            request.uri().path().contains(path_contains),
+            "unexpected request {}",
+            request.uri()
+        );
+        send.send_response(response);
+    }
+
+    fn mock_snapshot(content_name: Option<&str>) -> VolumeSnapshot {
+        let mut snapshot = VolumeSnapshot::new(
+            "nightly",
+            VolumeSnapshotSpec {
+                source: VolumeSnapshotSource {
+                    persistent_volume_claim_name: Some("data".into()),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        );
+        snapshot.metadata.namespace = Some("default".into());
+        snapshot.metadata.labels = Some(BTreeMap::from([("schedule".into(), "nightly".into())]));
+        snapshot.status = Some(VolumeSnapshotStatus {
+            bound_volume_snapshot_content_name: content_name.map(Into::into),
+            ..Default::default()
+        });
+        snapshot
+    }
+
+    fn mock_content(driver: &str, handle: Option<&str>) -> VolumeSnapshotContent {
+        let mut content = VolumeSnapshotContent::new(
+            "snapcontent-abc",
+            VolumeSnapshotContentSpec {
+                driver: driver.into(),
+            },
+        );
+        content.status = Some(VolumeSnapshotContentStatus {
+            snapshot_handle: handle.map(Into::into),
+        });
+        content
+    }
+
+    fn mock_pvc() -> PersistentVolumeClaim {
+        PersistentVolumeClaim {
+            metadata: ObjectMeta {
+                name: Some("data".into()),
+                namespace: Some("default".into()),
+                labels: Some(BTreeMap::from([
+                    ("team".into(), "payments".into()),
+                    ("schedule".into(), "hourly".into()),
+                ])),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn unbound_snapshot_returns_none() {
+        let (client, _handle) = mock_client();
+
+        let result = mock_snapshot(None)
+            .resolve_cloud_resource(&client, &Stores::default(), &Config::default())
+            .await
+            .unwrap();
+
+        assert!(result.is_none());
+    }
+
+    #[tokio::test]
+    async fn content_without_handle_returns_none() {
+        let (client, mut handle) = mock_client();
+
+        tokio::spawn(async move {
+            respond(
+                &mut handle,
+                "volumesnapshotcontents/snapcontent-abc",
+                ok(&mock_content("ebs.csi.aws.com", None)),
+            )
+            .await;
+        });
+
+        let result = mock_snapshot(Some("snapcontent-abc"))
+            .resolve_cloud_resource(&client, &Stores::default(), &Config::default())
+            .await
+            .unwrap();
+
+        assert!(result.is_none());
+    }
+
+    #[tokio::test]
+    async fn ready_snapshot_inherits_pvc_labels() {
+        let (client, mut handle) = mock_client();
+
+        tokio::spawn(async move {
+            respond(
+                &mut handle,
+                "volumesnapshotcontents/snapcontent-abc",
+                ok(&mock_content(
+                    "ebs.csi.aws.com",
+                    Some("snap-0123456789cafe0"),
+                )),
+            )
+            .await;
+            respond(
+                &mut handle,
+                "namespaces/default/persistentvolumeclaims/data",
+                ok(&mock_pvc()),
+            )
+            .await;
+        });
+
+        let cr = mock_snapshot(Some("snapcontent-abc"))
+            .resolve_cloud_resource(&client, &Stores::default(), &Config::default())
+            .await
+            .unwrap()
+            .expect("expected CloudResource");
+
+        assert_eq!(cr.provider, CloudProvider::Aws);
+        assert_eq!(cr.kind, ResourceKind::Snapshot);
+        assert_eq!(cr.resource_id, "snap-0123456789cafe0");
+        assert_eq!(
+            cr.labels,
+            BTreeMap::from([
+                ("team".into(), "payments".into()),
+                ("schedule".into(), "nightly".into()),
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn aws_snapshot_is_in_its_source_volume_region() {
+        let (client, mut handle) = mock_client();
+        let mut pvc = mock_pvc();
+        pvc.spec = Some(PersistentVolumeClaimSpec {
+            volume_name: Some("pv-data".into()),
+            ..Default::default()
+        });
+        let pv = PersistentVolume {
+            metadata: ObjectMeta {
+                name: Some("pv-data".into()),
+                ..Default::default()
+            },
+            spec: Some(PersistentVolumeSpec {
+                csi: Some(CSIPersistentVolumeSource {
+                    driver: "ebs.csi.aws.com".into(),
+                    volume_handle: "vol-0123456789cafe0".into(),
+                    ..Default::default()
+                }),
+                node_affinity: Some(VolumeNodeAffinity {
+                    required: Some(NodeSelector {
+                        node_selector_terms: vec![NodeSelectorTerm {
+                            match_expressions: Some(vec![NodeSelectorRequirement {
+                                key: "topology.kubernetes.io/zone".into(),
+                                operator: "In".into(),
+                                values: Some(vec!["eu-west-2a".into()]),
+                            }]),
+                            ..Default::default()
+                        }],
+                    }),
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        tokio::spawn(async move {
+            respond(
+                &mut handle,
+                "volumesnapshotcontents/snapcontent-abc",
+                ok(&mock_content(
+                    "ebs.csi.aws.com",
+                    Some("snap-0123456789cafe0"),
+                )),
+            )
+            .await;
+            respond(&mut handle, "persistentvolumeclaims/data", ok(&pvc)).await;
+            respond(&mut handle, "persistentvolumes/pv-data", ok(&pv)).await;
+        });
+
+        let cr = mock_snapshot(Some("snapcontent-abc"))
+            .resolve_cloud_resource(&client, &Stores::default(), &Config::default())
+            .await
+            .unwrap()
+            .expect("expected CloudResource");
+
+        assert_eq!(cr.resource_id, "snap-0123456789cafe0");
+        assert_eq!(cr.location.region.as_deref(), Some("eu-west-2"));
+        assert_eq!(cr.location.account, None);
+    }
+
+    #[tokio::test]
+    async fn deleted_source_pvc_is_not_an_error() {
+        let (client, mut handle) = mock_client();
+
+        tokio::spawn(async move {
+            respond(
+                &mut handle,
+                "volumesnapshotcontents/snapcontent-abc",
+                ok(&mock_content(
+                    "pd.csi.storage.gke.io",
+                    Some("projects/p/global/snapshots/snapshot-abc"),
+                )),
+            )
+            .await;
+            respond(&mut handle, "persistentvolumeclaims/data", not_found()).await;
+        });
+
+        let cr = mock_snapshot(Some("snapcontent-abc"))
+            .resolve_cloud_resource(&client, &Stores::default(), &Config::default())
+            .await
+            .unwrap()
+            .expect("expected CloudResource");
+
+        assert_eq!(cr.provider, CloudProvider::Gcp);
+        assert_eq!(cr.resource_id, "projects/p/global/snapshots/snapshot-abc");
+        assert_eq!(
+            cr.labels,
+            BTreeMap::from([("schedule".into(), "nightly".into())])
+        );
+    }
+}
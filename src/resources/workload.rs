@@ -0,0 +1,362 @@
+//! Finding the workload that uses a PVC.
+//!
+//! A claim is linked to its workload either directly, through an owner
+//! reference (StatefulSet claims with a `Delete` retention policy, generic
+//! ephemeral volumes owned by their Pod), or through a Pod that mounts it.
+//! From there, controller owner references are followed up to the top-level
+//! workload, e.g. Pod → ReplicaSet → Deployment.
+
+use crate::error::Error;
+use crate::labels::KeyPattern;
+use crate::stores::Stores;
+use k8s_openapi::api::apps::v1::{DaemonSet, Deployment, ReplicaSet, StatefulSet};
+use k8s_openapi::api::batch::v1::{CronJob, Job};
+use k8s_openapi::api::core::v1::{PersistentVolumeClaim, Pod};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{ObjectMeta, OwnerReference};
+use kube::{Api, Client, ResourceExt};
+use std::collections::BTreeMap;
+
+/// Guards against owner reference cycles, which the API server doesn't prevent.
+const MAX_OWNER_DEPTH: usize = 5;
+
+/// Labels of the workload using `pvc` that match any of `patterns`.
+///
+/// A claim that no workload uses contributes no labels.
+pub(super) async fn workload_labels(
+    client: Client,
+    stores: &Stores,
+    pvc: &PersistentVolumeClaim,
+    patterns: &[KeyPattern],
+) -> Result<BTreeMap<String, String>, Error> {
+    let Some(workload) = find_workload(client, stores, pvc).await? else {
+        return Ok(BTreeMap::new());
+    };
+
+    tracing::debug!(
+        pvc = %pvc.name_any(),
+        workload = %workload.name.as_deref().unwrap_or_default(),
+        "Found workload"
+    );
+
+    Ok(workload
+        .labels
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|(k, _)| patterns.iter().any(|p| p.matches(k)))
+        .collect())
+}
+
+async fn find_workload(
+    client: Client,
+    stores: &Stores,
+    pvc: &PersistentVolumeClaim,
+) -> Result<Option<ObjectMeta>, Error> {
+    let namespace = pvc.namespace().unwrap_or_default();
+
+    let start = match controller_owner(&pvc.metadata) {
+        Some(owner) => owner_metadata(client.clone(), &namespace, owner).await?,
+        None => {
+            stores
+                .mounting_pod(&client, &namespace, &pvc.name_any())
+                .await?
+        }
+    };
+    let Some(mut workload) = start else {
+        return Ok(None);
+    };
+
+    for _ in 0..MAX_OWNER_DEPTH {
+        let Some(owner) = controller_owner(&workload) else {
+            break;
+        };
+        match owner_metadata(client.clone(), &namespace, owner).await? {
+            Some(meta) => workload = meta,
+            None => break,
+        }
+    }
+
+    Ok(Some(workload))
+}
+
+fn controller_owner(meta: &ObjectMeta) -> Option<&OwnerReference> {
+    meta.owner_references
+        .as_ref()?
+        .iter()
+        .find(|o| o.controller == Some(true))
+}
+
+/// Fetch the metadata of an owner, for the owner kinds that make up workloads.
+///
+/// Returns `None` for other kinds, and for owners that no longer exist,
+/// including those replaced by a new object of the same name.
+async fn owner_metadata(
+    client: Client,
+    namespace: &str,
+    owner: &OwnerReference,
+) -> Result<Option<ObjectMeta>, Error> {
+    let name = owner.name.as_str();
+    let meta = match (owner.api_version.as_str(), owner.kind.as_str()) {
+        ("v1", "Pod") => Api::<Pod>::namespaced(client, namespace)
+            .get_metadata_opt(name)
+            .await?
+            .map(|m| m.metadata),
+        ("apps/v1", "ReplicaSet") => Api::<ReplicaSet>::namespaced(client, namespace)
+            .get_metadata_opt(name)
+            .await?
+            .map(|m| m.metadata),
+        ("apps/v1", "Deployment") => Api::<Deployment>::namespaced(client, namespace)
+            .get_metadata_opt(name)
+            .await?
+            .map(|m| m.metadata),
+        ("apps/v1", "StatefulSet") => Api::<StatefulSet>::namespaced(client, namespace)
+            .get_metadata_opt(name)
+            .await?
+            .map(|m| m.metadata),
+        ("apps/v1", "DaemonSet") => Api::<DaemonSet>::namespaced(client, namespace)
+            .get_metadata_opt(name)
+            .await?
+            .map(|m| m.metadata),
+        ("batch/v1", "Job") => Api::<Job>::namespaced(client, namespace)
+            .get_metadata_opt(name)
+            .await?
+            .map(|m| m.metadata),
+        ("batch/v1", "CronJob") => Api::<CronJob>::namespaced(client, namespace)
+            .get_metadata_opt(name)
+            .await?
+            .map(|m| m.metadata),
+        (api_version, kind) => {
+            tracing::debug!(%api_version, %kind, %name, "Not following unsupported owner kind");
+            None
+        }
+    };
+    Ok(meta.filter(|meta| {
+        let same = meta.uid.as_deref() == Some(owner.uid.as_str());
+        if !same {
+            tracing::debug!(kind = %owner.kind, %name, "Owner was replaced, not following");
+        }
+        same
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::{Request, Response, StatusCode};
+    use k8s_openapi::api::core::v1::{PersistentVolumeClaimVolumeSource, PodSpec, Volume};
+    use kube::client::Body;
+    use serde_json::json;
+    use tower_test::mock;
+
+    type Handle = mock::Handle<Request<Body>, Response<Body>>;
+
+    fn mock_client() -> (Client, Handle) {
+        let (mock_service, handle) = mock::pair::<Request<Body>, Response<Body>>();
+        (Client::new(mock_service, "default"), handle)
+    }
+
+    async fn respond(handle: &mut Handle, path: &str, body: serde_json::Value) {
+        let (request, send) = handle.next_request().await.expect("expected a request");
+        assert_eq!(request.uri().path(), path);
+        send.send_response(
+            Response::builder()
+                .status(StatusCode::OK)
+                .body(Body::from(serde_json::to_vec(&body).unwrap()))
+                .unwrap(),
+        );
+    }
+
+    fn owner(api_version: &str, kind: &str, name: &str) -> OwnerReference {
+        OwnerReference {
+            api_version: api_version.into(),
+            kind: kind.into(),
+            name: name.into(),
+            uid: format!("{name}-uid"),
+            controller: Some(true),
+            ..Default::default()
+        }
+    }
+
+    fn partial_metadata(meta: ObjectMeta) -> serde_json::Value {
+        json!({
+            "apiVersion": "meta.k8s.io/v1",
+            "kind": "PartialObjectMetadata",
+            "metadata": meta,
+        })
+    }
+
+    fn mock_pvc(owner_references: Option<Vec<OwnerReference>>) -> PersistentVolumeClaim {
+        PersistentVolumeClaim {
+            metadata: ObjectMeta {
+                name: Some("data".into()),
+                namespace: Some("default".into()),
+                owner_references,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn patterns() -> Vec<KeyPattern> {
+        vec![KeyPattern::parse("app.kubernetes.io/*").unwrap()]
+    }
+
+    #[tokio::test]
+    async fn follows_mounting_pod_to_deployment() {
+        let (client, mut handle) = mock_client();
+
+        tokio::spawn(async move {
+            let unrelated = Pod {
+                metadata: ObjectMeta {
+                    name: Some("other".into()),
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            let pod = Pod {
+                metadata: ObjectMeta {
+                    name: Some("api-7d9c-x2x4z".into()),
+                    owner_references: Some(vec![owner("apps/v1", "ReplicaSet", "api-7d9c")]),
+                    ..Default::default()
+                },
+                spec: Some(PodSpec {
+                    volumes: Some(vec![Volume {
+                        name: "data".into(),
+                        persistent_volume_claim: Some(PersistentVolumeClaimVolumeSource {
+                            claim_name: "data".into(),
+                            ..Default::default()
+                        }),
+                        ..Default::default()
+                    }]),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            };
+            respond(
+                &mut handle,
+                "/api/v1/namespaces/default/pods",
+                json!({
+                    "apiVersion": "v1",
+                    "kind": "PodList",
+                    "metadata": {},
+                    "items": [unrelated, pod],
+                }),
+            )
+            .await;
+            respond(
+                &mut handle,
+                "/apis/apps/v1/namespaces/default/replicasets/api-7d9c",
+                partial_metadata(ObjectMeta {
+                    name: Some("api-7d9c".into()),
+                    uid: Some("api-7d9c-uid".into()),
+                    owner_references: Some(vec![owner("apps/v1", "Deployment", "api")]),
+                    ..Default::default()
+                }),
+            )
+            .await;
+            respond(
+                &mut handle,
+                "/apis/apps/v1/namespaces/default/deployments/api",
+                partial_metadata(ObjectMeta {
+                    name: Some("api".into()),
+                    uid: Some("api-uid".into()),
+                    labels: Some(BTreeMap::from([
+                        ("app.kubernetes.io/name".into(), "api".into()),
+                        ("helm.sh/chart".into(), "api-1.0.0".into()),
+                    ])),
+                    ..Default::default()
+                }),
+            )
+            .await;
+        });
+
+        let labels = workload_labels(client, &Stores::default(), &mock_pvc(None), &patterns())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            labels,
+            BTreeMap::from([("app.kubernetes.io/name".into(), "api".into())])
+        );
+    }
+
+    #[tokio::test]
+    async fn follows_owner_reference_to_statefulset() {
+        let (client, mut handle) = mock_client();
+
+        tokio::spawn(async move {
+            respond(
+                &mut handle,
+                "/apis/apps/v1/namespaces/default/statefulsets/db",
+                partial_metadata(ObjectMeta {
+                    name: Some("db".into()),
+                    uid: Some("db-uid".into()),
+                    labels: Some(BTreeMap::from([(
+                        "app.kubernetes.io/name".into(),
+                        "postgres".into(),
+                    )])),
+                    ..Default::default()
+                }),
+            )
+            .await;
+        });
+
+        let pvc = mock_pvc(Some(vec![owner("apps/v1", "StatefulSet", "db")]));
+        let labels = workload_labels(client, &Stores::default(), &pvc, &patterns())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            labels,
+            BTreeMap::from([("app.kubernetes.io/name".into(), "postgres".into())])
+        );
+    }
+
+    #[tokio::test]
+    async fn unused_claim_has_no_workload_labels() {
+        let (client, mut handle) = mock_client();
+
+        tokio::spawn(async move {
+            respond(
+                &mut handle,
+                "/api/v1/namespaces/default/pods",
+                json!({"apiVersion": "v1", "kind": "PodList", "metadata": {}, "items": []}),
+            )
+            .await;
+        });
+
+        let labels = workload_labels(client, &Stores::default(), &mock_pvc(None), &patterns())
+            .await
+            .unwrap();
+
+        assert!(labels.is_empty());
+    }
+
+    #[tokio::test]
+    async fn does_not_follow_replaced_owner() {
+        let (client, mut handle) = mock_client();
+
+        tokio::spawn(async move {
+            respond(
+                &mut handle,
+                "/apis/apps/v1/namespaces/default/statefulsets/db",
+                partial_metadata(ObjectMeta {
+                    name: Some("db".into()),
+                    uid: Some("recreated-uid".into()),
+                    labels: Some(BTreeMap::from([(
+                        "app.kubernetes.io/name".into(),
+                        "other".into(),
+                    )])),
+                    ..Default::default()
+                }),
+            )
+            .await;
+        });
+
+        let pvc = mock_pvc(Some(vec![owner("apps/v1", "StatefulSet", "db")]));
+        let labels = workload_labels(client, &Stores::default(), &pvc, &patterns())
+            .await
+            .unwrap();
+
+        assert!(labels.is_empty());
+    }
+}
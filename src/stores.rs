@@ -0,0 +1,557 @@
+//! Reflector caches of cluster objects that reconciles read often.
+//!
+//! Every reconcile reads its namespace (for the enabled annotation and
+//! namespace labels), and resolving a claim may need the Pods in its namespace
+//! (to find the workload using it) and its volume's StorageClass (for the AWS
+//! account it's in). These change far less often than resources
+//! are reconciled, so while stores are watched the reads are served from
+//! memory. Lookups fall back to the API server for stores that aren't watched,
+//! e.g. `workloadLabels` enabled by a config reload after startup, and for
+//! objects not in the store yet.
+
+use crate::config::Config;
+use crate::error::Error;
+use crate::reconciler::ENABLED_ANNOTATION;
+use futures::StreamExt;
+use futures::future::{BoxFuture, join_all};
+use k8s_openapi::NamespaceResourceScope;
+use k8s_openapi::api::core::v1::{Namespace, Pod, PodSpec};
+use k8s_openapi::api::storage::v1::StorageClass;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
+use kube::api::{ListParams, PartialObjectMeta};
+use kube::runtime::reflector::{self, ObjectRef, Store};
+use kube::runtime::{WatchStreamExt, metadata_watcher, watcher};
+use kube::{Api, Client, Resource, ResourceExt};
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::sync::Mutex;
+
+/// One `Api` per watched namespace, or a single cluster-wide `Api` if none are configured.
+pub fn scoped_apis<K>(client: &Client, namespaces: &[String]) -> Vec<Api<K>>
+where
+    K: Resource<Scope = NamespaceResourceScope, DynamicType = ()>,
+{
+    if namespaces.is_empty() {
+        vec![Api::all(client.clone())]
+    } else {
+        namespaces
+            .iter()
+            .map(|ns| Api::namespaced(client.clone(), ns))
+            .collect()
+    }
+}
+
+/// Reflector stores for the objects reconciles look up.
+///
+/// Each kind has one store per watched namespace, or a single cluster-wide
+/// one, and none when it isn't watched.
+#[derive(Clone, Default)]
+pub struct Stores {
+    /// Namespace metadata.
+    namespaces: Vec<Store<PartialObjectMeta<Namespace>>>,
+    /// Pods, trimmed to what finding a claim's workload needs.
+    pods: Vec<Store<Pod>>,
+    /// StorageClass metadata.
+    storage_classes: Vec<Store<PartialObjectMeta<StorageClass>>>,
+}
+
+impl Stores {
+    /// Start watching the objects that `config` makes reconciles look up.
+    ///
+    /// Returns the stores and a future keeping them up to date, which must be
+    /// polled for lookups to be served from them.
+    pub fn watch(client: &Client, config: &Config) -> (Self, impl Future<Output = ()> + use<>) {
+        let mut stores = Self::default();
+        let mut reflectors: Vec<BoxFuture<'static, ()>> = Vec::new();
+
+        for selector in namespace_selectors(&config.watch_namespaces) {
+            let (store, writer) = reflector::store();
+            let events =
+                metadata_watcher(Api::<Namespace>::all(client.clone()), selector).default_backoff();
+            reflectors.push(drain("namespaces", reflector::reflector(writer, events)));
+            stores.namespaces.push(store);
+        }
+
+        if !config.workload_labels.is_empty() {
+            for api in scoped_apis::<Pod>(client, &config.watch_namespaces) {
+                let (store, writer) = reflector::store();
+                let events = watcher(api, watcher::Config::default())
+                    .modify(trim_pod)
+                    .default_backoff();
+                reflectors.push(drain("pods", reflector::reflector(writer, events)));
+                stores.pods.push(store);
+            }
+        }
+
+        // StorageClasses are only read for their AWS account annotation.
+        if !config.aws_account_roles.is_empty() {
+            let (store, writer) = reflector::store();
+            let events = metadata_watcher(
+                Api::<StorageClass>::all(client.clone()),
+                watcher::Config::default(),
+            )
+            .default_backoff();
+            reflectors.push(drain(
+                "storageclasses",
+                reflector::reflector(writer, events),
+            ));
+            stores.storage_classes.push(store);
+        }
+
+        (stores, async move {
+            join_all(reflectors).await;
+        })
+    }
+
+    /// The metadata of a namespace, or `None` if it doesn't exist.
+    pub async fn namespace(
+        &self,
+        client: &Client,
+        name: &str,
+    ) -> Result<Option<ObjectMeta>, Error> {
+        if ready(&self.namespaces).await {
+            let key = ObjectRef::new(name);
+            if let Some(ns) = self.namespaces.iter().find_map(|store| store.get(&key)) {
+                return Ok(Some(ns.metadata.clone()));
+            }
+        }
+
+        let namespaces: Api<Namespace> = Api::all(client.clone());
+        Ok(namespaces
+            .get_metadata_opt(name)
+            .await?
+            .map(|ns| ns.metadata))
+    }
+
+    /// The metadata of a StorageClass, or `None` if it doesn't exist.
+    pub async fn storage_class(
+        &self,
+        client: &Client,
+        name: &str,
+    ) -> Result<Option<ObjectMeta>, Error> {
+        if ready(&self.storage_classes).await {
+            let key = ObjectRef::new(name);
+            if let Some(class) = self
+                .storage_classes
+                .iter()
+                .find_map(|store| store.get(&key))
+            {
+                return Ok(Some(class.metadata.clone()));
+            }
+        }
+
+        let classes: Api<StorageClass> = Api::all(client.clone());
+        Ok(classes
+            .get_metadata_opt(name)
+            .await?
+            .map(|class| class.metadata))
+    }
+
+    /// The metadata of the first Pod in `namespace` with a volume backed by
+    /// the claim.
+    pub async fn mounting_pod(
+        &self,
+        client: &Client,
+        namespace: &str,
+        claim_name: &str,
+    ) -> Result<Option<ObjectMeta>, Error> {
+        if ready(&self.pods).await {
+            return Ok(self
+                .pods
+                .iter()
+                .flat_map(Store::state)
+                .find(|pod| {
+                    pod.namespace().as_deref() == Some(namespace) && mounts_claim(pod, claim_name)
+                })
+                .map(|pod| pod.metadata.clone()));
+        }
+
+        let pods: Api<Pod> = Api::namespaced(client.clone(), namespace);
+        let list = pods.list(&ListParams::default()).await?;
+        Ok(list
+            .items
+            .into_iter()
+            .find(|pod| mounts_claim(pod, claim_name))
+            .map(|pod| pod.metadata))
+    }
+}
+
+/// Watches of the namespaces in `names`, one each since field selectors can't
+/// match several names, or of every namespace if `names` is empty.
+fn namespace_selectors(names: &[String]) -> Vec<watcher::Config> {
+    if names.is_empty() {
+        return vec![watcher::Config::default()];
+    }
+    names
+        .iter()
+        .map(|name| watcher::Config::default().fields(&format!("metadata.name={name}")))
+        .collect()
+}
+
+/// Whether every store has been populated. False when there are none, or a
+/// reflector has stopped, so the caller reads the API server instead.
+async fn ready<K>(stores: &[Store<K>]) -> bool
+where
+    K: kube::runtime::reflector::Lookup + Clone + 'static,
+    K::DynamicType: Eq + std::hash::Hash + Clone,
+{
+    if stores.is_empty() {
+        return false;
+    }
+    for store in stores {
+        if store.wait_until_ready().await.is_err() {
+            return false;
+        }
+    }
+    true
+}
+
+/// Run a reflector's event stream, logging watch errors, which it retries.
+fn drain<S, K>(kind: &'static str, events: S) -> BoxFuture<'static, ()>
+where
+    S: futures::Stream<Item = Result<watcher::Event<K>, watcher::Error>> + Send + 'static,
+    K: Send + 'static,
+{
+    Box::pin(events.for_each(move |event| async move {
+        if let Err(e) = event {
+            tracing::warn!(%kind, %e, "Watch error");
+        }
+    }))
+}
+
+/// A `Controller::watches` mapper from namespaces to the objects in `store`
+/// inside each namespace whose labels or [`ENABLED_ANNOTATION`] changed since
+/// it was last seen, as they may need tagging differently. A namespace seen
+/// for the first time maps to nothing: its objects reconcile from their own
+/// watch, and a re-list after a watch restart doesn't re-tag everything.
+pub fn in_changed_namespace<K>(
+    store: Store<K>,
+) -> impl Fn(Namespace) -> Vec<ObjectRef<K>> + Send + Sync + 'static
+where
+    K: Resource<DynamicType = ()> + Clone + Send + Sync + 'static,
+{
+    let seen: Mutex<HashMap<String, TaggingInputs>> = Mutex::default();
+    move |ns| {
+        let name = ns.name_any();
+        let inputs = TaggingInputs::of(&ns.metadata);
+        {
+            let mut seen = seen.lock().unwrap_or_else(|e| e.into_inner());
+            if ns.metadata.deletion_timestamp.is_some() {
+                seen.remove(&name);
+                return Vec::new();
+            }
+            match seen.insert(name.clone(), inputs.clone()) {
+                Some(previous) if previous != inputs => {}
+                _ => return Vec::new(),
+            }
+        }
+        tracing::debug!(namespace = %name, "Namespace changed, re-tagging");
+        store
+            .state()
+            .iter()
+            .filter(|obj| obj.namespace().as_deref() == Some(name.as_str()))
+            .map(|obj| ObjectRef::from_obj(obj.as_ref()))
+            .collect()
+    }
+}
+
+/// What of a namespace affects how the resources in it are tagged.
+#[derive(Clone, PartialEq)]
+struct TaggingInputs {
+    labels: BTreeMap<String, String>,
+    enabled: Option<String>,
+}
+
+impl TaggingInputs {
+    fn of(meta: &ObjectMeta) -> Self {
+        Self {
+            labels: meta.labels.clone().unwrap_or_default(),
+            enabled: meta
+                .annotations
+                .as_ref()
+                .and_then(|a| a.get(ENABLED_ANNOTATION))
+                .cloned(),
+        }
+    }
+}
+
+/// Drop everything but the metadata and claim volumes from a Pod, so caching
+/// every Pod stays cheap.
+fn trim_pod(pod: &mut Pod) {
+    pod.metadata.managed_fields = None;
+    pod.metadata.annotations = None;
+    pod.status = None;
+    let volumes = pod
+        .spec
+        .take()
+        .and_then(|spec| spec.volumes)
+        .map(|volumes| {
+            volumes
+                .into_iter()
+                .filter(|v| v.persistent_volume_claim.is_some())
+                .collect()
+        });
+    pod.spec = Some(PodSpec {
+        volumes,
+        ..Default::default()
+    });
+}
+
+fn mounts_claim(pod: &Pod, claim_name: &str) -> bool {
+    pod.spec
+        .as_ref()
+        .and_then(|s| s.volumes.as_ref())
+        .is_some_and(|volumes| {
+            volumes.iter().any(|v| {
+                v.persistent_volume_claim
+                    .as_ref()
+                    .is_some_and(|c| c.claim_name == claim_name)
+            })
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::{Request, Response};
+    use k8s_openapi::api::core::v1::{
+        Container, PersistentVolumeClaim, PersistentVolumeClaimVolumeSource, Volume,
+    };
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::Time;
+    use kube::client::Body;
+    use tower_test::mock;
+
+    fn pod(namespace: &str, name: &str, claim: &str) -> Pod {
+        Pod {
+            metadata: ObjectMeta {
+                name: Some(name.into()),
+                namespace: Some(namespace.into()),
+                ..Default::default()
+            },
+            spec: Some(PodSpec {
+                containers: vec![Container {
+                    name: "app".into(),
+                    ..Default::default()
+                }],
+                volumes: Some(vec![Volume {
+                    name: "data".into(),
+                    persistent_volume_claim: Some(PersistentVolumeClaimVolumeSource {
+                        claim_name: claim.into(),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                }]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn mounting_pod_is_served_from_store() {
+        let (store, mut writer) = reflector::store();
+        writer.apply_watcher_event(&watcher::Event::Init);
+        for pod in [
+            pod("other", "web-0", "data"),
+            pod("default", "db-0", "data"),
+        ] {
+            writer.apply_watcher_event(&watcher::Event::InitApply(pod));
+        }
+        writer.apply_watcher_event(&watcher::Event::InitDone);
+        let stores = Stores {
+            pods: vec![store],
+            ..Default::default()
+        };
+
+        // No request may be made: the mock panics on drop if one is pending.
+        let (mock_service, handle) = mock::pair::<Request<Body>, Response<Body>>();
+        let client = Client::new(mock_service, "default");
+
+        let found = stores
+            .mounting_pod(&client, "default", "data")
+            .await
+            .unwrap();
+        assert_eq!(found.and_then(|m| m.name).as_deref(), Some("db-0"));
+        let missing = stores
+            .mounting_pod(&client, "default", "logs")
+            .await
+            .unwrap();
+        assert!(missing.is_none());
+        drop(handle);
+    }
+
+    #[test]
+    fn trim_pod_keeps_claim_volumes() {
+        let mut trimmed = pod("default", "db-0", "data");
+        trimmed
+            .spec
+            .as_mut()
+            .unwrap()
+            .volumes
+            .as_mut()
+            .unwrap()
+            .push(Volume {
+                name: "config".into(),
+                ..Default::default()
+            });
+        trim_pod(&mut trimmed);
+
+        let spec = trimmed.spec.unwrap();
+        assert!(spec.containers.is_empty());
+        assert_eq!(spec.volumes.unwrap().len(), 1);
+        assert_eq!(trimmed.metadata.name.as_deref(), Some("db-0"));
+    }
+
+    fn namespace(
+        name: &str,
+        labels: &[(&str, &str)],
+        enabled: Option<&str>,
+    ) -> PartialObjectMeta<Namespace> {
+        PartialObjectMeta {
+            metadata: ObjectMeta {
+                name: Some(name.into()),
+                labels: Some(
+                    labels
+                        .iter()
+                        .map(|(k, v)| (k.to_string(), v.to_string()))
+                        .collect(),
+                ),
+                annotations: enabled
+                    .map(|e| BTreeMap::from([(ENABLED_ANNOTATION.to_string(), e.to_string())])),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn claim(namespace: &str, name: &str) -> PersistentVolumeClaim {
+        PersistentVolumeClaim {
+            metadata: ObjectMeta {
+                name: Some(name.into()),
+                namespace: Some(namespace.into()),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn namespace_changes_requeue_only_its_objects() {
+        let (store, mut writer) = reflector::store();
+        writer.apply_watcher_event(&watcher::Event::Init);
+        for (ns, name) in [
+            ("team-a", "data-0"),
+            ("team-a", "data-1"),
+            ("team-b", "data-0"),
+        ] {
+            writer.apply_watcher_event(&watcher::Event::InitApply(claim(ns, name)));
+        }
+        writer.apply_watcher_event(&watcher::Event::InitDone);
+        let requeue = in_changed_namespace(store);
+        let ns = |name, labels: &[(&str, &str)], enabled| Namespace {
+            metadata: namespace(name, labels, enabled).metadata,
+            ..Default::default()
+        };
+
+        // New namespaces and unchanged updates requeue nothing.
+        assert!(requeue(ns("team-a", &[("team", "a")], None)).is_empty());
+        assert!(requeue(ns("team-b", &[], None)).is_empty());
+        assert!(requeue(ns("team-a", &[("team", "a")], None)).is_empty());
+
+        let mut requeued: Vec<_> = requeue(ns("team-a", &[("team", "a2")], None))
+            .into_iter()
+            .map(|obj| (obj.namespace.unwrap(), obj.name))
+            .collect();
+        requeued.sort();
+        assert_eq!(
+            requeued,
+            [
+                ("team-a".to_string(), "data-0".to_string()),
+                ("team-a".to_string(), "data-1".to_string()),
+            ]
+        );
+
+        let requeued = requeue(ns("team-b", &[], Some("false")));
+        assert_eq!(requeued.len(), 1);
+        assert_eq!(requeued[0].namespace.as_deref(), Some("team-b"));
+
+        // A namespace being deleted is forgotten rather than re-tagged.
+        let mut deleting = ns("team-b", &[("team", "b")], None);
+        deleting.metadata.deletion_timestamp = Some(Time(jiff::Timestamp::now()));
+        assert!(requeue(deleting).is_empty());
+        assert!(requeue(ns("team-b", &[], None)).is_empty());
+    }
+
+    #[tokio::test]
+    async fn namespace_is_served_from_store() {
+        let (store, mut writer) = reflector::store();
+        writer.apply_watcher_event(&watcher::Event::Init);
+        writer.apply_watcher_event(&watcher::Event::InitApply(namespace(
+            "default",
+            &[("team", "platform")],
+            Some("true"),
+        )));
+        writer.apply_watcher_event(&watcher::Event::InitDone);
+        let stores = Stores {
+            namespaces: vec![store],
+            ..Default::default()
+        };
+
+        // No request may be made: the mock panics on drop if one is pending.
+        let (mock_service, handle) = mock::pair::<Request<Body>, Response<Body>>();
+        let client = Client::new(mock_service, "default");
+
+        let ns = stores.namespace(&client, "default").await.unwrap().unwrap();
+        assert_eq!(ns.labels.unwrap()["team"], "platform");
+        drop(handle);
+    }
+
+    #[tokio::test]
+    async fn storage_class_is_served_from_store() {
+        let (store, mut writer) = reflector::store();
+        writer.apply_watcher_event(&watcher::Event::Init);
+        writer.apply_watcher_event(&watcher::Event::InitApply(PartialObjectMeta::<
+            StorageClass,
+        > {
+            metadata: ObjectMeta {
+                name: Some("gp3".into()),
+                annotations: Some(BTreeMap::from([(
+                    "cloud-tagger.upgrades.dev/aws-account".into(),
+                    "444455556666".into(),
+                )])),
+                ..Default::default()
+            },
+            ..Default::default()
+        }));
+        writer.apply_watcher_event(&watcher::Event::InitDone);
+        let stores = Stores {
+            storage_classes: vec![store],
+            ..Default::default()
+        };
+
+        // No request may be made: the mock panics on drop if one is pending.
+        let (mock_service, handle) = mock::pair::<Request<Body>, Response<Body>>();
+        let client = Client::new(mock_service, "default");
+
+        let class = stores.storage_class(&client, "gp3").await.unwrap().unwrap();
+        assert_eq!(
+            class.annotations.unwrap()["cloud-tagger.upgrades.dev/aws-account"],
+            "444455556666"
+        );
+        drop(handle);
+    }
+
+    #[test]
+    fn watches_only_named_namespaces() {
+        let all = namespace_selectors(&[]);
+        assert_eq!(all.len(), 1);
+        assert_eq!(all[0].field_selector, None);
+
+        let named = namespace_selectors(&["team-a".into(), "team-b".into()]);
+        let selectors: Vec<_> = named.iter().map(|c| c.field_selector.as_deref()).collect();
+        assert_eq!(
+            selectors,
+            [Some("metadata.name=team-a"), Some("metadata.name=team-b")]
+        );
+    }
+}
@@ -0,0 +1,236 @@
+//! The `sync` subcommand: reconcile every PVC once, print a summary and exit.
+//!
+//! Meant for CronJobs and CI, where a long-lived controller isn't wanted.
+//! Exits non-zero if any claim failed.
+
+use crate::cloud::CloudClient;
+use crate::error::Error;
+use crate::reconciler::{Context, Outcome, reconcile_once};
+use crate::stores::scoped_apis;
+use anyhow::{anyhow, bail};
+use futures::{StreamExt, stream};
+use k8s_openapi::api::core::v1::PersistentVolumeClaim;
+use kube::ResourceExt;
+
+/// How many claims one-shot commands work on at once, unless `--concurrency` is given.
+pub const DEFAULT_CONCURRENCY: usize = 10;
+
+/// Parse the value following a `--concurrency` flag.
+pub fn parse_concurrency(value: Option<&String>) -> anyhow::Result<usize> {
+    let value = value.ok_or_else(|| anyhow!("--concurrency needs a value"))?;
+    value
+        .parse()
+        .ok()
+        .filter(|n| *n > 0)
+        .ok_or_else(|| anyhow!("invalid --concurrency '{value}'"))
+}
+
+/// Command line options for `sync`.
+#[derive(Debug, PartialEq)]
+pub struct Options {
+    pub dry_run: bool,
+    /// How many claims are reconciled at once.
+    pub concurrency: usize,
+}
+
+impl Options {
+    pub fn parse(args: &[String]) -> anyhow::Result<Self> {
+        let mut opts = Self {
+            dry_run: false,
+            concurrency: DEFAULT_CONCURRENCY,
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--dry-run" => opts.dry_run = true,
+                "--concurrency" => opts.concurrency = parse_concurrency(args.next())?,
+                other => bail!("unexpected argument '{other}'"),
+            }
+        }
+        Ok(opts)
+    }
+}
+
+/// The result of reconciling one claim.
+struct Row {
+    namespace: String,
+    name: String,
+    result: Result<Outcome, Error>,
+}
+
+impl Row {
+    fn status(&self) -> String {
+        match &self.result {
+            Ok(outcome) => outcome.to_string(),
+            Err(_) => "failed".to_string(),
+        }
+    }
+}
+
+/// Reconcile every PVC in the watch scope once and print a summary table.
+pub async fn run<C: CloudClient>(ctx: &Context<C>, opts: &Options) -> anyhow::Result<()> {
+    let claims = list_claims(ctx).await?;
+    tracing::info!(count = claims.len(), "Syncing PersistentVolumeClaims");
+
+    let mut rows: Vec<Row> = stream::iter(&claims)
+        .map(|pvc| async move {
+            Row {
+                namespace: pvc.namespace().unwrap_or_default(),
+                name: pvc.name_any(),
+                result: reconcile_once(pvc, ctx).await,
+            }
+        })
+        .buffer_unordered(opts.concurrency)
+        .collect()
+        .await;
+    rows.sort_by(|a, b| (&a.namespace, &a.name).cmp(&(&b.namespace, &b.name)));
+
+    print!("{}", summary(&rows));
+
+    let failed = rows.iter().filter(|row| row.result.is_err()).count();
+    if failed > 0 {
+        bail!(
+            "{failed} of {} PersistentVolumeClaim(s) failed to sync",
+            rows.len()
+        );
+    }
+    Ok(())
+}
+
+/// Every PVC in the watch scope.
+pub async fn list_claims<C: CloudClient>(
+    ctx: &Context<C>,
+) -> Result<Vec<PersistentVolumeClaim>, kube::Error> {
+    let config = ctx.config.get();
+    let mut claims = Vec::new();
+    for api in scoped_apis::<PersistentVolumeClaim>(&ctx.client, &config.watch_namespaces) {
+        claims.extend(api.list(&config.list_params()).await?.items);
+    }
+    Ok(claims)
+}
+
+/// Render rows as an aligned table, followed by a count of each status.
+fn summary(rows: &[Row]) -> String {
+    let header = ["NAMESPACE", "NAME", "STATUS", "ERROR"];
+    let lines: Vec<[String; 4]> = rows
+        .iter()
+        .map(|row| {
+            [
+                row.namespace.clone(),
+                row.name.clone(),
+                row.status(),
+                row.result
+                    .as_ref()
+                    .err()
+                    .map(ToString::to_string)
+                    .unwrap_or_default(),
+            ]
+        })
+        .collect();
+
+    let mut widths = header.map(str::len);
+    for line in &lines {
+        for (width, cell) in widths.iter_mut().zip(line) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    let mut out = String::new();
+    let mut push_line = |cells: [&str; 4]| {
+        let line = format!(
+            "{:<w0$}  {:<w1$}  {:<w2$}  {}",
+            cells[0],
+            cells[1],
+            cells[2],
+            cells[3],
+            w0 = widths[0],
+            w1 = widths[1],
+            w2 = widths[2],
+        );
+        out.push_str(line.trim_end());
+        out.push('\n');
+    };
+    push_line(header);
+    for line in &lines {
+        push_line([&line[0], &line[1], &line[2], &line[3]]);
+    }
+
+    let statuses = [
+        Outcome::Tagged,
+        Outcome::Unchanged,
+        Outcome::Skipped,
+        Outcome::NotReady,
+        Outcome::Ignored,
+    ]
+    .map(|outcome| outcome.to_string());
+    let totals: Vec<String> = statuses
+        .iter()
+        .map(String::as_str)
+        .chain(["failed"])
+        .map(|status| {
+            let count = lines.iter().filter(|line| line[2] == status).count();
+            format!("{status}: {count}")
+        })
+        .collect();
+    out.push('\n');
+    out.push_str(&totals.join(", "));
+    out.push('\n');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn parses_options() {
+        assert_eq!(
+            Options::parse(&args(&[])).unwrap(),
+            Options {
+                dry_run: false,
+                concurrency: DEFAULT_CONCURRENCY
+            }
+        );
+        assert_eq!(
+            Options::parse(&args(&["--concurrency", "4", "--dry-run"])).unwrap(),
+            Options {
+                dry_run: true,
+                concurrency: 4
+            }
+        );
+        assert!(Options::parse(&args(&["--concurrency"])).is_err());
+        assert!(Options::parse(&args(&["--concurrency", "0"])).is_err());
+        assert!(Options::parse(&args(&["--force"])).is_err());
+    }
+
+    #[test]
+    fn summary_lists_each_claim_and_totals() {
+        let rows = [
+            Row {
+                namespace: "default".into(),
+                name: "data".into(),
+                result: Ok(Outcome::Tagged),
+            },
+            Row {
+                namespace: "team-a".into(),
+                name: "db".into(),
+                result: Err(Error::CloudApi("access denied".into())),
+            },
+        ];
+
+        assert_eq!(
+            summary(&rows),
+            "\
+NAMESPACE  NAME  STATUS  ERROR
+default    data  tagged
+team-a     db    failed  Cloud API error: access denied
+
+tagged: 1, unchanged: 0, skipped: 0, not ready: 0, ignored: 0, failed: 1
+"
+        );
+    }
+}
@@ -1,4 +1,6 @@
+use crate::config::Config;
 use crate::error::Error;
+use crate::stores::Stores;
 use kube::{Client, Resource};
 use std::collections::BTreeMap;
 use std::future::Future;
@@ -12,12 +14,49 @@ use std::str::FromStr;
 pub struct CloudResource {
     /// The cloud provider that owns this resource.
     pub provider: CloudProvider, // TODO https://github.com/upgrades-dev/k8s-cloud-tagger/issues/85
+    /// What kind of cloud resource this is, which decides the API used to tag it.
+    pub kind: ResourceKind,
     /// Provider-specific resource identifier (e.g. `vol-0abc123`).
     pub resource_id: String,
+    /// Where the resource lives, where `resource_id` alone doesn't say.
+    pub location: Location,
     /// Labels to propagate from Kubernetes to the cloud resource.
     pub labels: BTreeMap<String, String>,
 }
 
+/// The region and account of a cloud resource, for providers whose resource
+/// IDs don't carry them (e.g. bare EBS volume IDs).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Location {
+    /// The resource's region, if known. Clients fall back to their own.
+    pub region: Option<String>,
+    /// The account owning the resource, if known. Clients fall back to their own.
+    pub account: Option<String>,
+}
+
+/// Kinds of cloud resource that can be tagged.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResourceKind {
+    /// A block storage volume, identified by its CSI volume handle.
+    Disk,
+    /// A load balancer, identified by its ingress hostname or IP address.
+    /// The cloud client looks up the underlying resource (ELBv2 load balancer,
+    /// GCP forwarding rule, Azure public IP).
+    LoadBalancer,
+    /// A volume snapshot, identified by its CSI snapshot handle.
+    Snapshot,
+}
+
+impl std::fmt::Display for ResourceKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResourceKind::Disk => write!(f, "disk"),
+            ResourceKind::LoadBalancer => write!(f, "load_balancer"),
+            ResourceKind::Snapshot => write!(f, "snapshot"),
+        }
+    }
+}
+
 /// Supported cloud providers.
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum CloudProvider {
@@ -59,9 +98,19 @@ impl FromStr for CloudProvider {
 
 /// Any Kubernetes resource that can propagate labels to a cloud resource
 pub trait CloudTaggable: Resource<DynamicType = ()> + Clone + Send + Sync + 'static {
+    /// Whether this resource should be tagged at all.
+    ///
+    /// Resources that return `false` are not resolved and not requeued until
+    /// they change (e.g. a Service that isn't `type: LoadBalancer`).
+    fn is_taggable(&self) -> bool {
+        true
+    }
+
     /// Resolve the cloud resource (may require fetching intermediate resources)
     fn resolve_cloud_resource(
         &self,
         client: &Client,
+        stores: &Stores,
+        config: &Config,
     ) -> impl Future<Output = Result<Option<CloudResource>, Error>> + Send;
 }